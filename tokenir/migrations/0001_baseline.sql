@@ -0,0 +1,81 @@
+-- The schema as it stood before versioning. Every statement is written to
+-- also bring a database made by an earlier release up to it.
+
+CREATE TABLE IF NOT EXISTS devs (
+    dev_address TEXT PRIMARY KEY,
+    total_token_count INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS tokens (
+    mint TEXT PRIMARY KEY,
+    dev_address TEXT NOT NULL,
+    ath BIGINT NOT NULL DEFAULT 0,
+    created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())::BIGINT,
+    name TEXT,
+    ticker TEXT,
+    ipfs TEXT,
+    image TEXT,
+    description TEXT,
+    community_id TEXT,
+    -- PumpSwap pool of the mint, filled in from the mint for older rows
+    pool_address TEXT,
+    slot BIGINT,
+    signature TEXT,
+    completed_at BIGINT,
+    migrated_at BIGINT,
+    CONSTRAINT fk_dev FOREIGN KEY (dev_address)
+        REFERENCES devs(dev_address)
+);
+
+ALTER TABLE tokens
+    ADD COLUMN IF NOT EXISTS pool_address TEXT,
+    ADD COLUMN IF NOT EXISTS slot BIGINT,
+    ADD COLUMN IF NOT EXISTS signature TEXT,
+    ADD COLUMN IF NOT EXISTS completed_at BIGINT,
+    ADD COLUMN IF NOT EXISTS migrated_at BIGINT;
+
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    -- Keys in clear from before hashing, emptied on start
+    access_key CHAR(32) UNIQUE,
+    hint TEXT,
+    admin BOOLEAN DEFAULT false,
+    autobuy BOOLEAN NOT NULL DEFAULT false,
+    max_sessions INTEGER NOT NULL DEFAULT 1,
+    key_prefix TEXT,
+    key_hash TEXT,
+    key_salt TEXT,
+    expires_at BIGINT,
+    disabled BOOLEAN NOT NULL DEFAULT false,
+    role TEXT
+);
+
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS autobuy BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN IF NOT EXISTS max_sessions INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN IF NOT EXISTS key_prefix TEXT,
+    ADD COLUMN IF NOT EXISTS key_hash TEXT,
+    ADD COLUMN IF NOT EXISTS key_salt TEXT,
+    ADD COLUMN IF NOT EXISTS expires_at BIGINT,
+    ADD COLUMN IF NOT EXISTS disabled BOOLEAN NOT NULL DEFAULT false,
+    ADD COLUMN IF NOT EXISTS role TEXT;
+
+-- Some deployments added autobuy by hand, without a default
+UPDATE users SET autobuy = false WHERE autobuy IS NULL;
+ALTER TABLE users
+    ALTER COLUMN autobuy SET DEFAULT false,
+    ALTER COLUMN autobuy SET NOT NULL;
+
+-- Admins from before roles
+UPDATE users SET role = 'admin' WHERE admin AND role IS NULL;
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())::BIGINT,
+    actor_id INTEGER,
+    actor TEXT,
+    action TEXT NOT NULL,
+    target TEXT,
+    details TEXT,
+    ip TEXT
+);
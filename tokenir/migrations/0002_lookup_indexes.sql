@@ -0,0 +1,18 @@
+-- Lookups on the create path and the ATH updates, which all ran as
+-- sequential scans over `tokens`.
+
+-- Dev history, newest first
+CREATE INDEX IF NOT EXISTS tokens_dev_created_idx ON tokens (dev_address, created_at DESC);
+
+-- ATH updates by pool, and the pool address backfill
+CREATE INDEX IF NOT EXISTS tokens_pool_address_idx ON tokens (pool_address);
+
+-- Duplicate checks. Hash indexes for values that can outgrow a B-tree entry
+CREATE INDEX IF NOT EXISTS tokens_image_idx ON tokens USING HASH (image);
+CREATE INDEX IF NOT EXISTS tokens_ipfs_idx ON tokens USING HASH (ipfs);
+CREATE INDEX IF NOT EXISTS tokens_description_idx ON tokens USING HASH (description);
+CREATE INDEX IF NOT EXISTS tokens_name_ticker_idx ON tokens (name, ticker);
+CREATE INDEX IF NOT EXISTS tokens_community_idx ON tokens USING HASH (community_id);
+
+-- Key lookups on every login
+CREATE INDEX IF NOT EXISTS users_key_prefix_idx ON users (key_prefix);
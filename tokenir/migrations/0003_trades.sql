@@ -0,0 +1,23 @@
+-- Every curve trade seen by the analysis connection. Partitioned by day on
+-- `timestamp`, the server creates upcoming days and drops the ones past
+-- retention.
+
+CREATE TABLE IF NOT EXISTS trades (
+    mint TEXT NOT NULL,
+    pool TEXT NOT NULL,
+    user_address TEXT NOT NULL,
+    is_buy BOOLEAN NOT NULL,
+    sol_amount BIGINT NOT NULL,
+    token_amount BIGINT NOT NULL,
+    virtual_sol_reserves BIGINT NOT NULL,
+    virtual_token_reserves BIGINT NOT NULL,
+    slot BIGINT,
+    signature TEXT,
+    timestamp BIGINT NOT NULL
+) PARTITION BY RANGE (timestamp);
+
+-- Charts of one token
+CREATE INDEX IF NOT EXISTS trades_mint_timestamp_idx ON trades (mint, timestamp);
+
+-- Trades of one wallet on one token
+CREATE INDEX IF NOT EXISTS trades_mint_user_idx ON trades (mint, user_address);
@@ -0,0 +1,10 @@
+-- What the dev wallet did on the token's own curve, and when selling made
+-- it a rug. Totals are in lamports and raw token amounts.
+
+ALTER TABLE tokens
+    ADD COLUMN IF NOT EXISTS dev_bought_sol BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS dev_sold_sol BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS dev_bought_tokens BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS dev_sold_tokens BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS first_dev_sell_at BIGINT,
+    ADD COLUMN IF NOT EXISTS rugged_at BIGINT;
@@ -1,18 +1,150 @@
 use serde::{Deserialize, Serialize};
-use sqlx::prelude::FromRow;
+use solana_sdk::hash::hashv;
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    prelude::FromRow,
+    Decode, Encode, Postgres, Type,
+};
+use std::{fmt, str::FromStr};
+use uuid::Uuid;
+
+/// Length of the part of a key kept in clear, to find its row and to tell
+/// keys apart in logs and the admin panel.
+pub const KEY_PREFIX_LEN: usize = 8;
 
 #[derive(Deserialize)]
 pub struct AddUserPayload {
     pub provided_key: String,
     pub hint: String,
     pub autobuy: bool,
+    /// Sessions the key may have open at once
+    #[serde(default = "default_max_sessions")]
+    pub max_sessions: i32,
+    /// Unix seconds after which the key stops working
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// Access to the admin API, none for plain feed users
+    #[serde(default)]
+    pub role: Option<Role>,
+}
+
+fn default_max_sessions() -> i32 {
+    1
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: i32,
-    pub access_key: String,
+    pub key_prefix: String,
     pub hint: String,
-    pub admin: bool,
+    pub role: Option<Role>,
     pub autobuy: bool,
+    pub max_sessions: i32,
+    pub expires_at: Option<i64>,
+    pub disabled: bool,
+}
+
+/// What a key may do on the admin API. Each role includes the ones below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reads users and connections
+    Viewer,
+    /// Also disconnects sessions and restarts the server
+    Operator,
+    /// Also manages users and their keys
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Operator => "operator",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Self::Viewer),
+            "operator" => Ok(Self::Operator),
+            "admin" => Ok(Self::Admin),
+            other => Err(format!("unknown role: {}", other)),
+        }
+    }
+}
+
+// Stored as TEXT
+impl Type<Postgres> for Role {
+    fn type_info() -> PgTypeInfo {
+        <&str as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Role {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+impl Encode<'_, Postgres> for Role {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <&str as Encode<Postgres>>::encode(self.as_str(), buf)
+    }
+}
+
+/// How a key is stored: its prefix and a salted hash of the whole key.
+#[derive(Debug, Clone)]
+pub struct HashedKey {
+    pub prefix: String,
+    pub salt: String,
+    pub hash: String,
+}
+
+impl HashedKey {
+    pub fn new(key: &str) -> Self {
+        let salt = Uuid::new_v4().simple().to_string();
+        Self {
+            prefix: key_prefix(key).to_string(),
+            hash: hash_key(key, &salt),
+            salt,
+        }
+    }
+
+    pub fn verify(key: &str, salt: &str, hash: &str) -> bool {
+        hash_key(key, salt) == hash
+    }
+}
+
+/// A fresh random key, 32 characters like the ones the admin panel makes.
+pub fn generate_key() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// The clear part of a key. Safe to log.
+pub fn key_prefix(key: &str) -> &str {
+    key.char_indices()
+        .nth(KEY_PREFIX_LEN)
+        .map_or(key, |(end, _)| &key[..end])
+}
+
+fn hash_key(key: &str, salt: &str) -> String {
+    hashv(&[salt.as_bytes(), key.as_bytes()]).to_string()
 }
@@ -0,0 +1,135 @@
+use dashmap::DashMap;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::logs::{AmmSwap, AmmTrade};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How long a pool that failed to load is left alone before it is retried
+const MISS_TTL: Duration = Duration::from_secs(30);
+
+// PumpSwap `Pool` account: discriminator, bump (u8), index (u16), creator,
+// then the base and quote mints
+const BASE_MINT_OFFSET: usize = 8 + 1 + 2 + 32;
+const QUOTE_MINT_OFFSET: usize = BASE_MINT_OFFSET + 32;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AmmPool {
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+}
+
+/// Base/quote mints of PumpSwap pools. Pools never change their mints, so
+/// entries are fetched once over RPC (or learned from a pool creation event)
+/// and kept for good.
+///
+/// Fetches run in the background so the logs stream is never held up by RPC;
+/// swaps on a pool that isn't cached yet are dropped until it is. Pools that
+/// fail to load are not retried for [`MISS_TTL`].
+pub struct AmmPoolCache {
+    rpc: RpcClient,
+    pools: DashMap<Pubkey, AmmPool>,
+    // Pools that failed to load, with when that happened
+    misses: DashMap<Pubkey, Instant>,
+    in_flight: DashMap<Pubkey, ()>,
+    unresolved: AtomicU64,
+}
+
+impl AmmPoolCache {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc: RpcClient::new(rpc_url),
+            pools: DashMap::new(),
+            misses: DashMap::new(),
+            in_flight: DashMap::new(),
+            unresolved: AtomicU64::new(0),
+        }
+    }
+
+    /// Swaps dropped because their pool account was not loaded (yet).
+    pub fn unresolved(&self) -> u64 {
+        self.unresolved.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    pub fn insert(&self, pool: Pubkey, base_mint: Pubkey, quote_mint: Pubkey) {
+        self.pools.insert(
+            pool,
+            AmmPool {
+                base_mint,
+                quote_mint,
+            },
+        );
+    }
+
+    pub fn get(&self, pool: &Pubkey) -> Option<AmmPool> {
+        self.pools.get(pool).map(|entry| *entry)
+    }
+
+    /// Resolves the pool of a swap and converts it into a token/SOL trade.
+    /// Starts loading the pool when it isn't cached yet.
+    pub fn resolve(self: &Arc<Self>, swap: AmmSwap) -> Option<AmmTrade> {
+        let Some(pool) = self.get(&swap.pool) else {
+            self.unresolved.fetch_add(1, Ordering::Relaxed);
+            self.spawn_fetch(swap.pool);
+            return None;
+        };
+
+        swap.resolve(pool.base_mint, pool.quote_mint)
+    }
+
+    fn spawn_fetch(self: &Arc<Self>, pool: Pubkey) {
+        if let Some(missed) = self.misses.get(&pool) {
+            if missed.elapsed() < MISS_TTL {
+                return;
+            }
+        }
+        if self.in_flight.insert(pool, ()).is_some() {
+            return;
+        }
+
+        let cache = self.clone();
+        tokio::spawn(async move {
+            match cache.fetch(&pool).await {
+                Some(entry) => {
+                    cache.pools.insert(pool, entry);
+                    cache.misses.remove(&pool);
+                }
+                None => {
+                    cache.misses.insert(pool, Instant::now());
+                }
+            }
+            cache.in_flight.remove(&pool);
+        });
+    }
+
+    async fn fetch(&self, pool: &Pubkey) -> Option<AmmPool> {
+        let data = tokio::time::timeout(FETCH_TIMEOUT, self.rpc.get_account_data(pool))
+            .await
+            .ok()?
+            .ok()?;
+
+        let base_mint = data.get(BASE_MINT_OFFSET..QUOTE_MINT_OFFSET)?;
+        let quote_mint = data.get(QUOTE_MINT_OFFSET..QUOTE_MINT_OFFSET + 32)?;
+
+        Some(AmmPool {
+            base_mint: Pubkey::try_from(base_mint).ok()?,
+            quote_mint: Pubkey::try_from(quote_mint).ok()?,
+        })
+    }
+}
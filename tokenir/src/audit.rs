@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use sqlx::prelude::FromRow;
+
+use crate::access::User;
+
+/// A recorded admin action or failed login, as the admin API returns it.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    /// Unix seconds
+    pub at: i64,
+    pub actor_id: Option<i32>,
+    /// Key prefix of the caller, or of the key that failed to log in
+    pub actor: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+    /// Short summary of the request, never a full key
+    pub details: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// An entry about to be written.
+#[derive(Debug, Clone, Default)]
+pub struct AuditEvent {
+    pub actor_id: Option<i32>,
+    pub actor: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+    pub details: Option<String>,
+    pub ip: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(action: impl Into<String>) -> Self {
+        Self {
+            action: action.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_user(mut self, user: &User) -> Self {
+        self.actor_id = Some(user.id);
+        self.actor = Some(user.key_prefix.clone());
+        self
+    }
+
+    /// For callers that have no user, like a key that failed to log in.
+    pub fn with_actor(mut self, key_prefix: impl Into<String>) -> Self {
+        self.actor = Some(key_prefix.into());
+        self
+    }
+
+    pub fn with_target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+
+    pub fn with_ip(mut self, ip: Option<impl ToString>) -> Self {
+        self.ip = ip.map(|ip| ip.to_string());
+        self
+    }
+}
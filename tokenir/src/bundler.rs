@@ -1,43 +1,379 @@
 use crate::{
     database::{Database, DbToken},
-    Token,
+    health::Backoff,
+    history::TradeRecord,
+    rug::DevActivity,
 };
+use futures::{future::BoxFuture, FutureExt};
 use solana_sdk::pubkey::Pubkey;
-use sqlx::{Pool, Postgres};
-use std::collections::HashMap; // Ensure this import is present
+use std::{
+    collections::HashMap,
+    mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{sync::Notify, task::JoinHandle};
+
+#[derive(Debug, Clone, Copy)]
+pub struct BundlerConfig {
+    /// Pending writes that trigger a flush before the interval is up
+    pub max_batch: usize,
+    pub interval: Duration,
+    /// Pending writes past which new ones are dropped, while the database
+    /// is down
+    pub max_pending: usize,
+    /// Pending trades past which new ones are dropped, so they can't take
+    /// up all of `max_pending`
+    pub max_trades: usize,
+}
+
+impl Default for BundlerConfig {
+    fn default() -> Self {
+        Self {
+            max_batch: 500,
+            interval: Duration::from_secs(1),
+            max_pending: 100_000,
+            max_trades: 50_000,
+        }
+    }
+}
+
+/// Writes waiting for the next flush. Each token and each pool appears once,
+/// trades all in arrival order.
+#[derive(Debug, Default)]
+pub struct Batch {
+    tokens: HashMap<Pubkey, (DbToken, String)>,
+    aths: HashMap<Pubkey, i64>,
+    devs: HashMap<Pubkey, DevActivity>,
+    trades: Vec<TradeRecord>,
+}
+
+impl Batch {
+    /// A later add of the same mint replaces the earlier one.
+    pub fn add_token(&mut self, mint: Pubkey, token: DbToken, dev_address: String) {
+        self.tokens.insert(mint, (token, dev_address));
+    }
+
+    /// Keeps the highest ATH seen for the pool.
+    pub fn add_ath(&mut self, pool: Pubkey, ath: i64) {
+        let max = self.aths.entry(pool).or_insert(ath);
+        *max = (*max).max(ath);
+    }
+
+    /// Dev totals only grow, so the latest for the pool replaces the rest.
+    pub fn add_dev_activity(&mut self, pool: Pubkey, activity: DevActivity) {
+        self.devs.insert(pool, activity);
+    }
+
+    pub fn add_trade(&mut self, trade: TradeRecord) {
+        self.trades.push(trade);
+    }
+
+    pub fn ath(&self, pool: &Pubkey) -> Option<i64> {
+        self.aths.get(pool).copied()
+    }
 
+    pub fn token(&self, mint: &Pubkey) -> Option<&DbToken> {
+        self.tokens.get(mint).map(|(token, _)| token)
+    }
+
+    pub fn dev_activity(&self, pool: &Pubkey) -> Option<&DevActivity> {
+        self.devs.get(pool)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len() + self.aths.len() + self.devs.len() + self.trades.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Puts back writes that failed, without losing newer ones.
+    pub fn merge(&mut self, older: Batch) {
+        for (mint, token) in older.tokens {
+            self.tokens.entry(mint).or_insert(token);
+        }
+        for (pool, ath) in older.aths {
+            self.add_ath(pool, ath);
+        }
+        for (pool, activity) in older.devs {
+            self.devs.entry(pool).or_insert(activity);
+        }
+        let newer = mem::replace(&mut self.trades, older.trades);
+        self.trades.extend(newer);
+    }
+}
+
+/// Collects new tokens, ATH updates, dev totals and trades and writes them
+/// in batches, from a background task, on size or on interval.
 pub struct Bundler {
-    limit: u64,
-    current: u64,
-    data: HashMap<Pubkey, DbToken>,
+    config: BundlerConfig,
+    batch: Mutex<Batch>,
+    full: Notify,
+    stop: Notify,
+    shed: AtomicU64,
+}
+
+impl Default for Bundler {
+    fn default() -> Self {
+        Self::new(BundlerConfig::default())
+    }
 }
 
 impl Bundler {
-    pub fn new(limit: u64) -> Self {
+    pub fn new(config: BundlerConfig) -> Self {
         Self {
-            limit,
-            current: 0,
-            data: HashMap::new(),
+            config,
+            batch: Mutex::new(Batch::default()),
+            full: Notify::new(),
+            stop: Notify::new(),
+            shed: AtomicU64::new(0),
         }
     }
 
-    pub fn add(&mut self, data: (Pubkey, DbToken)) {
-        self.current += 1;
-        self.data.insert(data.0, data.1);
+    pub fn add_token(&self, mint: Pubkey, token: DbToken, dev_address: String) {
+        self.with_batch(|batch| batch.add_token(mint, token, dev_address));
     }
 
-    pub fn full(&self) -> bool {
-        self.current >= self.limit
+    pub fn add_ath(&self, pool: Pubkey, ath: i64) {
+        self.with_batch(|batch| batch.add_ath(pool, ath));
     }
 
-    pub async fn send(&mut self, database: &Database) -> Result<(), sqlx::Error> {
-        // if self.data.is_empty() {
-        //     return Ok(());
-        // }
+    pub fn add_dev_activity(&self, pool: Pubkey, activity: DevActivity) {
+        self.with_batch(|batch| batch.add_dev_activity(pool, activity));
+    }
+
+    /// One write per trade, so trades have a cap of their own.
+    pub fn add_trade(&self, trade: TradeRecord) {
+        let max_trades = self.config.max_trades;
+        self.with_batch_if(
+            |batch| batch.trades.len() < max_trades,
+            |batch| batch.add_trade(trade),
+        );
+    }
 
-        // self.data.clear();
-        // self.current = 0;
+    fn with_batch(&self, add: impl FnOnce(&mut Batch)) {
+        self.with_batch_if(|_| true, add);
+    }
+
+    fn with_batch_if(&self, room: impl FnOnce(&Batch) -> bool, add: impl FnOnce(&mut Batch)) {
+        let mut batch = self.batch.lock().unwrap();
+        if batch.len() >= self.config.max_pending || !room(&batch) {
+            let pending = batch.len();
+            drop(batch);
+            self.shed_one(pending);
+            return;
+        }
+        add(&mut batch);
+        if batch.len() >= self.config.max_batch {
+            self.full.notify_one();
+        }
+    }
+
+    /// Ends the task started by `spawn` once its current flush is done. Await
+    /// its handle before the final `flush`, so nothing is still in flight.
+    pub fn stop(&self) {
+        self.stop.notify_one();
+    }
+
+    pub fn pending(&self) -> usize {
+        self.batch.lock().unwrap().len()
+    }
+
+    /// Writes dropped because `max_pending` was reached.
+    pub fn shed(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
+    fn shed_one(&self, pending: usize) {
+        let shed = self.shed.fetch_add(1, Ordering::Relaxed) + 1;
+        if shed == 1 || shed.is_multiple_of(10_000) {
+            eprintln!(
+                "[bundler] {} writes pending, dropping new ones ({} dropped so far)",
+                pending, shed
+            );
+        }
+    }
+
+    /// Writes everything pending, returns how many writes that was. Writes
+    /// that fail because the database can't be reached stay pending for the
+    /// next flush, the ones it rejects are logged and dropped.
+    pub async fn flush(&self, database: &Database) -> Result<usize, sqlx::Error> {
+        let batch = mem::take(&mut *self.batch.lock().unwrap());
+        if batch.is_empty() {
+            return Ok(0);
+        }
+        let taken = batch.len();
+        let Batch {
+            tokens,
+            aths,
+            devs,
+            trades,
+        } = batch;
+
+        // Tokens first, so updates of tokens in the same batch find their row
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        let mut dropped = match write_rows(database, "token", tokens, |database, rows| {
+            let tokens: Vec<_> = rows.iter().map(|(_, token)| token.clone()).collect();
+            async move { database.add_tokens(&tokens).await }.boxed()
+        })
+        .await
+        {
+            Ok(dropped) => dropped,
+            Err((err, tokens)) => {
+                self.requeue(Batch {
+                    tokens: tokens.into_iter().collect(),
+                    aths,
+                    devs,
+                    trades,
+                });
+                return Err(err);
+            }
+        };
+
+        let aths: Vec<_> = aths.into_iter().collect();
+        match write_rows(database, "ATH", aths, |database, rows| {
+            database.update_token_aths(rows).boxed()
+        })
+        .await
+        {
+            Ok(n) => dropped += n,
+            Err((err, aths)) => {
+                self.requeue(Batch {
+                    aths: aths.into_iter().collect(),
+                    devs,
+                    trades,
+                    ..Batch::default()
+                });
+                return Err(err);
+            }
+        }
+
+        let devs: Vec<_> = devs.into_iter().collect();
+        match write_rows(database, "dev activity", devs, |database, rows| {
+            database.update_dev_activity(rows).boxed()
+        })
+        .await
+        {
+            Ok(n) => dropped += n,
+            Err((err, devs)) => {
+                self.requeue(Batch {
+                    devs: devs.into_iter().collect(),
+                    trades,
+                    ..Batch::default()
+                });
+                return Err(err);
+            }
+        }
+
+        match write_rows(database, "trade", trades, |database, rows| {
+            database.add_trades(rows).boxed()
+        })
+        .await
+        {
+            Ok(n) => dropped += n,
+            Err((err, trades)) => {
+                self.requeue(Batch {
+                    trades,
+                    ..Batch::default()
+                });
+                return Err(err);
+            }
+        }
+
+        Ok(taken - dropped)
+    }
+
+    /// Puts back writes that failed, without losing newer ones.
+    fn requeue(&self, older: Batch) {
+        self.batch.lock().unwrap().merge(older);
+    }
+
+    /// Flushes every `interval`, or sooner once `max_batch` writes are pending,
+    /// until `stop` is called.
+    pub fn spawn(self: &Arc<Self>, database: Arc<Database>) -> JoinHandle<()> {
+        let bundler = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(bundler.config.interval);
+            // Paces retries while the database is down
+            let mut backoff = Backoff::new(bundler.config.interval, Duration::from_secs(30));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = bundler.full.notified() => {}
+                    _ = bundler.stop.notified() => break,
+                }
+
+                match bundler.flush(&database).await {
+                    Ok(_) => backoff.reset(),
+                    Err(err) => {
+                        let delay = backoff.next_delay();
+                        eprintln!(
+                            "[bundler] flush failed, {} writes pending, retrying in {}ms: {}",
+                            bundler.pending(),
+                            delay.as_millis(),
+                            err
+                        );
+                        tokio::select! {
+                            _ = tokio::time::sleep(delay) => {}
+                            _ = bundler.stop.notified() => break,
+                        }
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// Writes `rows` in one statement. When the database rejects them, writes
+/// them one at a time and drops the ones it rejects, so a bad row can't hold
+/// up the rest. Returns how many were dropped, or the rows still to write
+/// when the database can't be reached.
+async fn write_rows<T>(
+    database: &Database,
+    what: &str,
+    rows: Vec<T>,
+    write: impl for<'a> Fn(&'a Database, &'a [T]) -> BoxFuture<'a, Result<(), sqlx::Error>>,
+) -> Result<usize, (sqlx::Error, Vec<T>)> {
+    if rows.is_empty() {
+        return Ok(0);
+    }
+    match write(database, &rows).await {
+        Ok(()) => return Ok(0),
+        Err(err) if is_transient(&err) => return Err((err, rows)),
+        Err(_) => {}
+    }
+
+    let mut dropped = 0;
+    let mut rows = rows.into_iter();
+    while let Some(row) = rows.next() {
+        match write(database, std::slice::from_ref(&row)).await {
+            Ok(()) => {}
+            Err(err) if is_transient(&err) => {
+                return Err((err, std::iter::once(row).chain(rows).collect()));
+            }
+            Err(err) => {
+                dropped += 1;
+                eprintln!("[bundler] dropping a {} write: {}", what, err);
+            }
+        }
+    }
+
+    Ok(dropped)
+}
 
-        Ok(())
+/// Whether a failed write may go through on a retry. Bad data (class 22) and
+/// constraint violations (class 23) never will.
+fn is_transient(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(err) => !err
+            .code()
+            .is_some_and(|code| code.starts_with("22") || code.starts_with("23")),
+        sqlx::Error::Encode(_) => false,
+        _ => true,
     }
 }
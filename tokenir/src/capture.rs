@@ -0,0 +1,113 @@
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::mpsc,
+    thread::JoinHandle,
+};
+
+use crate::{fetcher::since_epoch, source::SourceKind};
+
+/// One raw message as it came off the wire, one JSON object per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CaptureRecord {
+    /// `logsNotification` text from the RPC websocket
+    Logs { at: u64, text: String },
+    /// PumpPortal data API text
+    PumpPortal { at: u64, text: String },
+    /// Bincode entries from the shredstream, base64 encoded
+    Shred { at: u64, slot: u64, entries: String },
+}
+
+impl CaptureRecord {
+    /// Receive time in microseconds since the unix epoch.
+    pub fn at(&self) -> u64 {
+        match self {
+            CaptureRecord::Logs { at, .. }
+            | CaptureRecord::PumpPortal { at, .. }
+            | CaptureRecord::Shred { at, .. } => *at,
+        }
+    }
+
+    pub fn source(&self) -> SourceKind {
+        match self {
+            CaptureRecord::Logs { .. } => SourceKind::Logs,
+            CaptureRecord::PumpPortal { .. } => SourceKind::PumpPortal,
+            CaptureRecord::Shred { .. } => SourceKind::Shredstream,
+        }
+    }
+}
+
+/// Appends raw stream messages to a capture file.
+///
+/// Writes happen on a dedicated thread so the read loops never wait on disk.
+/// The file is only ever appended to, captures from several runs can share it.
+pub struct CaptureWriter {
+    tx: mpsc::Sender<CaptureRecord>,
+    handle: JoinHandle<()>,
+}
+
+impl CaptureWriter {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let (tx, rx) = mpsc::channel::<CaptureRecord>();
+
+        let handle = std::thread::spawn(move || {
+            let mut out = BufWriter::new(file);
+
+            while let Ok(record) = rx.recv() {
+                // Write out whatever queued up meanwhile, then flush once
+                for record in std::iter::once(record).chain(rx.try_iter()) {
+                    if let Err(e) = write_record(&mut out, &record) {
+                        eprintln!("[capture] write failed: {}", e);
+                        return;
+                    }
+                }
+                let _ = out.flush();
+            }
+        });
+
+        Ok(Self { tx, handle })
+    }
+
+    pub fn logs(&self, text: &str) {
+        let _ = self.tx.send(CaptureRecord::Logs {
+            at: now_micros(),
+            text: text.to_string(),
+        });
+    }
+
+    pub fn pumpportal(&self, text: &str) {
+        let _ = self.tx.send(CaptureRecord::PumpPortal {
+            at: now_micros(),
+            text: text.to_string(),
+        });
+    }
+
+    pub fn shred(&self, slot: u64, entries: &[u8]) {
+        let _ = self.tx.send(CaptureRecord::Shred {
+            at: now_micros(),
+            slot,
+            entries: BASE64_STANDARD.encode(entries),
+        });
+    }
+
+    /// Writes out everything queued so far and closes the file.
+    pub fn close(self) {
+        drop(self.tx);
+        let _ = self.handle.join();
+    }
+}
+
+fn write_record(out: &mut impl Write, record: &CaptureRecord) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, record)?;
+    out.write_all(b"\n")
+}
+
+fn now_micros() -> u64 {
+    since_epoch().as_micros() as u64
+}
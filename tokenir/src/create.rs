@@ -0,0 +1,427 @@
+use futures::future::BoxFuture;
+use solana_sdk::signature::Signature;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+use crate::{
+    constans::helper::{get_community_by_id, get_metadata, parse_community_id, CommunityInfo},
+    database::Database,
+    fetcher::since_epoch,
+    logs::CreateEvent,
+    requests::Metadata,
+    DevPerformance, Token,
+};
+
+/// Creates that waited longer than this before their lookups started are
+/// not broadcast, clients would see them too late to act on.
+pub const MAX_CREATE_DELAY: Duration = Duration::from_secs(5);
+
+/// Names, tickers, uris, images and descriptions of tokens seen since start,
+/// checked before the database.
+#[derive(Default)]
+pub struct TokenCache {
+    images: HashMap<String, ()>,
+    ipfs: HashMap<String, ()>,
+    descriptions: HashMap<String, ()>,
+    names: HashMap<String, ()>,
+    tickers: HashMap<String, ()>,
+    name_ticker_pairs: HashMap<(String, String), ()>,
+    desc_name_pairs: HashMap<(String, String), ()>,
+    desc_ticker_pairs: HashMap<(String, String), ()>,
+}
+
+/// Twitter communities already used by a token.
+#[derive(Default)]
+pub struct CommunityCache {
+    community_ids: HashMap<String, ()>,
+}
+
+impl CommunityCache {
+    pub fn has_community(&self, community_id: &str) -> bool {
+        self.community_ids.contains_key(community_id)
+    }
+
+    pub fn insert_community(&mut self, community_id: &str) {
+        self.community_ids.insert(community_id.to_string(), ());
+    }
+}
+
+impl TokenCache {
+    pub fn check_duplicate(
+        &self,
+        image: Option<&str>,
+        ipfs: Option<&str>,
+        description: Option<&str>,
+        name: Option<&str>,
+        ticker: Option<&str>,
+    ) -> bool {
+        // Match SQL logic: EXISTS if ANY condition is true
+
+        // Check: image exists
+        if let Some(img) = image {
+            if self.images.contains_key(img) {
+                return true;
+            }
+        }
+
+        // Check: ipfs exists
+        if let Some(ipfs_val) = ipfs {
+            if self.ipfs.contains_key(ipfs_val) {
+                return true;
+            }
+        }
+
+        // Check: description + name pair exists
+        if let (Some(desc), Some(n)) = (description, name) {
+            if self
+                .desc_name_pairs
+                .contains_key(&(desc.to_string(), n.to_string()))
+            {
+                return true;
+            }
+        }
+
+        // Check: description + ticker pair exists
+        if let (Some(desc), Some(t)) = (description, ticker) {
+            if self
+                .desc_ticker_pairs
+                .contains_key(&(desc.to_string(), t.to_string()))
+            {
+                return true;
+            }
+        }
+
+        // Check: name + ticker pair exists
+        if let (Some(n), Some(t)) = (name, ticker) {
+            if self
+                .name_ticker_pairs
+                .contains_key(&(n.to_string(), t.to_string()))
+            {
+                return true;
+            }
+        }
+
+        // Check: name exists (standalone)
+        if let Some(n) = name {
+            if self.names.contains_key(n) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    pub fn insert_token(
+        &mut self,
+        image: Option<&str>,
+        ipfs: Option<&str>,
+        description: Option<&str>,
+        name: Option<&str>,
+        ticker: Option<&str>,
+    ) {
+        if let Some(img) = image {
+            self.images.insert(img.to_string(), ());
+        }
+        if let Some(ipfs_val) = ipfs {
+            self.ipfs.insert(ipfs_val.to_string(), ());
+        }
+        if let Some(desc) = description {
+            self.descriptions.insert(desc.to_string(), ());
+            if let Some(n) = name {
+                self.desc_name_pairs
+                    .insert((desc.to_string(), n.to_string()), ());
+            }
+            if let Some(t) = ticker {
+                self.desc_ticker_pairs
+                    .insert((desc.to_string(), t.to_string()), ());
+            }
+        }
+        if let Some(n) = name {
+            self.names.insert(n.to_string(), ());
+            if let Some(t) = ticker {
+                self.name_ticker_pairs
+                    .insert((n.to_string(), t.to_string()), ());
+            }
+        }
+        if let Some(t) = ticker {
+            self.tickers.insert(t.to_string(), ());
+        }
+    }
+}
+
+/// What the create flow looks up about a new token. [`LiveLookups`] asks IPFS,
+/// Twitter and the database, a replay can stand in its own answers.
+pub trait CreateLookups: Send + Sync {
+    fn metadata<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Option<Metadata>>;
+
+    /// A stored token already has the name, ticker or uri of `create`.
+    fn token_exists<'a>(&'a self, create: &'a CreateEvent) -> BoxFuture<'a, bool>;
+
+    fn community<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Option<CommunityInfo>>;
+
+    /// Earlier tokens of the Twitter user `creator_id`, leaving out `mint`.
+    fn dev_performance<'a>(
+        &'a self,
+        creator_id: &'a str,
+        mint: &'a str,
+    ) -> BoxFuture<'a, Option<DevPerformance>>;
+}
+
+pub struct LiveLookups {
+    database: Arc<Database>,
+    twitter_key: Arc<String>,
+    ipfs_node: Arc<String>,
+}
+
+impl LiveLookups {
+    pub fn new(database: Arc<Database>, twitter_key: Arc<String>, ipfs_node: Arc<String>) -> Self {
+        Self {
+            database,
+            twitter_key,
+            ipfs_node,
+        }
+    }
+}
+
+impl CreateLookups for LiveLookups {
+    fn metadata<'a>(&'a self, uri: &'a str) -> BoxFuture<'a, Option<Metadata>> {
+        Box::pin(async move { get_metadata(&self.ipfs_node, uri).await.ok() })
+    }
+
+    fn token_exists<'a>(&'a self, create: &'a CreateEvent) -> BoxFuture<'a, bool> {
+        Box::pin(async move {
+            self.database
+                .token_any_exists(
+                    Some(&create.name),
+                    Some(&create.symbol),
+                    Some(&create.uri),
+                    None,
+                    None,
+                )
+                .await
+                .unwrap_or(false)
+        })
+    }
+
+    fn community<'a>(&'a self, id: &'a str) -> BoxFuture<'a, Option<CommunityInfo>> {
+        Box::pin(async move { get_community_by_id(&self.twitter_key, id).await.ok() })
+    }
+
+    fn dev_performance<'a>(
+        &'a self,
+        creator_id: &'a str,
+        mint: &'a str,
+    ) -> BoxFuture<'a, Option<DevPerformance>> {
+        Box::pin(async move {
+            let (median, count, migrated, rugged) = self
+                .database
+                .get_dev_median_ath_excluding(creator_id, mint)
+                .await
+                .ok()??;
+            let last_three = self
+                .database
+                .get_last_tokens_by_dev_excluding(creator_id, mint, 3)
+                .await
+                .ok()?;
+            let rug_rate = if count > 0 {
+                (rugged * 100 / count) as u64
+            } else {
+                0
+            };
+            Some(DevPerformance {
+                average_ath: median as u64,
+                last_tokens: last_three,
+                count,
+                migrated,
+                rug_rate,
+            })
+        })
+    }
+}
+
+/// The fast path of a new token: metadata, duplicate checks, Twitter and dev
+/// history, as quickly as possible so it can be broadcast.
+pub struct CreateFlow<L> {
+    lookups: L,
+    cache: Arc<Mutex<TokenCache>>,
+}
+
+impl<L: CreateLookups> CreateFlow<L> {
+    pub fn new(lookups: L, cache: Arc<Mutex<TokenCache>>) -> Self {
+        Self { lookups, cache }
+    }
+
+    /// The token to broadcast, or `None` for a duplicate of a known token or
+    /// a create received (`received`, since the unix epoch) too long ago.
+    pub async fn process(
+        &self,
+        data: CreateEvent,
+        slot: Option<u64>,
+        signature: Option<Signature>,
+        received: Duration,
+    ) -> Option<Token> {
+        let now = since_epoch();
+
+        // OPTIMIZATION: Run metadata fetch and DB check concurrently
+        let t0 = Instant::now();
+
+        let metadata_fut = async {
+            let t = Instant::now();
+            let res = self.lookups.metadata(&data.uri).await;
+            (res, t.elapsed())
+        };
+
+        let db_check_fut = self.lookups.token_exists(&data);
+
+        let ((metadata_res, meta_time), token_exists) = tokio::join!(metadata_fut, db_check_fut);
+
+        let Some(metadata) = metadata_res else {
+            // Fast cache check even without metadata
+            let cache_guard = self.cache.lock().await;
+            if cache_guard.check_duplicate(
+                None,
+                Some(&data.uri),
+                None,
+                Some(&data.name),
+                Some(&data.symbol),
+            ) {
+                return None;
+            }
+            drop(cache_guard);
+
+            let token = Token::fresh(
+                data.name.clone(),
+                data.symbol.clone(),
+                data.user,
+                data.bonding_curve,
+                None,
+                data.mint,
+                data.token_2022,
+                Some(data.uri.clone()),
+                None,
+            )
+            .with_origin(slot, signature);
+            pretty_token_log(&token, None, meta_time, None, t0.elapsed());
+            return Some(token);
+        };
+
+        // OPTIMIZATION: Check cache first (fast path)
+        {
+            let cache_guard = self.cache.lock().await;
+            if cache_guard.check_duplicate(
+                metadata.image.as_deref(),
+                Some(&data.uri),
+                metadata.description.as_deref(),
+                Some(&data.name),
+                Some(&data.symbol),
+            ) {
+                return None;
+            }
+        }
+
+        if token_exists {
+            return None;
+        }
+
+        // OPTIMIZATION: Early timeout check to prevent slow tokens from broadcasting
+        if now.saturating_sub(received) > MAX_CREATE_DELAY {
+            println!(
+                "[break early {} took more than {} seconds to load]",
+                &data.mint,
+                MAX_CREATE_DELAY.as_secs()
+            );
+            return None;
+        }
+
+        let community_id = metadata.twitter.as_deref().and_then(parse_community_id);
+        let (community, twitter_time) = match community_id {
+            Some(id) => {
+                let t = Instant::now();
+                let res = self.lookups.community(&id).await;
+                (res, Some(t.elapsed()))
+            }
+            None => (None, None),
+        };
+
+        let dev_performance = match &community {
+            Some(c) => {
+                self.lookups
+                    .dev_performance(&c.creator.id, &data.mint.to_string())
+                    .await
+            }
+            None => None,
+        };
+
+        let mut token = Token::fresh(
+            data.name,
+            data.symbol,
+            data.user,
+            data.bonding_curve,
+            community,
+            data.mint,
+            data.token_2022,
+            Some(data.uri.clone()),
+            Some(metadata.clone()),
+        )
+        .with_origin(slot, signature);
+        token.dev_performance = dev_performance;
+
+        // Add to cache after successful creation
+        {
+            let mut cache_guard = self.cache.lock().await;
+            cache_guard.insert_token(
+                metadata.image.as_deref(),
+                Some(&data.uri),
+                metadata.description.as_deref(),
+                Some(&token.name),
+                Some(&token.ticker),
+            );
+        }
+
+        let total_time = t0.elapsed();
+
+        pretty_token_log(&token, Some(&metadata), meta_time, twitter_time, total_time);
+
+        Some(token)
+    }
+}
+
+fn pretty_token_log(
+    token: &Token,
+    metadata: Option<&Metadata>,
+    meta_time: Duration,
+    twitter_time: Option<Duration>,
+    total_time: Duration,
+) {
+    println!(
+        "\n [data: {}]new token
+├─ name:      {} ({})
+├─ mint:      {}
+├─ twitter:   {}
+├─ twitter_data: {:?}
+├─ dev_perf:  {}
+├─ token2022: {}
+├─ timing:
+│  ├─ metadata: {:>4} ms
+│  ├─ twitter:  {:>4} ms
+│  └─ total:    {:>4} ms",
+        metadata.is_some(),
+        token.name,
+        token.ticker,
+        token.mint,
+        token.twitter.is_some(),
+        metadata
+            .and_then(|m| m.twitter.as_ref())
+            .map(|s| s.as_str())
+            .unwrap_or("None"),
+        token.dev_performance.is_some(),
+        token.token_2022,
+        meta_time.as_millis(),
+        twitter_time.map(|t| t.as_millis()).unwrap_or(0),
+        total_time.as_millis(),
+    );
+}
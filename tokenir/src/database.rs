@@ -1,16 +1,144 @@
 use serde::{Deserialize, Serialize};
 use solana_sdk::pubkey::Pubkey;
-use sqlx::{postgres::PgPoolOptions, prelude::FromRow, PgPool, Pool, Postgres, Row, Transaction};
+use sqlx::{
+    postgres::PgPoolOptions, prelude::FromRow, PgPool, Pool, Postgres, QueryBuilder, Row,
+    Transaction,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
 
 use crate::{
-    access::{AddUserPayload, User},
+    access::{generate_key, key_prefix, AddUserPayload, HashedKey, Role, User},
+    audit::{AuditEntry, AuditEvent},
     constans::helper::pool_pda,
+    fetcher::since_epoch,
+    history::{Partition, TradeRecord, DAY},
+    migrations,
+    rug::DevActivity,
     Token,
 };
 
+const USER_COLUMNS: &str =
+    "id, key_prefix, hint, role, autobuy, max_sessions, expires_at, disabled";
+
+// Queries on the create path and the ATH updates. Public so their plans can
+// be checked to stay on the indexes of `0002_lookup_indexes.sql`.
+
+/// Binds image, ipfs, description, name, ticker. A `None` matches nothing.
+pub const TOKEN_ANY_EXISTS: &str = r#"
+    SELECT EXISTS(
+        SELECT 1 FROM tokens WHERE image = $1
+        UNION ALL
+        SELECT 1 FROM tokens WHERE ipfs = $2
+        UNION ALL
+        SELECT 1 FROM tokens WHERE description = $3 AND (name = $4 OR ticker = $5)
+        UNION ALL
+        SELECT 1 FROM tokens WHERE name = $4 AND ticker = $5
+    )
+"#;
+
+/// Binds dev address, excluded mint.
+pub const DEV_STATS_EXCLUDING: &str = r#"
+    SELECT
+        PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY ath)::BIGINT AS median,
+        COUNT(*)::BIGINT AS count,
+        COUNT(migrated_at)::BIGINT AS migrated,
+        COUNT(rugged_at)::BIGINT AS rugged
+    FROM tokens
+    WHERE dev_address = $1 AND mint != $2
+"#;
+
+/// Every column of `DbToken`, in the order of its fields. A macro so the
+/// queries built from it stay `const`.
+macro_rules! token_columns {
+    () => {
+        r#"
+            mint,
+            dev_address,
+            ath,
+            COALESCE(name, 'No name found') AS name,
+            COALESCE(ticker, 'No ticker found') AS ticker,
+            ipfs,
+            image,
+            description,
+            community_id,
+            pool_address,
+            slot,
+            signature,
+            completed_at,
+            migrated_at,
+            rugged_at
+        "#
+    };
+}
+
+/// Binds dev address, excluded mint, limit.
+pub const DEV_TOKENS_EXCLUDING: &str = concat!(
+    "SELECT",
+    token_columns!(),
+    r#"
+    FROM tokens
+    WHERE dev_address = $1 AND mint != $2
+    ORDER BY created_at DESC
+    LIMIT $3
+"#
+);
+
+/// Binds dev address, limit.
+const DEV_TOKENS: &str = concat!(
+    "SELECT",
+    token_columns!(),
+    r#"
+    FROM tokens
+    WHERE dev_address = $1
+    ORDER BY created_at DESC
+    LIMIT $2
+"#
+);
+
+/// Binds dev address.
+const ALL_DEV_TOKENS: &str = concat!(
+    "SELECT",
+    token_columns!(),
+    r#"
+    FROM tokens
+    WHERE dev_address = $1
+    ORDER BY created_at DESC
+"#
+);
+
+/// Binds community id.
+pub const TOKEN_COMMUNITY_EXISTS: &str = r#"
+    SELECT EXISTS(
+        SELECT 1 FROM tokens
+        WHERE community_id = $1
+    )
+"#;
+
+/// Binds pool address, ATH.
+pub const UPDATE_TOKEN_ATH: &str = r#"
+    UPDATE tokens
+    SET ath = GREATEST(ath, $2)
+    WHERE pool_address = $1
+"#;
+
+// Rows per multi-row statement, well below the 65535 binds Postgres takes
+const ROWS_PER_STATEMENT: usize = 1000;
+
+// Days of `trades` partitions created ahead of time
+const PARTITIONS_AHEAD: i64 = 2;
+
+// Admin key earlier versions seeded into every database
+const RETIRED_SEED_KEY: &str = "af3soy8thnhi06tsqc38talrs4a227ma";
+
 pub struct Database {
     connection_url: String,
     pool: Pool<Postgres>,
+    // Partitions of `trades` known to exist
+    trade_partitions: Mutex<HashSet<String>>,
 }
 
 impl Database {
@@ -23,6 +151,7 @@ impl Database {
         Ok(Self {
             pool,
             connection_url: url,
+            trade_partitions: Mutex::new(HashSet::new()),
         })
     }
 
@@ -60,61 +189,78 @@ impl Database {
         dev_address: &str,
         limit: i64,
     ) -> Result<Vec<DbToken>, sqlx::Error> {
-        let tokens = sqlx::query_as::<_, DbToken>(
+        let tokens = sqlx::query_as::<_, DbToken>(DEV_TOKENS)
+            .bind(clean(dev_address))
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tokens)
+    }
+
+    /// The user a key belongs to, if it is neither expired nor disabled.
+    pub async fn find_user_by_key(&self, key: &str) -> Result<Option<User>, sqlx::Error> {
+        let key = clean(key);
+        let candidates: Vec<(i32, String, String)> = sqlx::query_as(
             r#"
-            SELECT *
-            FROM tokens
-            WHERE dev_address = $1
-            ORDER BY created_at DESC
-            LIMIT $2
+            SELECT id, key_hash, key_salt FROM users
+            WHERE key_prefix = $1
+                AND NOT disabled
+                AND (expires_at IS NULL OR expires_at > EXTRACT(EPOCH FROM NOW())::BIGINT)
             "#,
         )
-        .bind(clean(dev_address))
-        .bind(limit)
-        .fetch_all(&self.pool)
+        .bind(key_prefix(&key))
+        .fetch_all(self.connection())
         .await?;
 
-        Ok(tokens)
+        let Some((id, _, _)) = candidates
+            .into_iter()
+            .find(|(_, hash, salt)| HashedKey::verify(&key, salt, hash))
+        else {
+            return Ok(None);
+        };
+
+        let user =
+            sqlx::query_as::<_, User>(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
+                .bind(id)
+                .fetch_optional(self.connection())
+                .await?;
+
+        Ok(user)
     }
 
     pub async fn validate_user_key(&self, key: &str) -> Result<bool, sqlx::Error> {
-        let result: Option<(i32,)> = sqlx::query_as("SELECT id FROM users WHERE access_key = $1")
-            .bind(clean(key))
-            .fetch_optional(self.connection())
-            .await?;
-
-        Ok(result.is_some())
+        Ok(self.find_user_by_key(key).await?.is_some())
     }
 
-    pub async fn add_user(
-        &self,
-        caller_admin_key: &str,
-        payload: AddUserPayload,
-    ) -> Result<(), sqlx::Error> {
-        let is_admin: (bool,) = sqlx::query_as("SELECT admin FROM users WHERE access_key = $1")
-            .bind(clean(caller_admin_key))
-            .fetch_one(self.connection())
-            .await?;
-
-        if !is_admin.0 {
-            return Err(sqlx::Error::RowNotFound);
-        }
-
+    pub async fn add_user(&self, payload: AddUserPayload) -> Result<(), sqlx::Error> {
         if payload.provided_key.len() != 32 {
             return Err(sqlx::Error::Protocol(
                 "Key must be exactly 32 characters".into(),
             ));
         }
 
+        if payload.max_sessions < 1 {
+            return Err(sqlx::Error::Protocol(
+                "A key needs at least one session".into(),
+            ));
+        }
+
+        let key = HashedKey::new(&clean(payload.provided_key));
         sqlx::query(
             r#"
-            INSERT INTO users (access_key, hint, admin, autobuy)
-            VALUES ($1, $2, false, $3)
+            INSERT INTO users (key_prefix, key_hash, key_salt, hint, role, autobuy, max_sessions, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             "#,
         )
-        .bind(clean(payload.provided_key))
+        .bind(key.prefix)
+        .bind(key.hash)
+        .bind(key.salt)
         .bind(clean(payload.hint))
+        .bind(payload.role)
         .bind(payload.autobuy)
+        .bind(payload.max_sessions)
+        .bind(payload.expires_at)
         .execute(self.connection())
         .await?;
 
@@ -122,34 +268,59 @@ impl Database {
     }
 
     pub async fn get_user_autobuy_status(&self, key: &str) -> Result<bool, sqlx::Error> {
-        let result: (bool,) = sqlx::query_as("SELECT autobuy FROM users WHERE access_key = $1")
-            .bind(clean(key))
-            .fetch_one(self.connection())
-            .await?;
+        self.find_user_by_key(key)
+            .await?
+            .map(|user| user.autobuy)
+            .ok_or(sqlx::Error::RowNotFound)
+    }
 
-        Ok(result.0)
+    /// Gives the user a new random key, the old one stops working. The new
+    /// key is only ever returned here.
+    pub async fn rotate_user_key(&self, user_id: i32) -> Result<String, sqlx::Error> {
+        let key = generate_key();
+        let hashed = HashedKey::new(&key);
+        let updated = sqlx::query(
+            "UPDATE users SET key_prefix = $1, key_hash = $2, key_salt = $3 WHERE id = $4",
+        )
+        .bind(hashed.prefix)
+        .bind(hashed.hash)
+        .bind(hashed.salt)
+        .bind(user_id)
+        .execute(self.connection())
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Err(sqlx::Error::RowNotFound);
+        }
+
+        Ok(key)
     }
 
-    pub async fn remove_user(
-        &self,
-        caller_admin_key: &str,
-        user_id: i32,
-    ) -> Result<(), sqlx::Error> {
-        let is_admin: (bool,) = sqlx::query_as("SELECT admin FROM users WHERE access_key = $1")
-            .bind(clean(caller_admin_key))
-            .fetch_one(self.connection())
-            .await?;
+    pub async fn set_user_disabled(&self, user_id: i32, disabled: bool) -> Result<(), sqlx::Error> {
+        // Admins can't lock each other out
+        let updated = sqlx::query(
+            "UPDATE users SET disabled = $1 WHERE id = $2 AND role IS DISTINCT FROM $3",
+        )
+        .bind(disabled)
+        .bind(user_id)
+        .bind(Role::Admin)
+        .execute(self.connection())
+        .await?;
 
-        if !is_admin.0 {
+        if updated.rows_affected() == 0 {
             return Err(sqlx::Error::RowNotFound);
         }
 
-        let target_admin: (bool,) = sqlx::query_as("SELECT admin FROM users WHERE id = $1")
+        Ok(())
+    }
+
+    pub async fn remove_user(&self, user_id: i32) -> Result<(), sqlx::Error> {
+        let target_role: (Option<Role>,) = sqlx::query_as("SELECT role FROM users WHERE id = $1")
             .bind(user_id)
             .fetch_one(self.connection())
             .await?;
 
-        if target_admin.0 {
+        if target_role.0 == Some(Role::Admin) {
             return Err(sqlx::Error::RowNotFound);
         }
 
@@ -161,92 +332,167 @@ impl Database {
         Ok(())
     }
 
-    pub async fn fetch_all_users(&self, caller_admin_key: &str) -> Result<Vec<User>, sqlx::Error> {
-        let is_admin: (bool,) = sqlx::query_as("SELECT admin FROM users WHERE access_key = $1")
-            .bind(caller_admin_key)
-            .fetch_one(self.connection())
-            .await?;
-
-        if !is_admin.0 {
-            return Err(sqlx::Error::RowNotFound);
-        }
-
+    pub async fn fetch_all_users(&self) -> Result<Vec<User>, sqlx::Error> {
         let users =
-            sqlx::query_as::<_, User>("SELECT id, access_key, hint, admin, autobuy FROM users")
+            sqlx::query_as::<_, User>(&format!("SELECT {} FROM users ORDER BY id", USER_COLUMNS))
                 .fetch_all(self.connection())
                 .await?;
 
         Ok(users)
     }
 
-    pub async fn initialize_tables(&self) -> Result<(), sqlx::Error> {
-        let pool = self.connection();
-
+    pub async fn record_audit(&self, event: AuditEvent) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS devs (
-                dev_address TEXT PRIMARY KEY,
-                total_token_count INTEGER NOT NULL DEFAULT 0
-            );
+            INSERT INTO audit_log (actor_id, actor, action, target, details, ip)
+            VALUES ($1, $2, $3, $4, $5, $6)
             "#,
         )
-        .execute(pool)
+        .bind(event.actor_id)
+        .bind(clean_opt(event.actor))
+        .bind(clean(event.action))
+        .bind(clean_opt(event.target))
+        .bind(clean_opt(event.details))
+        .bind(event.ip)
+        .execute(self.connection())
         .await?;
 
-        sqlx::query(
+        Ok(())
+    }
+
+    /// Newest entries first, `before` is the id of the last entry of the
+    /// previous page.
+    pub async fn fetch_audit(
+        &self,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        let entries = sqlx::query_as::<_, AuditEntry>(
             r#"
-            CREATE TABLE IF NOT EXISTS tokens (
-                mint TEXT PRIMARY KEY,
-                dev_address TEXT NOT NULL,
-                ath BIGINT NOT NULL DEFAULT 0,
-                created_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())::BIGINT,
-                name TEXT,
-                ticker TEXT,
-                ipfs TEXT,
-                image TEXT,
-                description TEXT,
-                community_id TEXT,
-                CONSTRAINT fk_dev FOREIGN KEY (dev_address)
-                    REFERENCES devs(dev_address)
-            );
+            SELECT id, at, actor_id, actor, action, target, details, ip FROM audit_log
+            WHERE $1::BIGINT IS NULL OR id < $1
+            ORDER BY id DESC
+            LIMIT $2
             "#,
         )
-        .execute(pool)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(self.connection())
         .await?;
 
+        Ok(entries)
+    }
+
+    /// Creates an admin with `key` when there is no usable admin yet, so a
+    /// fresh database can be managed without a key baked into the binary.
+    pub async fn bootstrap_admin(&self, key: Option<String>) -> Result<(), sqlx::Error> {
+        let admins: (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM users WHERE role = $1 AND NOT disabled")
+                .bind(Role::Admin)
+                .fetch_one(self.connection())
+                .await?;
+        if admins.0 > 0 {
+            return Ok(());
+        }
+
+        let Some(key) = key.map(clean).filter(|key| key.len() == 32) else {
+            eprintln!("[db] no admin user, set ADMIN_BOOTSTRAP_KEY to a 32 character key");
+            return Ok(());
+        };
+
+        let hashed = HashedKey::new(&key);
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id SERIAL PRIMARY KEY,
-                access_key CHAR(32) UNIQUE,
-                hint TEXT,
-                admin BOOLEAN DEFAULT false
-            );
+            INSERT INTO users (key_prefix, key_hash, key_salt, hint, role, max_sessions)
+            VALUES ($1, $2, $3, 'Admin', $4, 1)
             "#,
         )
-        .execute(pool)
+        .bind(&hashed.prefix)
+        .bind(hashed.hash)
+        .bind(hashed.salt)
+        .bind(Role::Admin)
+        .execute(self.connection())
         .await?;
 
-        sqlx::query(
-            r#"
-            INSERT INTO users (access_key, hint, admin)
-            VALUES ('af3soy8thnhi06tsqc38talrs4a227ma', 'Админ', true)
-            ON CONFLICT (access_key) DO NOTHING;
-            "#,
+        println!("[db] bootstrapped admin with key {}...", hashed.prefix);
+        Ok(())
+    }
+
+    /// Migrates the schema, then fixes up rows older releases left behind.
+    pub async fn initialize_tables(&self) -> Result<(), sqlx::Error> {
+        migrations::run(self.connection()).await?;
+
+        self.hash_plain_keys().await?;
+        self.backfill_pool_addresses().await?;
+
+        Ok(())
+    }
+
+    /// Tokens stored before `pool_address` existed get the PumpSwap pool of
+    /// their mint.
+    async fn backfill_pool_addresses(&self) -> Result<(), sqlx::Error> {
+        let mints: Vec<(String,)> = sqlx::query_as(
+            "SELECT mint FROM tokens WHERE pool_address IS NULL OR pool_address = ''",
         )
-        .execute(pool)
+        .fetch_all(self.connection())
         .await?;
+        if mints.is_empty() {
+            return Ok(());
+        }
+
+        let mut filled = 0;
+        for (mint,) in mints {
+            let Ok(pubkey) = mint.parse::<Pubkey>() else {
+                continue;
+            };
+            sqlx::query("UPDATE tokens SET pool_address = $1 WHERE mint = $2")
+                .bind(pool_pda(&pubkey).0.to_string())
+                .bind(&mint)
+                .execute(self.connection())
+                .await?;
+            filled += 1;
+        }
+        println!("[db] filled in the pool address of {} tokens", filled);
 
         Ok(())
     }
 
-    pub async fn get_key_by_id(&self, user_id: i32) -> Result<String, sqlx::Error> {
-        let result: (String,) = sqlx::query_as("SELECT access_key FROM users WHERE id = $1")
-            .bind(user_id)
-            .fetch_one(self.connection())
+    /// Moves keys stored in clear by older versions to their hashed columns.
+    async fn hash_plain_keys(&self) -> Result<(), sqlx::Error> {
+        let plain: Vec<(i32, String)> = sqlx::query_as(
+            "SELECT id, access_key FROM users WHERE access_key IS NOT NULL AND key_hash IS NULL",
+        )
+        .fetch_all(self.connection())
+        .await?;
+
+        for (id, key) in plain {
+            let key = clean(key);
+            let hashed = HashedKey::new(&key);
+            // The old seeded admin key is public, it never works again
+            let retired = key == RETIRED_SEED_KEY;
+
+            sqlx::query(
+                r#"
+                UPDATE users
+                SET key_prefix = $1, key_hash = $2, key_salt = $3, access_key = NULL,
+                    disabled = disabled OR $4
+                WHERE id = $5
+                "#,
+            )
+            .bind(hashed.prefix)
+            .bind(hashed.hash)
+            .bind(hashed.salt)
+            .bind(retired)
+            .bind(id)
+            .execute(self.connection())
             .await?;
 
-        Ok(result.0)
+            if retired {
+                println!("[db] disabled the old seeded admin key (user {})", id);
+            }
+        }
+
+        Ok(())
     }
 
     pub async fn add_dev(&self, dev: String) -> Result<(), sqlx::Error> {
@@ -287,9 +533,9 @@ impl Database {
         sqlx::query(
             r#"
             INSERT INTO tokens
-                (mint, dev_address, ath, name, ticker, ipfs, image, description, community_id, pool_address)
+                (mint, dev_address, ath, name, ticker, ipfs, image, description, community_id, pool_address, slot, signature)
             VALUES
-                ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
             ON CONFLICT (mint) DO UPDATE SET
                 ath = GREATEST(tokens.ath, EXCLUDED.ath),
                 name = COALESCE(NULLIF(EXCLUDED.name, ''), tokens.name),
@@ -298,7 +544,9 @@ impl Database {
                 image = COALESCE(EXCLUDED.image, tokens.image),
                 description = COALESCE(NULLIF(EXCLUDED.description, ''), tokens.description),
                 community_id = COALESCE(NULLIF(EXCLUDED.community_id, ''), tokens.community_id),
-                pool_address = COALESCE(NULLIF(EXCLUDED.pool_address, ''), tokens.pool_address)
+                pool_address = COALESCE(NULLIF(EXCLUDED.pool_address, ''), tokens.pool_address),
+                slot = COALESCE(tokens.slot, EXCLUDED.slot),
+                signature = COALESCE(tokens.signature, EXCLUDED.signature)
             "#,
         )
         .bind(mint.to_string())
@@ -311,6 +559,8 @@ impl Database {
         .bind(clean_opt(token.description.clone()))
         .bind(&clean_opt(token.community_id.clone()))
         .bind(&clean(token.pool_address.clone())) // bind new field
+        .bind(token.slot)
+        .bind(&token.signature)
         .execute(&mut *tx)
         .await?;
 
@@ -318,7 +568,71 @@ impl Database {
         Ok(())
     }
 
-    // возвращаем token_any_exists к прежнему виду без community_id
+    /// `add_token` for many tokens at once, one statement per table and chunk.
+    pub async fn add_tokens(&self, tokens: &[(DbToken, String)]) -> Result<(), sqlx::Error> {
+        for chunk in tokens.chunks(ROWS_PER_STATEMENT) {
+            let mut tx = self.connection().begin().await?;
+
+            // One row per dev, a conflict may only touch a row once
+            let mut devs: HashMap<String, i32> = HashMap::new();
+            for (_, dev_address) in chunk {
+                *devs.entry(clean(dev_address)).or_default() += 1;
+            }
+            let mut query =
+                QueryBuilder::<Postgres>::new("INSERT INTO devs (dev_address, total_token_count) ");
+            query.push_values(devs, |mut row, (dev_address, count)| {
+                row.push_bind(dev_address).push_bind(count);
+            });
+            query.push(
+                r#"
+                ON CONFLICT (dev_address)
+                DO UPDATE SET total_token_count = devs.total_token_count + EXCLUDED.total_token_count
+                "#,
+            );
+            query.build().execute(&mut *tx).await?;
+
+            let mut query = QueryBuilder::<Postgres>::new(
+                "INSERT INTO tokens (mint, dev_address, ath, name, ticker, ipfs, image, description, community_id, pool_address, slot, signature) ",
+            );
+            query.push_values(chunk, |mut row, (token, dev_address)| {
+                row.push_bind(clean(&token.mint))
+                    .push_bind(clean(dev_address))
+                    .push_bind(token.ath)
+                    .push_bind(clean(&token.name))
+                    .push_bind(clean(&token.ticker))
+                    .push_bind(clean_opt(token.ipfs.as_ref()))
+                    .push_bind(clean_opt(token.image.as_ref()))
+                    .push_bind(clean_opt(token.description.as_ref()))
+                    .push_bind(clean_opt(token.community_id.as_ref()))
+                    .push_bind(clean(&token.pool_address))
+                    .push_bind(token.slot)
+                    .push_bind(token.signature.clone());
+            });
+            query.push(
+                r#"
+                ON CONFLICT (mint) DO UPDATE SET
+                    ath = GREATEST(tokens.ath, EXCLUDED.ath),
+                    name = COALESCE(NULLIF(EXCLUDED.name, ''), tokens.name),
+                    ticker = COALESCE(NULLIF(EXCLUDED.ticker, ''), tokens.ticker),
+                    ipfs = COALESCE(EXCLUDED.ipfs, tokens.ipfs),
+                    image = COALESCE(EXCLUDED.image, tokens.image),
+                    description = COALESCE(NULLIF(EXCLUDED.description, ''), tokens.description),
+                    community_id = COALESCE(NULLIF(EXCLUDED.community_id, ''), tokens.community_id),
+                    pool_address = COALESCE(NULLIF(EXCLUDED.pool_address, ''), tokens.pool_address),
+                    slot = COALESCE(tokens.slot, EXCLUDED.slot),
+                    signature = COALESCE(tokens.signature, EXCLUDED.signature)
+                "#,
+            );
+            query.build().execute(&mut *tx).await?;
+
+            tx.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether a token with the same image, metadata uri, description with
+    /// name or ticker, or name and ticker was stored already.
     pub async fn token_any_exists(
         &self,
         name: Option<&str>,
@@ -327,27 +641,15 @@ impl Database {
         image: Option<&str>,
         description: Option<&str>,
     ) -> Result<bool, sqlx::Error> {
-        let row: Option<(bool,)> = sqlx::query_as(
-            r#"
-            SELECT EXISTS(
-                SELECT 1 FROM tokens
-                WHERE ($1 IS NOT NULL AND image = $1)
-                   OR ($2 IS NOT NULL AND ipfs = $2)
-                   OR ($3 IS NOT NULL AND $6 IS NOT NULL AND description = $3 AND name = $6)
-                   OR ($3 IS NOT NULL AND $7 IS NOT NULL AND description = $3 AND ticker = $7)
-                   OR ($4 IS NOT NULL AND $5 IS NOT NULL AND name = $4 AND ticker = $5)
-            )
-            "#,
-        )
-        .bind(image) // $1
-        .bind(ipfs) // $2
-        .bind(description) // $3
-        .bind(name) // $4
-        .bind(ticker) // $5
-        .bind(name) // $6 for (description + name)
-        .bind(ticker) // $7 for (description + ticker)
-        .fetch_optional(self.connection())
-        .await?;
+        // Each branch is its own index lookup, EXISTS stops at the first hit
+        let row: Option<(bool,)> = sqlx::query_as(TOKEN_ANY_EXISTS)
+            .bind(image) // $1
+            .bind(ipfs) // $2
+            .bind(description) // $3
+            .bind(name) // $4
+            .bind(ticker) // $5
+            .fetch_optional(self.connection())
+            .await?;
 
         Ok(row.map(|r| r.0).unwrap_or(false))
     }
@@ -358,20 +660,12 @@ impl Database {
         exclude_mint: &str,
         limit: i64,
     ) -> Result<Vec<DbToken>, sqlx::Error> {
-        let tokens = sqlx::query_as::<_, DbToken>(
-            r#"
-            SELECT *
-            FROM tokens
-            WHERE dev_address = $1 AND mint != $2
-            ORDER BY created_at DESC
-            LIMIT $3
-            "#,
-        )
-        .bind(clean(dev_address))
-        .bind(exclude_mint)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+        let tokens = sqlx::query_as::<_, DbToken>(DEV_TOKENS_EXCLUDING)
+            .bind(clean(dev_address))
+            .bind(exclude_mint)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
 
         Ok(tokens)
     }
@@ -381,86 +675,259 @@ impl Database {
         &self,
         dev_address: &str,
         exclude_mint: &str,
-    ) -> Result<Option<(i64, usize)>, sqlx::Error> {
-        let row = sqlx::query(
-            r#"
-            SELECT
-                PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY ath)::BIGINT AS median,
-                COUNT(*)::BIGINT AS count
-            FROM tokens
-            WHERE dev_address = $1 AND mint != $2
-            "#,
-        )
-        .bind(clean(dev_address))
-        .bind(exclude_mint)
-        .fetch_one(&self.pool)
-        .await?;
+    ) -> Result<Option<(i64, usize, usize, usize)>, sqlx::Error> {
+        let row = sqlx::query(DEV_STATS_EXCLUDING)
+            .bind(clean(dev_address))
+            .bind(exclude_mint)
+            .fetch_one(&self.pool)
+            .await?;
 
         let median: Option<i64> = row.get("median");
         let count: i64 = row.get("count");
+        let migrated: i64 = row.get("migrated");
+        let rugged: i64 = row.get("rugged");
 
-        Ok(median.map(|m| (m, count as usize)))
+        Ok(median.map(|m| (m, count as usize, migrated as usize, rugged as usize)))
     }
 
     pub async fn token_community_exists(&self, community_id: &str) -> Result<bool, sqlx::Error> {
-        let row: Option<(bool,)> = sqlx::query_as(
-            r#"
-            SELECT EXISTS(
-                SELECT 1 FROM tokens
-                WHERE community_id = $1
-            )
-            "#,
-        )
-        .bind(community_id)
-        .fetch_optional(self.connection())
-        .await?;
+        let row: Option<(bool,)> = sqlx::query_as(TOKEN_COMMUNITY_EXISTS)
+            .bind(community_id)
+            .fetch_optional(self.connection())
+            .await?;
 
         Ok(row.map(|r| r.0).unwrap_or(false))
     }
 
+    /// Keyed by the PumpSwap pool address, which curve trades carry as well,
+    /// so the ATH keeps updating after migration.
     pub async fn update_token_ath(
         &self,
         pool_address: &Pubkey,
         ath: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(UPDATE_TOKEN_ATH)
+            .bind(pool_address.to_string())
+            .bind(ath)
+            .execute(self.connection())
+            .await?;
+
+        Ok(())
+    }
+
+    /// `update_token_ath` for many pools at once, each pool at most once.
+    pub async fn update_token_aths(&self, aths: &[(Pubkey, i64)]) -> Result<(), sqlx::Error> {
+        for chunk in aths.chunks(ROWS_PER_STATEMENT) {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "UPDATE tokens SET ath = GREATEST(tokens.ath, v.ath) FROM (",
+            );
+            query.push_values(chunk, |mut row, (pool_address, ath)| {
+                row.push_bind(pool_address.to_string()).push_bind(*ath);
+            });
+            query.push(") AS v(pool_address, ath) WHERE tokens.pool_address = v.pool_address");
+            query.build().execute(self.connection()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Stores the dev totals of tokens by pool. Totals only grow and the
+    /// first sell and rug times stay once set, so a late retry changes nothing.
+    pub async fn update_dev_activity(
+        &self,
+        activity: &[(Pubkey, DevActivity)],
+    ) -> Result<(), sqlx::Error> {
+        for chunk in activity.chunks(ROWS_PER_STATEMENT) {
+            let mut query = QueryBuilder::<Postgres>::new(
+                r#"
+                UPDATE tokens SET
+                    dev_bought_sol = GREATEST(tokens.dev_bought_sol, v.bought_sol),
+                    dev_sold_sol = GREATEST(tokens.dev_sold_sol, v.sold_sol),
+                    dev_bought_tokens = GREATEST(tokens.dev_bought_tokens, v.bought_tokens),
+                    dev_sold_tokens = GREATEST(tokens.dev_sold_tokens, v.sold_tokens),
+                    first_dev_sell_at = COALESCE(tokens.first_dev_sell_at, v.first_sell_at),
+                    rugged_at = COALESCE(tokens.rugged_at, v.rugged_at)
+                FROM ("#,
+            );
+            query.push_values(chunk, |mut row, (pool_address, activity)| {
+                row.push_bind(pool_address.to_string())
+                    .push_bind(activity.bought_sol as i64)
+                    .push_bind(activity.sold_sol as i64)
+                    .push_bind(activity.bought_tokens as i64)
+                    .push_bind(activity.sold_tokens as i64)
+                    .push_bind(activity.first_sell_at)
+                    .push_bind(activity.rugged_at);
+            });
+            query.push(
+                ") AS v(pool_address, bought_sol, sold_sol, bought_tokens, sold_tokens, first_sell_at, rugged_at) WHERE tokens.pool_address = v.pool_address",
+            );
+            query.build().execute(self.connection()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Appends to the trade history, creating the partitions it needs.
+    pub async fn add_trades(&self, trades: &[TradeRecord]) -> Result<(), sqlx::Error> {
+        let mut days: Vec<i64> = trades
+            .iter()
+            .map(|trade| trade.timestamp.div_euclid(DAY))
+            .collect();
+        days.sort_unstable();
+        days.dedup();
+        for day in days {
+            self.create_trade_partition(&Partition::containing(day * DAY))
+                .await?;
+        }
+
+        // All or nothing, so a retry never stores a trade twice
+        let mut tx = self.connection().begin().await?;
+        for chunk in trades.chunks(ROWS_PER_STATEMENT) {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "INSERT INTO trades (mint, pool, user_address, is_buy, sol_amount, token_amount, virtual_sol_reserves, virtual_token_reserves, slot, signature, timestamp) ",
+            );
+            query.push_values(chunk, |mut row, trade| {
+                row.push_bind(&trade.mint)
+                    .push_bind(&trade.pool)
+                    .push_bind(&trade.user)
+                    .push_bind(trade.is_buy)
+                    .push_bind(trade.sol_amount)
+                    .push_bind(trade.token_amount)
+                    .push_bind(trade.virtual_sol_reserves)
+                    .push_bind(trade.virtual_token_reserves)
+                    .push_bind(trade.slot)
+                    .push_bind(&trade.signature)
+                    .push_bind(trade.timestamp);
+            });
+            query.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn create_trade_partition(&self, partition: &Partition) -> Result<(), sqlx::Error> {
+        if self
+            .trade_partitions
+            .lock()
+            .unwrap()
+            .contains(&partition.name)
+        {
+            return Ok(());
+        }
+
+        // Names and bounds come from `Partition`, never from input
+        sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS {} PARTITION OF trades FOR VALUES FROM ({}) TO ({})",
+            partition.name, partition.from, partition.to
+        ))
+        .execute(self.connection())
+        .await?;
+
+        self.trade_partitions
+            .lock()
+            .unwrap()
+            .insert(partition.name.clone());
+        Ok(())
+    }
+
+    /// Creates the partitions of the coming days and drops the ones that
+    /// ended more than `retention` ago. Returns how many were dropped.
+    pub async fn maintain_trade_partitions(
+        &self,
+        retention: Duration,
+    ) -> Result<usize, sqlx::Error> {
+        let now = since_epoch().as_secs() as i64;
+        for day in 0..=PARTITIONS_AHEAD {
+            self.create_trade_partition(&Partition::containing(now + day * DAY))
+                .await?;
+        }
+
+        let names: Vec<(String,)> = sqlx::query_as(
+            r#"
+            SELECT child.relname::TEXT
+            FROM pg_inherits
+            JOIN pg_class child ON child.oid = pg_inherits.inhrelid
+            WHERE pg_inherits.inhparent = 'trades'::regclass
+            "#,
+        )
+        .fetch_all(self.connection())
+        .await?;
+
+        let cutoff = now - retention.as_secs() as i64;
+        let mut dropped = 0;
+        for partition in names
+            .iter()
+            .filter_map(|(name,)| Partition::from_name(name))
+            .filter(|partition| partition.to <= cutoff)
+        {
+            sqlx::query(&format!("DROP TABLE IF EXISTS {}", partition.name))
+                .execute(self.connection())
+                .await?;
+            self.trade_partitions
+                .lock()
+                .unwrap()
+                .remove(&partition.name);
+            println!("[db] dropped trades partition {}", partition.name);
+            dropped += 1;
+        }
+
+        Ok(dropped)
+    }
+
+    /// Records the bonding curve completion, the first one seen wins.
+    pub async fn mark_token_complete(
+        &self,
+        mint: &Pubkey,
+        timestamp: i64,
     ) -> Result<(), sqlx::Error> {
         sqlx::query(
             r#"
             UPDATE tokens
-            SET ath = GREATEST(ath, $2)
-            WHERE pool_address = $1
+            SET completed_at = COALESCE(completed_at, $2)
+            WHERE mint = $1
             "#,
         )
-        .bind(pool_address.to_string())
-        .bind(ath)
+        .bind(mint.to_string())
+        .bind(timestamp)
         .execute(self.connection())
         .await?;
 
         Ok(())
     }
 
-    pub async fn get_tokens_by_dev(&self, dev_address: &str) -> Result<Vec<DbToken>, sqlx::Error> {
-        let tokens = sqlx::query_as::<_, DbToken>(
+    /// Records the migration into a PumpSwap pool. Implies completion, in case
+    /// the complete event was missed.
+    pub async fn mark_token_migrated(
+        &self,
+        mint: &Pubkey,
+        pool_address: &Pubkey,
+        timestamp: i64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
             r#"
-            SELECT
-                mint,
-                dev_address,
-                ath,
-                COALESCE(name, 'No name found') AS name,
-                COALESCE(ticker, 'No ticker found') AS ticker,
-                ipfs,
-                image,
-                description,
-                community_id,
-                pool_address
-            FROM tokens
-            WHERE dev_address = $1
-            ORDER BY created_at DESC
+            UPDATE tokens
+            SET migrated_at = COALESCE(migrated_at, $3),
+                completed_at = COALESCE(completed_at, $3),
+                pool_address = $2
+            WHERE mint = $1
             "#,
         )
-        .bind(clean(dev_address))
-        .fetch_all(self.connection())
+        .bind(mint.to_string())
+        .bind(pool_address.to_string())
+        .bind(timestamp)
+        .execute(self.connection())
         .await?;
 
+        Ok(())
+    }
+
+    pub async fn get_tokens_by_dev(&self, dev_address: &str) -> Result<Vec<DbToken>, sqlx::Error> {
+        let tokens = sqlx::query_as::<_, DbToken>(ALL_DEV_TOKENS)
+            .bind(clean(dev_address))
+            .fetch_all(self.connection())
+            .await?;
+
         Ok(tokens)
     }
 
@@ -485,6 +952,12 @@ pub struct DbToken {
     pub description: Option<String>,
     pub community_id: Option<String>,
     pub pool_address: String,
+    pub slot: Option<i64>,
+    pub signature: Option<String>,
+    pub completed_at: Option<i64>,
+    pub migrated_at: Option<i64>,
+    /// When the dev dumped the token, see `rug::DevTracker`
+    pub rugged_at: Option<i64>,
 }
 
 fn clean(s: impl AsRef<str>) -> String {
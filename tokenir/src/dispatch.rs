@@ -0,0 +1,270 @@
+use dashmap::{mapref::entry::Entry, DashMap};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::hash_map::DefaultHasher,
+    future::Future,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::mpsc;
+
+use crate::{constans::helper::pool_pda, logs::Event, source::EventEnvelope};
+
+#[derive(Debug, Clone, Copy)]
+pub struct DispatchConfig {
+    /// Callbacks running at once, one per lane
+    pub concurrency: usize,
+    /// Lanes reserved for creates, whose metadata lookups take seconds
+    pub creates: usize,
+    /// Events waiting per lane before new ones are shed
+    pub queue: usize,
+}
+
+impl Default for DispatchConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 64,
+            creates: 16,
+            queue: 256,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DispatchStats {
+    dispatched: AtomicU64,
+    shed: AtomicU64,
+}
+
+impl DispatchStats {
+    pub fn dispatched(&self) -> u64 {
+        self.dispatched.load(Ordering::Relaxed)
+    }
+
+    /// Events dropped because their lane was full.
+    pub fn shed(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
+    fn shed_one(&self, envelope: &EventEnvelope) {
+        let shed = self.shed.fetch_add(1, Ordering::Relaxed) + 1;
+        if shed == 1 || shed.is_multiple_of(100) {
+            eprintln!(
+                "[dispatch] lane full, shedding {} ({} shed so far)",
+                envelope.event.mint(),
+                shed
+            );
+        }
+    }
+}
+
+/// Runs event callbacks off the read loop with bounded concurrency.
+///
+/// Every token is pinned to one lane (by its PumpSwap pool address, which all
+/// event kinds can be mapped to), so its create, trades and graduation are
+/// handled one after another in arrival order. Lanes run concurrently. When a
+/// lane's queue is full the event is dropped and counted rather than making
+/// the stream wait.
+///
+/// Creates run on lanes of their own so their slow lookups don't hold up the
+/// trades of unrelated tokens. Events of a token whose create is still running
+/// are parked and handed to its lane once the create is done.
+#[derive(Clone)]
+pub struct Dispatcher {
+    lanes: Lanes,
+    creates: Lanes,
+    parked: Arc<DashMap<Pubkey, Vec<EventEnvelope>>>,
+    queue: usize,
+}
+
+#[derive(Clone)]
+struct Lanes {
+    senders: Vec<mpsc::Sender<EventEnvelope>>,
+    stats: Arc<DispatchStats>,
+}
+
+impl Lanes {
+    /// Queues the event on the lane of `key`, never waits.
+    fn send(&self, key: &Pubkey, envelope: EventEnvelope) -> bool {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let lane = &self.senders[hasher.finish() as usize % self.senders.len()];
+
+        match lane.try_send(envelope) {
+            Ok(()) => {
+                self.stats.dispatched.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+            Err(mpsc::error::TrySendError::Full(envelope)) => {
+                self.stats.shed_one(&envelope);
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+}
+
+impl Dispatcher {
+    /// Spawns one worker per lane, each with its own copy of `func`.
+    pub fn new<F, Fut>(config: DispatchConfig, func: F) -> Self
+    where
+        F: FnMut(EventEnvelope) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self::with_stats(config, func, Arc::default())
+    }
+
+    pub(crate) fn with_stats<F, Fut>(
+        config: DispatchConfig,
+        func: F,
+        stats: Arc<DispatchStats>,
+    ) -> Self
+    where
+        F: FnMut(EventEnvelope) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let queue = config.queue.max(1);
+        let parked: Arc<DashMap<Pubkey, Vec<EventEnvelope>>> = Arc::default();
+
+        let senders = (0..config.concurrency.max(1))
+            .map(|_| {
+                let (tx, mut rx) = mpsc::channel::<EventEnvelope>(queue);
+                let mut func = func.clone();
+                tokio::spawn(async move {
+                    while let Some(envelope) = rx.recv().await {
+                        func(envelope).await;
+                    }
+                });
+                tx
+            })
+            .collect();
+        let lanes = Lanes {
+            senders,
+            stats: stats.clone(),
+        };
+
+        let senders = (0..config.creates.max(1))
+            .map(|_| {
+                let (tx, mut rx) = mpsc::channel::<EventEnvelope>(queue);
+                let mut func = func.clone();
+                let lanes = lanes.clone();
+                let parked = parked.clone();
+                tokio::spawn(async move {
+                    while let Some(envelope) = rx.recv().await {
+                        let key = ordering_key(&envelope.event);
+                        func(envelope).await;
+                        release(&parked, &lanes, &key);
+                    }
+                });
+                tx
+            })
+            .collect();
+        let creates = Lanes { senders, stats };
+
+        Self {
+            lanes,
+            creates,
+            parked,
+            queue,
+        }
+    }
+
+    pub fn stats(&self) -> Arc<DispatchStats> {
+        self.lanes.stats.clone()
+    }
+
+    /// Queues the event on its token's lane, never waits.
+    pub fn dispatch(&self, envelope: EventEnvelope) {
+        let key = ordering_key(&envelope.event);
+
+        if matches!(envelope.event, Event::Create(_)) {
+            if let Entry::Vacant(entry) = self.parked.entry(key) {
+                // Parked before queueing, the create may finish right away
+                entry.insert(Vec::new());
+                if !self.creates.send(&key, envelope) {
+                    release(&self.parked, &self.lanes, &key);
+                }
+                return;
+            }
+        }
+
+        // Released through `lanes`, counted there
+        if let Some(mut parked) = self.parked.get_mut(&key) {
+            if parked.len() < self.queue {
+                parked.push(envelope);
+            } else {
+                self.lanes.stats.shed_one(&envelope);
+            }
+            return;
+        }
+
+        self.lanes.send(&key, envelope);
+    }
+}
+
+/// Hands the events parked behind a finished create to the token's lane.
+fn release(parked: &DashMap<Pubkey, Vec<EventEnvelope>>, lanes: &Lanes, key: &Pubkey) {
+    loop {
+        let waiting = match parked.get_mut(key) {
+            Some(mut waiting) => std::mem::take(&mut *waiting),
+            None => return,
+        };
+        // Events parked meanwhile are taken on the next round, the entry only
+        // goes once it is empty so nothing overtakes them
+        if waiting.is_empty()
+            && parked
+                .remove_if(key, |_, waiting| waiting.is_empty())
+                .is_some()
+        {
+            return;
+        }
+        for envelope in waiting {
+            lanes.send(key, envelope);
+        }
+    }
+}
+
+/// Key shared by every event of one token.
+fn ordering_key(event: &Event) -> Pubkey {
+    match event {
+        Event::Create(create) => pool_pda(&create.mint).0,
+        // Curve trades already carry the pool
+        Event::Buy(buy) => buy.mint,
+        Event::Sell(sell) => sell.mint,
+        Event::Complete(complete) => complete.pool,
+        Event::Migrated(migrated) => migrated.pool,
+        Event::PoolCreated(created) => created.pool,
+        Event::AmmTrade(trade) => trade.pool,
+    }
+}
+
+/// Where a stream hands its events: straight to the callback, or through a
+/// dispatcher when one is configured.
+#[derive(Clone)]
+pub(crate) enum Sink<F> {
+    Inline(F),
+    Dispatched(Dispatcher),
+}
+
+impl<F, Fut> Sink<F>
+where
+    F: FnMut(EventEnvelope) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    pub(crate) fn new(func: F, config: Option<DispatchConfig>, stats: &Arc<DispatchStats>) -> Self {
+        match config {
+            Some(config) => Self::Dispatched(Dispatcher::with_stats(config, func, stats.clone())),
+            None => Self::Inline(func),
+        }
+    }
+
+    pub(crate) async fn send(&mut self, envelope: EventEnvelope) {
+        match self {
+            Sink::Inline(func) => func(envelope).await,
+            Sink::Dispatched(dispatcher) => dispatcher.dispatch(envelope),
+        }
+    }
+}
@@ -1,13 +1,21 @@
 use crate::{
+    amm::AmmPoolCache,
+    capture::CaptureWriter,
     constans::{
         self,
         helper::{calc_price_impact, pool_pda},
     },
+    dispatch::{DispatchConfig, DispatchStats, Sink},
+    health::{Backoff, ConnectionHealth, HealthRegistry},
     logs::{
-        BuyEvent, BuyEventAMM, CreateEvent, CreateEventV2, Event, PumpCreateEvent, SellEvent,
-        SellEventAMM, TradeEvent,
+        AmmSwap, BuyEvent, BuyEventAMM, CreateEvent, CreateEventV2, CreatePoolEventAMM,
+        CurveCompleteEvent, Event, MigratedEvent, PumpCreateEvent, SellEvent, SellEventAMM,
+        TradeEvent,
     },
+    lookup::LookupTableCache,
     requests::LogsNotification,
+    source::{EventEnvelope, SourceKind},
+    ParseError, ParseStats,
 };
 
 use jito_protos::shredstream::{
@@ -19,9 +27,17 @@ use base64::Engine;
 use borsh::{BorshDeserialize, BorshSerialize};
 use futures::{SinkExt, StreamExt};
 use serde_json::from_str;
+use solana_sdk::instruction::CompiledInstruction;
 use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
-use std::{future::Future, time::Duration};
+use solana_sdk::signature::Signature;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
 use tokio::time::sleep;
 use tokio_tungstenite::{
     connect_async,
@@ -30,11 +46,50 @@ use tokio_tungstenite::{
 
 pub struct Client {
     url: String,
+    include_failed: bool,
+    pools: Option<Arc<AmmPoolCache>>,
+    capture: Option<Arc<CaptureWriter>>,
+    stats: Arc<ParseStats>,
+    dispatch: Option<DispatchConfig>,
+    dispatch_stats: Arc<DispatchStats>,
+    health: Option<(Arc<HealthRegistry>, String)>,
 }
 
 const PUMP_PROGRAM: Pubkey = pubkey!("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P");
-const CREATE_V2_DISCRIMINATOR: &[u8] = &[214, 144, 76, 236, 95, 139, 49, 180];
-const BUY_DISCRIMINATOR: &[u8] = &[102, 6, 61, 18, 1, 218, 235, 234];
+const PUMP_AMM_PROGRAM: Pubkey = pubkey!("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA");
+const TOKEN_2022_PROGRAM: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+const CREATE_IX_DISCRIMINATOR: [u8; 8] = [24, 30, 200, 40, 5, 28, 7, 119];
+const CREATE_V2_DISCRIMINATOR: [u8; 8] = [214, 144, 76, 236, 95, 139, 49, 180];
+const BUY_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+const SELL_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+
+// Pump.fun instructions that produce no event we track. `buy_exact_sol_in`
+// is left to the logs path, which sees the trade event it emits
+const IGNORED_IX_DISCRIMINATORS: &[[u8; 8]] = &[
+    [56, 252, 116, 8, 158, 223, 205, 95],     // buy_exact_sol_in
+    [20, 22, 86, 123, 198, 28, 219, 132],     // collect_creator_fee
+    [234, 102, 194, 203, 150, 72, 62, 229],   // extend_account
+    [27, 234, 178, 52, 147, 2, 187, 141],     // set_params
+    [254, 148, 255, 112, 207, 142, 170, 165], // set_creator
+    [138, 96, 174, 217, 48, 85, 197, 246],    // set_metaplex_creator
+    [155, 234, 231, 146, 236, 158, 162, 30],  // migrate
+    [16, 4, 71, 28, 204, 1, 40, 27],          // claim_token_incentives
+    [94, 6, 202, 115, 255, 96, 232, 183],     // init_user_volume_accumulator
+    [86, 31, 192, 87, 163, 87, 79, 238],      // sync_user_volume_accumulator
+    [249, 69, 164, 218, 150, 103, 84, 138],   // close_user_volume_accumulator
+    [175, 175, 109, 31, 13, 152, 155, 237],   // initialize
+    [227, 181, 74, 196, 208, 21, 97, 213],    // update_global_authority
+    [69, 25, 171, 142, 57, 239, 13, 4],       // admin_set_creator
+    [8, 217, 96, 231, 144, 104, 192, 5],      // admin_set_idl_authority
+    [209, 11, 115, 87, 213, 23, 124, 204],    // admin_update_token_incentives
+];
+
+// Initial virtual reserves of a fresh bonding curve
+const INITIAL_VIRTUAL_SOL_RESERVES: u64 = 30_000_000_000;
+const INITIAL_VIRTUAL_TOKEN_RESERVES: u64 = 1_073_000_000_000_000;
+
+// How many curves the shredstream keeps reserve estimates for
+const TRACKED_CURVES: usize = 50_000;
 
 // Internal structs for Borsh (mapping raw instruction data)
 #[derive(BorshSerialize, BorshDeserialize)]
@@ -46,12 +101,102 @@ struct CreateV2Args {
     mayhem: bool,
 }
 
+// Legacy `create`. Older transactions have no trailing creator, so it is read separately.
+#[derive(BorshSerialize, BorshDeserialize)]
+struct CreateArgs {
+    name: String,
+    symbol: String,
+    uri: String,
+}
+
 #[derive(BorshSerialize, BorshDeserialize)]
 struct BuyArgs {
     amount: u64,
     max_sol: u64,
 }
 
+#[derive(BorshSerialize, BorshDeserialize)]
+struct SellArgs {
+    amount: u64,
+    min_sol: u64,
+}
+
+/// Locally estimated reserves of a bonding curve, advanced by every decoded
+/// buy/sell instruction. Only curves created while the stream is up are known.
+struct CurveState {
+    mint: Pubkey,
+    pool: Pubkey,
+    virtual_sol_reserves: u64,
+    virtual_token_reserves: u64,
+}
+
+struct CurveTracker {
+    curves: HashMap<Pubkey, CurveState>,
+    order: VecDeque<Pubkey>,
+    max_size: usize,
+}
+
+impl CurveTracker {
+    fn new(max_size: usize) -> Self {
+        Self {
+            curves: HashMap::new(),
+            order: VecDeque::new(),
+            max_size,
+        }
+    }
+
+    fn insert(&mut self, bonding_curve: Pubkey, mint: &Pubkey) {
+        let state = CurveState {
+            mint: *mint,
+            pool: pool_pda(mint).0,
+            virtual_sol_reserves: INITIAL_VIRTUAL_SOL_RESERVES,
+            virtual_token_reserves: INITIAL_VIRTUAL_TOKEN_RESERVES,
+        };
+
+        if self.curves.insert(bonding_curve, state).is_none() {
+            self.order.push_back(bonding_curve);
+        }
+
+        while self.order.len() > self.max_size {
+            if let Some(front) = self.order.pop_front() {
+                self.curves.remove(&front);
+            }
+        }
+    }
+
+    /// Applies a trade to the constant-product curve and returns the estimated
+    /// SOL amount together with the reserves after the trade.
+    fn apply(
+        &mut self,
+        bonding_curve: &Pubkey,
+        token_amount: u64,
+        is_buy: bool,
+    ) -> Option<(&CurveState, u64)> {
+        let state = self.curves.get_mut(bonding_curve)?;
+
+        let sol = state.virtual_sol_reserves as u128;
+        let tokens = state.virtual_token_reserves as u128;
+        let amount = token_amount as u128;
+
+        let sol_amount = if is_buy {
+            if amount >= tokens {
+                return None;
+            }
+            let cost = sol * amount / (tokens - amount);
+            state.virtual_sol_reserves = (sol + cost) as u64;
+            state.virtual_token_reserves = (tokens - amount) as u64;
+            cost
+        } else {
+            let out = sol * amount / (tokens + amount);
+            state.virtual_sol_reserves = (sol - out) as u64;
+            state.virtual_token_reserves = (tokens + amount) as u64;
+            out
+        };
+
+        Some((state, sol_amount as u64))
+    }
+}
+
 use chrono::Local;
 
 // Inline for zero-cost abstraction
@@ -63,22 +208,86 @@ fn ts(step: &str) {
 impl Client {
     #[inline]
     pub fn new(url: String) -> Self {
-        Self { url }
+        Self {
+            url,
+            include_failed: false,
+            pools: None,
+            capture: None,
+            stats: Arc::new(ParseStats::default()),
+            dispatch: None,
+            dispatch_stats: Arc::new(DispatchStats::default()),
+            health: None,
+        }
+    }
+
+    /// Parse failures of this client's streams, by kind.
+    pub fn parse_stats(&self) -> Arc<ParseStats> {
+        self.stats.clone()
+    }
+
+    /// Pool cache used to resolve PumpSwap trades. Without one, AMM swaps
+    /// are dropped since their token can't be told.
+    pub fn with_amm_pools(mut self, pools: Arc<AmmPoolCache>) -> Self {
+        self.pools = Some(pools);
+        self
+    }
+
+    /// Append every raw message received to a capture file, for replay.
+    pub fn with_capture(mut self, capture: Arc<CaptureWriter>) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Run the callback on a bounded pool of per-token lanes instead of
+    /// awaiting it inside the read loop. Events of one token keep their order,
+    /// events that don't fit in their lane are shed and counted.
+    pub fn with_dispatch(mut self, config: DispatchConfig) -> Self {
+        self.dispatch = Some(config);
+        self
+    }
+
+    /// Dispatched and shed events, only counted with `with_dispatch`.
+    pub fn dispatch_stats(&self) -> Arc<DispatchStats> {
+        self.dispatch_stats.clone()
+    }
+
+    /// Report the state of every stream this client opens to `registry`,
+    /// named `<name> <stream>`.
+    pub fn with_health(mut self, registry: Arc<HealthRegistry>, name: impl Into<String>) -> Self {
+        self.health = Some((registry, name.into()));
+        self
+    }
+
+    /// Also deliver events logged by transactions that failed on chain
+    /// (dropped by default).
+    pub fn include_failed(mut self, include: bool) -> Self {
+        self.include_failed = include;
+        self
     }
 
     pub async fn subscribe_to_pump<F, Fut>(&self, func: F, amm: bool) -> Result<(), Error>
     where
-        F: FnMut((Duration, Event)) -> Fut + Clone + Send + 'static,
-        Fut: Future<Output = ()> + Send,
+        F: FnMut(EventEnvelope) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
     {
+        // Both subscriptions share the lanes, so a token's curve and AMM
+        // trades stay in order
+        let sink = Sink::new(func, self.dispatch, &self.dispatch_stats);
+
         let pump_handle = {
-            let func = func.clone();
+            let sink = sink.clone();
             let url = self.url.clone();
+            let decoder = Decoder::new(self.include_failed, None, None, self.stats.clone());
+            let capture = self.capture.clone();
+            let health = self.health("pump");
             tokio::spawn(async move {
                 Client::subscribe_to_websocket(
                     url,
                     constans::requests::SUBSCRIBE_REQUEST_PUMP,
-                    func,
+                    decoder,
+                    capture,
+                    health,
+                    sink,
                 )
                 .await
             })
@@ -86,10 +295,25 @@ impl Client {
 
         let amm_handle = if amm {
             let url = self.url.clone();
-            let func = func.clone();
+            let sink = sink.clone();
+            let decoder = Decoder::new(
+                self.include_failed,
+                self.pools.clone(),
+                None,
+                self.stats.clone(),
+            );
+            let capture = self.capture.clone();
+            let health = self.health("amm");
             Some(tokio::spawn(async move {
-                Client::subscribe_to_websocket(url, constans::requests::SUBSCRIBE_REQUEST_AMM, func)
-                    .await
+                Client::subscribe_to_websocket(
+                    url,
+                    constans::requests::SUBSCRIBE_REQUEST_AMM,
+                    decoder,
+                    capture,
+                    health,
+                    sink,
+                )
+                .await
             }))
         } else {
             None
@@ -111,11 +335,14 @@ impl Client {
     async fn subscribe_to_websocket<F, Fut>(
         url: String,
         subscription_request: &'static str,
-        mut func: F,
+        mut decoder: Decoder,
+        capture: Option<Arc<CaptureWriter>>,
+        health: Arc<ConnectionHealth>,
+        mut sink: Sink<F>,
     ) -> Result<(), Error>
     where
-        F: FnMut((Duration, Event)) -> Fut + Send,
-        Fut: Future<Output = ()> + Send,
+        F: FnMut(EventEnvelope) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
     {
         use futures_util::{SinkExt, StreamExt};
         use std::sync::Arc;
@@ -123,13 +350,14 @@ impl Client {
         use tokio::time::{sleep, Duration};
         use tokio_tungstenite::tungstenite::protocol::Message;
 
-        let mut decode_buf = Vec::with_capacity(512);
+        let mut backoff = Backoff::default();
 
         loop {
             ts(&format!(
                 "connecting to websocket ({})...",
                 subscription_request
             ));
+            health.connecting();
 
             let ws_stream = match connect_async(&url).await {
                 Ok((stream, _)) => {
@@ -137,13 +365,16 @@ impl Client {
                     stream
                 }
                 Err(e) => {
+                    let delay = backoff.next_delay();
                     eprintln!(
-                        "[{}] connection failed ({}): {}. retrying in 5s...",
+                        "[{}] connection failed ({}): {}. retrying in {}ms...",
                         Local::now().format("%H:%M:%S"),
                         subscription_request,
-                        e
+                        e,
+                        delay.as_millis()
                     );
-                    sleep(Duration::from_secs(5)).await;
+                    health.disconnected(&e);
+                    sleep(delay).await;
                     continue;
                 }
             };
@@ -155,13 +386,16 @@ impl Client {
             {
                 let mut w = write.lock().await;
                 if let Err(e) = w.send(Message::Text(subscription_request.into())).await {
+                    let delay = backoff.next_delay();
                     eprintln!(
-                        "[{}] subscription failed ({}): {}",
+                        "[{}] subscription failed ({}): {}. retrying in {}ms...",
                         Local::now().format("%H:%M:%S"),
                         subscription_request,
-                        e
+                        e,
+                        delay.as_millis()
                     );
-                    sleep(Duration::from_secs(1)).await;
+                    health.disconnected(&e);
+                    sleep(delay).await;
                     continue;
                 }
             }
@@ -170,6 +404,8 @@ impl Client {
                 "subscribed ({}). listening...",
                 subscription_request
             ));
+            health.connected();
+            backoff.connected();
 
             // ===== heartbeat task =====
             let write_hb = write.clone();
@@ -185,33 +421,28 @@ impl Client {
             });
 
             // ===== read loop =====
+            let mut reason = String::from("stream ended");
             while let Some(msg) = read.next().await {
                 match msg {
                     Ok(Message::Ping(payload)) => {
+                        health.message();
                         let mut w = write.lock().await;
                         let _ = w.send(Message::Pong(payload)).await;
                     }
 
                     Ok(Message::Pong(_)) => {
                         // alive
+                        health.message();
                     }
 
                     Ok(Message::Text(text)) => {
-                        if let Ok(parsed) = from_str::<LogsNotification>(&text) {
-                            for log in &parsed.params.result.value.logs {
-                                if !log.starts_with("Program data: ") {
-                                    continue;
-                                }
-
-                                let data = &log[14..];
-                                if let Ok(event) = parse_optimized(data, &mut decode_buf) {
-                                    let ts = std::time::SystemTime::now()
-                                        .duration_since(std::time::UNIX_EPOCH)
-                                        .unwrap_or_default();
-
-                                    func((ts, event)).await;
-                                }
-                            }
+                        health.message();
+                        if let Some(capture) = &capture {
+                            capture.logs(&text);
+                        }
+
+                        for envelope in decoder.logs(&text) {
+                            sink.send(envelope).await;
                         }
                     }
 
@@ -222,6 +453,7 @@ impl Client {
                             subscription_request,
                             frame
                         );
+                        reason = format!("closed: {:?}", frame);
                         break;
                     }
 
@@ -232,6 +464,7 @@ impl Client {
                             subscription_request,
                             e
                         );
+                        reason = e.to_string();
                         break;
                     }
 
@@ -240,33 +473,45 @@ impl Client {
             }
 
             heartbeat.abort();
+            health.disconnected(&reason);
 
+            let delay = backoff.next_delay();
             ts(&format!(
-                "connection lost ({}). retrying in 5s...",
-                subscription_request
+                "connection lost ({}). retrying in {}ms...",
+                subscription_request,
+                delay.as_millis()
             ));
-            sleep(Duration::from_secs(5)).await;
+            sleep(delay).await;
         }
     }
 
-    pub async fn subscribe_new_tokens<F, Fut>(&self, mut func: F) -> Result<(), Error>
+    pub async fn subscribe_new_tokens<F, Fut>(&self, func: F) -> Result<(), Error>
     where
-        F: FnMut((Event, Duration)) -> Fut + Send + 'static,
-        Fut: Future<Output = ()> + Send,
+        F: FnMut(EventEnvelope) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
     {
+        let mut sink = Sink::new(func, self.dispatch, &self.dispatch_stats);
+        let mut decoder = Decoder::new(self.include_failed, None, None, self.stats.clone());
+        let health = self.health("pumpportal");
+        let mut backoff = Backoff::default();
+
         loop {
             ts("Connecting to PumpPortal Data API...");
+            health.connecting();
 
             let ws_result = connect_async(&self.url).await;
             let (ws_stream, _) = match ws_result {
                 Ok(s) => s,
                 Err(e) => {
+                    let delay = backoff.next_delay();
                     eprintln!(
-                        "[{}] Connection failed: {}. Retrying in 5s...",
+                        "[{}] Connection failed: {}. Retrying in {}ms...",
                         Local::now().format("%H:%M:%S"),
-                        e
+                        e,
+                        delay.as_millis()
                     );
-                    sleep(Duration::from_secs(5)).await;
+                    health.disconnected(&e);
+                    sleep(delay).await;
                     continue;
                 }
             };
@@ -276,82 +521,87 @@ impl Client {
             let subscribe_msg = r#"{"method":"subscribeNewToken"}"#;
             if let Err(e) = write.send(Message::Text(subscribe_msg.into())).await {
                 eprintln!("Subscription send failed: {}", e);
+                health.disconnected(&e);
+                sleep(backoff.next_delay()).await;
                 continue;
             }
 
             ts("Subscribed to New Tokens. Listening for pump-only create events...");
+            health.connected();
+            backoff.connected();
 
+            let mut reason = String::from("stream ended");
             while let Some(msg) = read.next().await {
                 match msg {
                     Ok(Message::Text(text)) => {
-                        if let Ok(raw_event) = from_str::<PumpCreateEvent>(&text) {
-                            // FILTER: Only "create" type AND only "pump" pool
-                            if raw_event.tx_type == "create" && raw_event.pool == "pump" {
-                                // Safe parsing of Pubkeys from dynamic strings
-                                let mint = raw_event.mint.parse::<Pubkey>().unwrap_or_default();
-                                let bonding_curve = raw_event
-                                    .bonding_curve_key
-                                    .parse::<Pubkey>()
-                                    .unwrap_or_default();
-                                let user = raw_event
-                                    .trader_public_key
-                                    .parse::<Pubkey>()
-                                    .unwrap_or_default();
-
-                                let event = CreateEvent {
-                                    name: raw_event.name,
-                                    symbol: raw_event.symbol,
-                                    uri: raw_event.uri,
-                                    mint,
-                                    bonding_curve,
-                                    user,
-                                    timestamp: current_timestamp_secs() as i64,
-                                    token_2022: true,
-                                };
-
-                                let since_epoch = std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap_or(Duration::ZERO);
-
-                                func((Event::Create(event), since_epoch)).await;
-                            }
+                        health.message();
+                        if let Some(capture) = &self.capture {
+                            capture.pumpportal(&text);
+                        }
+
+                        if let Some(envelope) = decoder.pumpportal(&text) {
+                            sink.send(envelope).await;
                         }
                     }
-                    Ok(Message::Close(_)) => break,
+                    Ok(Message::Close(frame)) => {
+                        reason = format!("closed: {:?}", frame);
+                        break;
+                    }
                     Err(e) => {
                         eprintln!("Websocket error: {}", e);
+                        reason = e.to_string();
                         break;
                     }
-                    _ => {}
+                    _ => health.message(),
                 }
             }
+            health.disconnected(&reason);
 
-            ts("Connection lost. Reconnecting in 2s...");
-            sleep(Duration::from_secs(2)).await;
+            let delay = backoff.next_delay();
+            ts(&format!(
+                "Connection lost. Reconnecting in {}ms...",
+                delay.as_millis()
+            ));
+            sleep(delay).await;
         }
     }
 
     pub async fn subscribe_jito<F, Fut>(
         &self,
         jito_url: String,
-        mut func: F,
+        tables: Arc<LookupTableCache>,
+        func: F,
     ) -> Result<(), Box<dyn std::error::Error>>
     where
-        F: FnMut((Event, Duration)) -> Fut + Send + 'static,
-        Fut: Future<Output = ()> + Send,
+        F: FnMut(EventEnvelope) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
     {
+        let mut sink = Sink::new(func, self.dispatch, &self.dispatch_stats);
         ts(&format!(
             "Connecting to Jito Shredstream at {}...",
             jito_url
         ));
 
+        // Reserve estimates survive reconnects, the curves themselves don't change
+        let mut decoder = Decoder::new(self.include_failed, None, Some(tables), self.stats.clone());
+        let health = self.health("jito");
+        let mut backoff = Backoff::default();
+
         // Wrapped in a loop for basic reconnection logic
         loop {
+            health.connecting();
+
             let mut client = match ShredstreamProxyClient::connect(jito_url.clone()).await {
                 Ok(c) => c,
                 Err(e) => {
-                    eprintln!("Jito connection failed: {}. Retrying in 5s...", e);
-                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    let delay = backoff.next_delay();
+                    eprintln!(
+                        "Jito connection failed: {}. Retrying in {}ms...",
+                        e,
+                        delay.as_millis()
+                    );
+                    health.disconnected(&e);
+                    sleep(delay).await;
                     continue;
                 }
             };
@@ -359,83 +609,332 @@ impl Client {
             let mut stream = match client.subscribe_entries(SubscribeEntriesRequest {}).await {
                 Ok(s) => s.into_inner(),
                 Err(e) => {
-                    eprintln!("Jito subscription failed: {}. Retrying...", e);
+                    let delay = backoff.next_delay();
+                    eprintln!(
+                        "Jito subscription failed: {}. Retrying in {}ms...",
+                        e,
+                        delay.as_millis()
+                    );
+                    health.disconnected(&e);
+                    sleep(delay).await;
                     continue;
                 }
             };
 
             ts("Jito Stream Connected. Monitoring transactions...");
+            health.connected();
+            backoff.connected();
 
-            while let Some(slot_entry_res) = stream.message().await.ok().flatten() {
-                let entries: Vec<solana_entry::entry::Entry> =
-                    match bincode::deserialize(&slot_entry_res.entries) {
-                        Ok(e) => e,
-                        Err(_) => continue,
-                    };
+            let reason = loop {
+                let slot_entry_res = match stream.message().await {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => break String::from("stream ended"),
+                    Err(e) => break e.to_string(),
+                };
+                health.message();
 
-                for tx in entries.iter().flat_map(|e| &e.transactions) {
-                    let lookup = tx.message.static_account_keys();
+                if let Some(capture) = &self.capture {
+                    capture.shred(slot_entry_res.slot, &slot_entry_res.entries);
+                }
 
-                    for instruction in tx.message.instructions() {
-                        // --- FIX: SAFE BOUNDS CHECKING ---
-                        // Instead of calling instruction.program_id(&lookup) which panics,
-                        // we manually check if the index exists in our static lookup.
-                        let program_idx = instruction.program_id_index as usize;
-                        let program_id = match lookup.get(program_idx) {
-                            Some(id) => id,
-                            None => continue, // Skip if index is out of bounds (e.g. in a Lookup Table)
-                        };
+                for envelope in decoder.shred(slot_entry_res.slot, &slot_entry_res.entries) {
+                    sink.send(envelope).await;
+                }
+            };
+            health.disconnected(&reason);
 
-                        if program_id != &PUMP_PROGRAM {
-                            continue;
-                        }
+            let delay = backoff.next_delay();
+            ts(&format!(
+                "Jito connection lost ({}). Reconnecting in {}ms...",
+                reason,
+                delay.as_millis()
+            ));
+            sleep(delay).await;
+        }
+    }
+
+    /// Health record for one of this client's streams.
+    fn health(&self, stream: &str) -> Arc<ConnectionHealth> {
+        match &self.health {
+            Some((registry, name)) => registry.register(format!("{} {}", name, stream)),
+            None => Arc::new(ConnectionHealth::new(stream)),
+        }
+    }
+}
+
+/// Turns raw stream messages into events. The live connections and capture
+/// replay both go through it, so a replayed capture decodes exactly like the
+/// original stream did.
+pub(crate) struct Decoder {
+    decode_buf: Vec<u8>,
+    curves: CurveTracker,
+    include_failed: bool,
+    pools: Option<Arc<AmmPoolCache>>,
+    tables: Option<Arc<LookupTableCache>>,
+    stats: Arc<ParseStats>,
+}
+
+impl Decoder {
+    pub(crate) fn new(
+        include_failed: bool,
+        pools: Option<Arc<AmmPoolCache>>,
+        tables: Option<Arc<LookupTableCache>>,
+        stats: Arc<ParseStats>,
+    ) -> Self {
+        Self {
+            decode_buf: Vec::with_capacity(512),
+            curves: CurveTracker::new(TRACKED_CURVES),
+            include_failed,
+            pools,
+            tables,
+            stats,
+        }
+    }
+
+    /// A `logsNotification` from the RPC websocket.
+    pub(crate) fn logs(&mut self, text: &str) -> Vec<EventEnvelope> {
+        let mut envelopes = Vec::new();
+
+        let Ok(parsed) = from_str::<LogsNotification>(text) else {
+            return envelopes;
+        };
+
+        let result = &parsed.params.result;
+        let failed = result.value.failed();
+        if failed && !self.include_failed {
+            return envelopes;
+        }
+
+        let signature = result.value.signature.parse::<Signature>().ok();
 
-                        let since_epoch = std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap_or(Duration::ZERO);
-
-                        // 2. Handle CREATE_V2
-                        if instruction.data.starts_with(CREATE_V2_DISCRIMINATOR) {
-                            if let Ok(args) = CreateV2Args::try_from_slice(&instruction.data[8..]) {
-                                // --- FIX: SAFE ACCOUNT RESOLUTION ---
-                                // Using the updated get_account_ptr helper below
-                                let mint = get_account_ptr(0, &instruction.accounts, lookup);
-                                let bonding_curve =
-                                    get_account_ptr(2, &instruction.accounts, lookup);
-                                let token_acc = get_account_ptr(7, &instruction.accounts, lookup);
-
-                                if let (Some(mint), Some(bonding_curve), Some(token_acc)) =
-                                    (mint, bonding_curve, token_acc)
-                                {
-                                    let event = CreateEvent {
-                                        name: args.name,
-                                        symbol: args.symbol,
-                                        uri: args.uri,
-                                        mint: *mint,
-                                        bonding_curve: *bonding_curve,
-                                        user: Pubkey::new_from_array(args.creator),
-                                        timestamp: since_epoch.as_secs() as i64,
-                                        // Token2022 check
-                                        token_2022: *token_acc
-                                            == pubkey!(
-                                                "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"
-                                            ),
-                                    };
-                                    func((Event::Create(event), since_epoch)).await;
-                                }
-                            }
+        // Reserves after the last trade, the complete event
+        // that follows it carries none of its own
+        let mut last_reserves = None;
+        // Index of the top-level instruction the logs are from
+        let mut instruction: Option<usize> = None;
+        // Whether each program on the invoke stack is Pump.fun or PumpSwap
+        let mut invoked: Vec<bool> = Vec::new();
+
+        for log in &result.value.logs {
+            let Some(data) = log.strip_prefix("Program data: ") else {
+                track_invocation(log, &mut invoked, &mut instruction);
+                continue;
+            };
+            // Other programs of the transaction emit data too. Data from
+            // before the first invoke line (cut logs) is still decoded
+            if invoked.last() == Some(&false) {
+                continue;
+            }
+
+            let mut event = match parse_optimized(data, &mut self.decode_buf) {
+                Ok(Some(Parsed::Event(event))) => event,
+                Ok(Some(Parsed::Swap(swap))) => {
+                    let Some(pools) = &self.pools else { continue };
+                    let Some(trade) = pools.resolve(swap) else {
+                        continue;
+                    };
+                    Event::AmmTrade(trade)
+                }
+                Ok(None) => continue,
+                Err(e) => {
+                    self.stats.record(&e);
+                    continue;
+                }
+            };
+
+            match &mut event {
+                Event::Buy(buy) => {
+                    last_reserves = Some((
+                        buy.mint,
+                        buy.virtual_sol_reserves_before,
+                        buy.virtual_token_reserves,
+                    ));
+                }
+                Event::Complete(complete) => {
+                    if let Some((pool, sol, tokens)) = last_reserves {
+                        if pool == complete.pool {
+                            complete.virtual_sol_reserves = sol;
+                            complete.virtual_token_reserves = tokens;
                         }
                     }
                 }
+                Event::PoolCreated(created) => {
+                    if let Some(pools) = &self.pools {
+                        pools.insert(created.pool, created.base_mint, created.quote_mint);
+                    }
+                }
+                _ => {}
+            }
+
+            envelopes.push(EventEnvelope {
+                source: SourceKind::Logs,
+                slot: Some(result.context.slot),
+                signature,
+                failed,
+                instruction,
+                received: since_epoch(),
+                replaces_estimate: false,
+                event,
+            });
+        }
+
+        envelopes
+    }
+
+    /// A PumpPortal data API message. Only pump.fun creates are kept.
+    pub(crate) fn pumpportal(&mut self, text: &str) -> Option<EventEnvelope> {
+        let raw_event = from_str::<PumpCreateEvent>(text).ok()?;
+
+        // FILTER: Only "create" type AND only "pump" pool
+        if raw_event.tx_type != "create" || raw_event.pool != "pump" {
+            return None;
+        }
+
+        // Safe parsing of Pubkeys from dynamic strings
+        let mint = raw_event.mint.parse::<Pubkey>().unwrap_or_default();
+        let bonding_curve = raw_event
+            .bonding_curve_key
+            .parse::<Pubkey>()
+            .unwrap_or_default();
+        let user = raw_event
+            .trader_public_key
+            .parse::<Pubkey>()
+            .unwrap_or_default();
+
+        let event = CreateEvent {
+            name: raw_event.name,
+            symbol: raw_event.symbol,
+            uri: raw_event.uri,
+            mint,
+            bonding_curve,
+            user,
+            timestamp: current_timestamp_secs() as i64,
+            token_2022: true,
+        };
+
+        Some(EventEnvelope {
+            source: SourceKind::PumpPortal,
+            slot: None,
+            signature: raw_event.signature.parse::<Signature>().ok(),
+            failed: false,
+            instruction: None,
+            received: since_epoch(),
+            replaces_estimate: false,
+            event: Event::Create(event),
+        })
+    }
+
+    /// A bincode encoded batch of shredstream entries.
+    pub(crate) fn shred(&mut self, slot: u64, entries: &[u8]) -> Vec<EventEnvelope> {
+        let mut envelopes = Vec::new();
+
+        let entries: Vec<solana_entry::entry::Entry> = match bincode::deserialize(entries) {
+            Ok(e) => e,
+            Err(_) => {
+                self.stats.record(&ParseError::Entries);
+                return envelopes;
+            }
+        };
+
+        for tx in entries.iter().flat_map(|e| &e.transactions) {
+            let static_keys = tx.message.static_account_keys();
+            let instructions = tx.message.instructions();
+
+            // Program ids are always static keys, so lookup tables only
+            // have to be loaded for transactions that touch Pump.fun
+            let mut touches_pump = false;
+            for instruction in instructions {
+                let Some(program_id) = static_keys.get(instruction.program_id_index as usize)
+                else {
+                    continue;
+                };
+
+                if program_id == &PUMP_PROGRAM {
+                    touches_pump = true;
+                } else if let (Some(tables), Some(table)) = (
+                    &self.tables,
+                    get_account_ptr(0, &instruction.accounts, static_keys),
+                ) {
+                    tables.observe(program_id, &instruction.data, table);
+                }
+            }
+
+            if !touches_pump {
+                continue;
+            }
+
+            let keys = match &self.tables {
+                Some(tables) => tables.resolve(&tx.message),
+                // Without a cache only transactions without lookups can be read
+                None => match tx.message.address_table_lookups() {
+                    Some(lookups) if !lookups.is_empty() => None,
+                    _ => Some(Cow::Borrowed(static_keys)),
+                },
+            };
+            let Some(keys) = keys else {
+                continue;
+            };
+
+            for (index, instruction) in instructions.iter().enumerate() {
+                if keys.get(instruction.program_id_index as usize) != Some(&PUMP_PROGRAM) {
+                    continue;
+                }
+
+                let received = since_epoch();
+
+                let event =
+                    match decode_pump_instruction(instruction, &keys, &mut self.curves, received) {
+                        Ok(Some(event)) => event,
+                        Ok(None) => continue,
+                        Err(e) => {
+                            self.stats.record(&e);
+                            continue;
+                        }
+                    };
+
+                envelopes.push(EventEnvelope {
+                    source: SourceKind::Shredstream,
+                    slot: Some(slot),
+                    signature: tx.signatures.first().copied(),
+                    failed: false,
+                    instruction: Some(index),
+                    received,
+                    replaces_estimate: false,
+                    event,
+                });
             }
-            ts("Jito connection lost. Reconnecting...");
-            tokio::time::sleep(Duration::from_secs(1)).await;
         }
+
+        envelopes
     }
 }
 
-/// Helper to resolve accounts from instruction account indexes and transaction lookup table
-/// UPDATED: Helper to resolve accounts with safety checks
+/// Follows `Program <id> invoke [n]` and `Program <id> success|failed` logs,
+/// keeping a stack of which invoked programs are Pump.fun or PumpSwap and
+/// counting top-level (`invoke [1]`) instructions.
+fn track_invocation(log: &str, invoked: &mut Vec<bool>, instruction: &mut Option<usize>) {
+    let Some(rest) = log.strip_prefix("Program ") else {
+        return;
+    };
+    let Some((program_id, status)) = rest.split_once(' ') else {
+        return;
+    };
+
+    if status.starts_with("invoke [") {
+        if status == "invoke [1]" {
+            *instruction = Some(instruction.map_or(0, |index| index + 1));
+        }
+        let pump = program_id
+            .parse::<Pubkey>()
+            .is_ok_and(|id| id == PUMP_PROGRAM || id == PUMP_AMM_PROGRAM);
+        invoked.push(pump);
+    } else if status == "success" || status.starts_with("failed") {
+        invoked.pop();
+    }
+}
+
+/// Helper to resolve accounts from instruction account indexes and the transaction's
+/// account keys (static keys followed by addresses loaded from lookup tables)
 fn get_account_ptr<'a>(
     index: u8,
     instruction_accounts: &[u8],
@@ -444,38 +943,214 @@ fn get_account_ptr<'a>(
     // 1. Get the account index from the instruction's account list
     let account_index = *instruction_accounts.get(index as usize)? as usize;
 
-    // 2. Safely return the Pubkey from the transaction's static account list
+    // 2. Safely return the Pubkey from the transaction's account list
     lookup.get(account_index)
 }
 
+/// Decodes a single Pump.fun instruction seen in the shredstream.
+///
+/// Creates are exact. Buys and sells have not executed yet: the token amount and
+/// accounts come from the instruction, while the SOL amount and reserves are
+/// estimated from the locally tracked curve (`estimated` is set on the event).
+/// Trades on curves created before the stream connected and instructions
+/// without an event we track are `Ok(None)`.
+fn decode_pump_instruction(
+    instruction: &CompiledInstruction,
+    lookup: &[Pubkey],
+    curves: &mut CurveTracker,
+    since_epoch: Duration,
+) -> Result<Option<Event>, ParseError> {
+    let data = &instruction.data;
+    if data.len() < 8 {
+        return Err(ParseError::TooShort { len: data.len() });
+    }
+
+    let accounts = &instruction.accounts;
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&data[..8]);
+    let mut args = &data[8..];
+    // A missing account is as much a layout change as bad arguments
+    let layout = || ParseError::BorshFailed { discriminator };
+    let account = |index| get_account_ptr(index, accounts, lookup).ok_or_else(layout);
+
+    if discriminator == CREATE_V2_DISCRIMINATOR {
+        let args = CreateV2Args::try_from_slice(args).map_err(|_| layout())?;
+        let mint = account(0)?;
+        let bonding_curve = account(2)?;
+        let token_acc = account(7)?;
+
+        curves.insert(*bonding_curve, mint);
+
+        Ok(Some(Event::Create(CreateEvent {
+            name: args.name,
+            symbol: args.symbol,
+            uri: args.uri,
+            mint: *mint,
+            bonding_curve: *bonding_curve,
+            user: Pubkey::new_from_array(args.creator),
+            timestamp: since_epoch.as_secs() as i64,
+            token_2022: *token_acc == TOKEN_2022_PROGRAM,
+        })))
+    } else if discriminator == CREATE_IX_DISCRIMINATOR {
+        let create = CreateArgs::deserialize(&mut args).map_err(|_| layout())?;
+        let mint = account(0)?;
+        let bonding_curve = account(2)?;
+        let user = account(7)?;
+
+        // Newer `create` carries the creator after the uri, fall back to the signer
+        let creator = Pubkey::deserialize(&mut args).unwrap_or(*user);
+
+        curves.insert(*bonding_curve, mint);
+
+        Ok(Some(Event::Create(CreateEvent {
+            name: create.name,
+            symbol: create.symbol,
+            uri: create.uri,
+            mint: *mint,
+            bonding_curve: *bonding_curve,
+            user: creator,
+            timestamp: since_epoch.as_secs() as i64,
+            token_2022: false,
+        })))
+    } else if discriminator == BUY_DISCRIMINATOR || discriminator == SELL_DISCRIMINATOR {
+        let is_buy = discriminator == BUY_DISCRIMINATOR;
+        let (token_amount, sol_limit) = if is_buy {
+            let args = BuyArgs::deserialize(&mut args).map_err(|_| layout())?;
+            (args.amount, args.max_sol)
+        } else {
+            let args = SellArgs::deserialize(&mut args).map_err(|_| layout())?;
+            (args.amount, args.min_sol)
+        };
+
+        let bonding_curve = account(3)?;
+        let user = *account(6)?;
+
+        // Curves created before we connected are left to the logsSubscribe path
+        let Some((state, estimated_sol)) = curves.apply(bonding_curve, token_amount, is_buy)
+        else {
+            return Ok(None);
+        };
+
+        // Never report more than the user allowed to spend / less than they accept
+        let sol_amount = if is_buy {
+            estimated_sol.min(sol_limit)
+        } else {
+            estimated_sol.max(sol_limit)
+        };
+
+        let impact = calc_price_impact(
+            state.virtual_sol_reserves,
+            state.virtual_token_reserves,
+            sol_amount,
+            token_amount,
+            is_buy,
+            1_000_000_000,
+        );
+
+        let timestamp = since_epoch.as_secs() as i64;
+
+        if is_buy {
+            Ok(Some(Event::Buy(BuyEvent {
+                mint: state.pool,
+                token_mint: state.mint,
+                sol_amount,
+                token_amount,
+                user,
+                timestamp,
+                virtual_sol_reserves_before: state.virtual_sol_reserves,
+                virtual_sol_reserves_after: impact.mcap_after,
+                virtual_token_reserves: state.virtual_token_reserves,
+                estimated: true,
+            })))
+        } else {
+            Ok(Some(Event::Sell(SellEvent {
+                mint: state.pool,
+                token_mint: state.mint,
+                sol_amount,
+                token_amount,
+                user,
+                timestamp,
+                virtual_sol_reserves_before: state.virtual_sol_reserves,
+                virtual_sol_reserves_after: impact.mcap_after,
+                virtual_token_reserves: state.virtual_token_reserves,
+                estimated: true,
+            })))
+        }
+    } else if IGNORED_IX_DISCRIMINATORS.contains(&discriminator) {
+        Ok(None)
+    } else {
+        Err(ParseError::UnknownDiscriminator { discriminator })
+    }
+}
+
 // Discriminators as constants
 const CREATE_DISCRIMINATOR: [u8; 8] = [27, 114, 169, 77, 222, 235, 99, 118];
 const TRADE_DISCRIMINATOR: [u8; 8] = [0xbd, 0xdb, 0x7f, 0xd3, 0x4e, 0xe6, 0x61, 0xee];
-const BUY_AMM_DISCRIMINATOR: [u8; 8] = [62, 47, 55, 10, 165, 3, 220, 42];
-const SELL_AMM_DISCRIMINATOR: [u8; 8] = [103, 244, 82, 31, 44, 245, 119, 119];
+const BUY_AMM_DISCRIMINATOR: [u8; 8] = [103, 244, 82, 31, 44, 245, 119, 119];
+const SELL_AMM_DISCRIMINATOR: [u8; 8] = [62, 47, 55, 10, 165, 3, 220, 42];
+const COMPLETE_DISCRIMINATOR: [u8; 8] = [95, 114, 97, 156, 212, 46, 152, 8];
+const MIGRATION_DISCRIMINATOR: [u8; 8] = [189, 233, 93, 185, 92, 148, 234, 148];
+const CREATE_POOL_DISCRIMINATOR: [u8; 8] = [177, 49, 12, 210, 160, 118, 167, 116];
+
+// Pump.fun and PumpSwap events we don't track
+const IGNORED_EVENT_DISCRIMINATORS: &[[u8; 8]] = &[
+    [45, 220, 93, 24, 25, 97, 172, 104], // AdminSetCoinCreatorEvent
+    [64, 69, 192, 104, 29, 30, 25, 107], // AdminSetCreatorEvent
+    [245, 59, 70, 34, 75, 185, 109, 92], // AdminSetIdlAuthorityEvent
+    [147, 250, 108, 120, 247, 29, 67, 222], // AdminUpdateTokenIncentivesEvent
+    [79, 172, 246, 49, 205, 91, 206, 232], // ClaimTokenIncentivesEvent
+    [146, 159, 189, 172, 146, 88, 56, 244], // CloseUserVolumeAccumulatorEvent
+    [232, 245, 194, 238, 234, 218, 58, 89], // CollectCoinCreatorFeeEvent
+    [122, 2, 127, 1, 14, 191, 12, 175],  // CollectCreatorFeeEvent
+    [107, 52, 89, 129, 55, 226, 81, 22], // CreateConfigEvent
+    [120, 248, 61, 83, 31, 142, 107, 144], // DepositEvent
+    [107, 253, 193, 76, 228, 202, 27, 104], // DisableEvent
+    [97, 97, 215, 144, 93, 146, 22, 124], // ExtendAccountEvent
+    [134, 36, 13, 72, 232, 101, 130, 216], // InitUserVolumeAccumulatorEvent
+    [242, 231, 235, 102, 65, 99, 189, 211], // SetBondingCurveCoinCreatorEvent
+    [237, 52, 123, 37, 245, 251, 72, 210], // SetCreatorEvent
+    [150, 107, 199, 123, 124, 207, 102, 228], // SetMetaplexCoinCreatorEvent
+    [142, 203, 6, 32, 127, 105, 191, 162], // SetMetaplexCreatorEvent
+    [223, 195, 159, 246, 62, 48, 143, 131], // SetParamsEvent
+    [197, 122, 167, 124, 116, 81, 91, 255], // SyncUserVolumeAccumulatorEvent
+    [225, 152, 171, 87, 246, 63, 66, 234], // UpdateAdminEvent
+    [90, 23, 65, 35, 62, 244, 188, 208], // UpdateFeeConfigEvent
+    [182, 195, 137, 42, 35, 206, 207, 247], // UpdateGlobalAuthorityEvent
+    [22, 9, 133, 26, 160, 44, 71, 192],  // WithdrawEvent
+];
+
+/// Output of the log parser. AMM swaps still need their pool resolved.
+enum Parsed {
+    Event(Event),
+    Swap(AmmSwap),
+}
 
-// Optimized parse function with buffer reuse
+// Optimized parse function with buffer reuse, events we don't track are `Ok(None)`
 #[inline]
-fn parse_optimized(data: &str, decode_buf: &mut Vec<u8>) -> Result<Event, ()> {
+fn parse_optimized(data: &str, decode_buf: &mut Vec<u8>) -> Result<Option<Parsed>, ParseError> {
     // Decode base64 into reusable buffer
     decode_buf.clear();
     BASE64_STANDARD
         .decode_vec(data, decode_buf)
-        .map_err(|_| ())?;
+        .map_err(|_| ParseError::Base64)?;
 
     // Fast bounds check
     if decode_buf.len() < 8 {
-        return Err(());
+        return Err(ParseError::TooShort {
+            len: decode_buf.len(),
+        });
     }
 
     // Get discriminator without allocation
-    let discriminator = &decode_buf[0..8];
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&decode_buf[0..8]);
     let mut buffer = &decode_buf[8..];
+    let borsh_failed = |_| ParseError::BorshFailed { discriminator };
 
     // Match discriminator (branch prediction friendly)
     if discriminator == TRADE_DISCRIMINATOR {
         // Most common case first for better branch prediction
-        let event = TradeEvent::deserialize(&mut buffer).map_err(|_| ())?;
+        let event = TradeEvent::deserialize(&mut buffer).map_err(borsh_failed)?;
 
         let impact = calc_price_impact(
             event.virtual_sol_reserves,
@@ -490,8 +1165,9 @@ fn parse_optimized(data: &str, decode_buf: &mut Vec<u8>) -> Result<Event, ()> {
 
         // Use if/else instead of match for better codegen
         if event.is_buy {
-            Ok(Event::Buy(BuyEvent {
+            Ok(Some(Parsed::Event(Event::Buy(BuyEvent {
                 mint: pool,
+                token_mint: event.mint,
                 sol_amount: event.sol_amount,
                 token_amount: event.token_amount,
                 user: event.user,
@@ -499,10 +1175,12 @@ fn parse_optimized(data: &str, decode_buf: &mut Vec<u8>) -> Result<Event, ()> {
                 virtual_sol_reserves_before: event.virtual_sol_reserves,
                 virtual_sol_reserves_after: impact.mcap_after,
                 virtual_token_reserves: event.virtual_token_reserves,
-            }))
+                estimated: false,
+            }))))
         } else {
-            Ok(Event::Sell(SellEvent {
+            Ok(Some(Parsed::Event(Event::Sell(SellEvent {
                 mint: pool,
+                token_mint: event.mint,
                 sol_amount: event.sol_amount,
                 token_amount: event.token_amount,
                 user: event.user,
@@ -510,31 +1188,45 @@ fn parse_optimized(data: &str, decode_buf: &mut Vec<u8>) -> Result<Event, ()> {
                 virtual_sol_reserves_before: event.virtual_sol_reserves,
                 virtual_sol_reserves_after: impact.mcap_after,
                 virtual_token_reserves: event.virtual_token_reserves,
-            }))
+                estimated: false,
+            }))))
         }
     } else if discriminator == CREATE_DISCRIMINATOR {
         if let Ok(create) = CreateEventV2::deserialize(&mut buffer) {
-            // inside the message processing loop, replace:
-            let since_epoch = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::ZERO);
-            Ok(Event::Create(create.into()))
+            Ok(Some(Parsed::Event(Event::Create(create.into()))))
         } else {
             buffer = &decode_buf[8..]; // Reset buffer
-            let create = CreateEvent::deserialize(&mut buffer).map_err(|_| ())?;
-            Ok(Event::Create(create))
+            let create = CreateEvent::deserialize(&mut buffer).map_err(borsh_failed)?;
+            Ok(Some(Parsed::Event(Event::Create(create))))
         }
     } else if discriminator == BUY_AMM_DISCRIMINATOR {
-        let buy = BuyEventAMM::deserialize(&mut buffer).map_err(|_| ())?;
-        Ok(Event::Buy(buy.into()))
+        let buy = BuyEventAMM::deserialize(&mut buffer).map_err(borsh_failed)?;
+        Ok(Some(Parsed::Swap(buy.into())))
     } else if discriminator == SELL_AMM_DISCRIMINATOR {
-        let sell = SellEventAMM::deserialize(&mut buffer).map_err(|_| ())?;
-        Ok(Event::Sell(sell.into()))
+        let sell = SellEventAMM::deserialize(&mut buffer).map_err(borsh_failed)?;
+        Ok(Some(Parsed::Swap(sell.into())))
+    } else if discriminator == COMPLETE_DISCRIMINATOR {
+        let complete = CurveCompleteEvent::deserialize(&mut buffer).map_err(borsh_failed)?;
+        Ok(Some(Parsed::Event(Event::Complete(complete.into()))))
+    } else if discriminator == MIGRATION_DISCRIMINATOR {
+        let migrated = MigratedEvent::deserialize(&mut buffer).map_err(borsh_failed)?;
+        Ok(Some(Parsed::Event(Event::Migrated(migrated))))
+    } else if discriminator == CREATE_POOL_DISCRIMINATOR {
+        let pool = CreatePoolEventAMM::deserialize(&mut buffer).map_err(borsh_failed)?;
+        Ok(Some(Parsed::Event(Event::PoolCreated(pool.into()))))
+    } else if IGNORED_EVENT_DISCRIMINATORS.contains(&discriminator) {
+        Ok(None)
     } else {
-        Err(())
+        Err(ParseError::UnknownDiscriminator { discriminator })
     }
 }
 
+pub(crate) fn since_epoch() -> Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+}
+
 fn current_timestamp_secs() -> f64 {
     let now = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
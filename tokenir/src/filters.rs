@@ -1,19 +1,29 @@
 use std::ops::Range;
 
 use crate::Token;
-use rust_decimal::prelude::ToPrimitive;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+/// Filters a client subscribes with. A token passes when every filter does.
+/// Same JSON shape as the desktop client's saved filter sets, which use a
+/// subset of the tags; the client's own view logic is looser and only sends
+/// the filters it ANDs itself (see `FilterSet::server_filters` there).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FilterSet {
     pub filters: HashMap<Tag, Filters>,
+    /// Lets tokens of devs without previous tokens through the filters on the
+    /// dev's record, instead of judging them as a record of zeros
+    #[serde(default)]
+    pub new_devs_pass: bool,
 }
 
 impl FilterSet {
     pub fn new() -> Self {
         Self {
             filters: HashMap::new(),
+            new_devs_pass: false,
         }
     }
 
@@ -25,28 +35,98 @@ impl FilterSet {
         self.filters.remove(tag);
     }
 
-    pub fn matches(&self, token: &Token, price: u64, average_mcap: u64) -> bool {
+    pub fn matches(&self, token: &Token) -> bool {
+        let new_dev = self.new_devs_pass && token.dev_performance.is_none();
         self.filters
             .values()
-            .all(|filter| filter.filter(token, average_mcap))
+            .all(|filter| (new_dev && filter.on_dev_record()) || filter.filter(token))
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Serialize, Deserialize)]
 pub enum Tag {
     AverageDevMarketCap,
     TransactionCount,
+    TokenCount,
+    MigrationPercentage,
+    RugRate,
+    Twitter,
+    Name,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Filters {
+    /// Median ATH of the dev's previous tokens, in USD
     AverageDevMarketCap(Range<u64>),
+    /// How many tokens the dev created before
+    TokenCount(Range<u64>),
+    /// Share of the dev's tokens that migrated, 0..100
+    MigrationPercentage(Range<u64>),
+    /// Share of the dev's tokens they dumped right after creation, 0..100
+    RugRate(Range<u64>),
+    /// Whether the token links a Twitter community
+    Twitter(bool),
+    /// Matched against both the name and the ticker
+    Name(NamePattern),
 }
 
 impl Filters {
-    pub fn filter(&self, token: &Token, average_mcap: u64) -> bool {
+    /// Whether the filter looks at the dev's previous tokens.
+    pub fn on_dev_record(&self) -> bool {
+        matches!(
+            self,
+            Self::AverageDevMarketCap(_)
+                | Self::TokenCount(_)
+                | Self::MigrationPercentage(_)
+                | Self::RugRate(_)
+        )
+    }
+
+    pub fn filter(&self, token: &Token) -> bool {
+        // A dev without previous tokens has no performance record
+        let performance = token.dev_performance.as_ref();
+
         match self {
-            Self::AverageDevMarketCap(range) => range.contains(&average_mcap),
+            Self::AverageDevMarketCap(range) => {
+                performance.is_some_and(|perf| range.contains(&perf.average_ath))
+            }
+            Self::TokenCount(range) => {
+                range.contains(&performance.map_or(0, |perf| perf.count as u64))
+            }
+            Self::MigrationPercentage(range) => {
+                let percentage = match performance {
+                    Some(perf) if perf.count > 0 => (perf.migrated * 100 / perf.count) as u64,
+                    _ => 0,
+                };
+                range.contains(&percentage)
+            }
+            Self::RugRate(range) => range.contains(&performance.map_or(0, |perf| perf.rug_rate)),
+            Self::Twitter(present) => token.twitter.is_some() == *present,
+            Self::Name(pattern) => {
+                pattern.0.is_match(&token.name) || pattern.0.is_match(&token.ticker)
+            }
         }
     }
 }
+
+/// A regex that travels as its source string.
+#[derive(Debug, Clone)]
+pub struct NamePattern(pub Regex);
+
+impl Serialize for NamePattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for NamePattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        // Patterns come from clients, keep them small
+        RegexBuilder::new(&source)
+            .size_limit(1 << 16)
+            .build()
+            .map(NamePattern)
+            .map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,188 @@
+use serde::Serialize;
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::fetcher::since_epoch;
+
+/// Reconnect delays: doubling from `base` up to `max`, each one randomized
+/// down to half its value so clients that dropped together don't come back
+/// together. The delays only start over once a connection has stayed up for
+/// `stable_after`, so an upstream that accepts and then drops right away keeps
+/// backing off.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    stable_after: Duration,
+    attempt: u32,
+    connected_at: Option<Instant>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(500), Duration::from_secs(30))
+    }
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            stable_after: Duration::from_secs(10),
+            attempt: 0,
+            connected_at: None,
+        }
+    }
+
+    pub fn with_stable_after(mut self, stable_after: Duration) -> Self {
+        self.stable_after = stable_after;
+        self
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        if let Some(connected_at) = self.connected_at.take() {
+            if connected_at.elapsed() >= self.stable_after {
+                self.attempt = 0;
+            }
+        }
+
+        let ceiling = self
+            .base
+            .saturating_mul(1 << self.attempt.min(16))
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let half = ceiling / 2;
+        half + half.mul_f64(jitter())
+    }
+
+    /// Call once a connection is up again.
+    pub fn connected(&mut self) {
+        self.connected_at = Some(Instant::now());
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Uniform-ish in [0, 1), good enough to spread reconnects.
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthSnapshot {
+    pub name: String,
+    pub state: ConnectionState,
+    /// Unix milliseconds of the last message received, if any
+    pub last_message_at: Option<u64>,
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+}
+
+struct Status {
+    state: ConnectionState,
+    last_error: Option<String>,
+}
+
+/// Live status of one upstream connection, updated by its read loop.
+pub struct ConnectionHealth {
+    name: String,
+    status: Mutex<Status>,
+    last_message_at: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+impl ConnectionHealth {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: Mutex::new(Status {
+                state: ConnectionState::Connecting,
+                last_error: None,
+            }),
+            last_message_at: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn connecting(&self) {
+        self.status.lock().unwrap().state = ConnectionState::Connecting;
+    }
+
+    pub fn connected(&self) {
+        self.status.lock().unwrap().state = ConnectionState::Connected;
+    }
+
+    /// The connection failed or dropped, a reconnect follows.
+    pub fn disconnected(&self, error: impl Display) {
+        let mut status = self.status.lock().unwrap();
+        status.state = ConnectionState::Disconnected;
+        status.last_error = Some(error.to_string());
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn message(&self) {
+        self.last_message_at
+            .store(since_epoch().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        let status = self.status.lock().unwrap();
+        let last_message_at = self.last_message_at.load(Ordering::Relaxed);
+
+        HealthSnapshot {
+            name: self.name.clone(),
+            state: status.state,
+            last_message_at: (last_message_at > 0).then_some(last_message_at),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            last_error: status.last_error.clone(),
+        }
+    }
+}
+
+/// Every upstream of the process, for the admin API.
+#[derive(Default)]
+pub struct HealthRegistry {
+    connections: Mutex<Vec<Arc<ConnectionHealth>>>,
+}
+
+impl HealthRegistry {
+    pub fn register(&self, name: impl Into<String>) -> Arc<ConnectionHealth> {
+        let health = Arc::new(ConnectionHealth::new(name));
+        self.connections.lock().unwrap().push(health.clone());
+        health
+    }
+
+    pub fn snapshot(&self) -> Vec<HealthSnapshot> {
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|health| health.snapshot())
+            .collect()
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::{DateTime, NaiveDate};
+use solana_sdk::signature::Signature;
+
+use crate::logs::Event;
+
+/// Seconds per partition of `trades`.
+pub const DAY: i64 = 86_400;
+
+const PARTITION_PREFIX: &str = "trades_";
+
+/// A curve trade as stored in `trades`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TradeRecord {
+    pub mint: String,
+    pub pool: String,
+    pub user: String,
+    pub is_buy: bool,
+    pub sol_amount: i64,
+    pub token_amount: i64,
+    /// Curve reserves after the trade
+    pub virtual_sol_reserves: i64,
+    pub virtual_token_reserves: i64,
+    pub slot: Option<i64>,
+    pub signature: Option<String>,
+    /// Unix seconds, picks the partition
+    pub timestamp: i64,
+}
+
+impl TradeRecord {
+    /// The record of a curve buy or sell. None for other events, and for
+    /// trades estimated from the shredstream, which may never land.
+    pub fn from_event(
+        event: &Event,
+        slot: Option<u64>,
+        signature: Option<Signature>,
+    ) -> Option<Self> {
+        // Buy and sell events have the same fields
+        macro_rules! record {
+            ($data:expr, $is_buy:expr) => {
+                Self {
+                    mint: $data.token_mint.to_string(),
+                    pool: $data.mint.to_string(),
+                    user: $data.user.to_string(),
+                    is_buy: $is_buy,
+                    sol_amount: $data.sol_amount as i64,
+                    token_amount: $data.token_amount as i64,
+                    virtual_sol_reserves: $data.virtual_sol_reserves_before as i64,
+                    virtual_token_reserves: $data.virtual_token_reserves as i64,
+                    slot: slot.map(|slot| slot as i64),
+                    signature: signature.map(|signature| signature.to_string()),
+                    timestamp: $data.timestamp,
+                }
+            };
+        }
+
+        match event {
+            Event::Buy(data) if !data.estimated => Some(record!(data, true)),
+            Event::Sell(data) if !data.estimated => Some(record!(data, false)),
+            _ => None,
+        }
+    }
+}
+
+/// One day of `trades`, holding timestamps in `from..to`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Partition {
+    pub name: String,
+    pub from: i64,
+    pub to: i64,
+}
+
+impl Partition {
+    /// The partition a trade at `timestamp` goes into.
+    pub fn containing(timestamp: i64) -> Self {
+        let from = timestamp.div_euclid(DAY) * DAY;
+        let date = DateTime::from_timestamp(from, 0)
+            .map(|at| at.date_naive())
+            .unwrap_or_default();
+        Self {
+            name: format!("{}{}", PARTITION_PREFIX, date.format("%Y%m%d")),
+            from,
+            to: from + DAY,
+        }
+    }
+
+    /// Back from a table name, None for tables that are not a partition.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let date =
+            NaiveDate::parse_from_str(name.strip_prefix(PARTITION_PREFIX)?, "%Y%m%d").ok()?;
+        let from = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+        Some(Self::containing(from))
+    }
+}
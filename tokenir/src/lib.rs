@@ -9,7 +9,21 @@ pub use token::*;
 pub use types::*;
 
 pub mod access;
+pub mod amm;
+pub mod audit;
 pub mod bundler;
+pub mod capture;
 pub mod constans;
+pub mod create;
 pub mod database;
+pub mod dispatch;
 pub mod filters;
+pub mod health;
+pub mod history;
+pub mod limiter;
+pub mod lookup;
+pub mod migrations;
+pub mod rug;
+pub mod sessions;
+pub mod source;
+pub mod updates;
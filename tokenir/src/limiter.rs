@@ -0,0 +1,241 @@
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Tracked clients above which idle ones are dropped.
+const PRUNE_ABOVE: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LimiterConfig {
+    /// Attempts a client may make back to back
+    pub burst: u32,
+    /// Attempts it gets back per second
+    pub refill_per_sec: f64,
+    /// Failed keys before a ban, forgotten once `ban` passes without another
+    pub max_failures: u32,
+    pub ban: Duration,
+}
+
+impl Default for LimiterConfig {
+    fn default() -> Self {
+        Self {
+            burst: 20,
+            refill_per_sec: 1.0,
+            max_failures: 5,
+            ban: Duration::from_secs(300),
+        }
+    }
+}
+
+/// What attempts are counted against. A login counts against its address
+/// and, when it presents one, the prefix of its key from that address. A bare
+/// prefix is never limited: prefixes are shown in logs and to viewers, so
+/// anyone could lock its owner out with it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LimitKey {
+    Ip(IpAddr),
+    Key(IpAddr, String),
+}
+
+impl fmt::Display for LimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "ip {}", ip),
+            Self::Key(ip, prefix) => write!(f, "key {}... from {}", prefix, ip),
+        }
+    }
+}
+
+/// The attempt was refused, the client may try again after `retry_after`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limited {
+    pub retry_after: Duration,
+}
+
+impl Limited {
+    /// Whole seconds for a `Retry-After` header, at least one.
+    pub fn retry_after_secs(&self) -> u64 {
+        self.retry_after.as_secs_f64().ceil().max(1.0) as u64
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LimiterEntry {
+    pub key: String,
+    pub tokens: f64,
+    pub failures: u32,
+    /// Seconds left of a ban
+    pub banned_for: Option<u64>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    failures: u32,
+    last_failure: Option<Instant>,
+    banned_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn refill(&mut self, config: &LimiterConfig, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * config.refill_per_sec).min(config.burst as f64);
+        self.updated = now;
+        if self.banned_until.is_some_and(|until| until <= now) {
+            self.banned_until = None;
+        }
+        if self
+            .last_failure
+            .is_some_and(|at| now.saturating_duration_since(at) >= config.ban)
+        {
+            self.failures = 0;
+            self.last_failure = None;
+        }
+    }
+
+    /// How long until it can take an attempt, zero if it can now.
+    fn wait(&self, config: &LimiterConfig, now: Instant) -> Duration {
+        if let Some(until) = self.banned_until {
+            return until - now;
+        }
+        if self.tokens >= 1.0 {
+            return Duration::ZERO;
+        }
+        if config.refill_per_sec <= 0.0 {
+            return config.ban;
+        }
+        Duration::from_secs_f64((1.0 - self.tokens) / config.refill_per_sec)
+    }
+
+    fn idle(&self, config: &LimiterConfig) -> bool {
+        self.banned_until.is_none() && self.failures == 0 && self.tokens >= config.burst as f64
+    }
+}
+
+/// Token buckets with failure counting for login attempts, in memory.
+pub struct LoginLimiter {
+    config: LimiterConfig,
+    buckets: Mutex<HashMap<LimitKey, Bucket>>,
+}
+
+impl Default for LoginLimiter {
+    fn default() -> Self {
+        Self::new(LimiterConfig::default())
+    }
+}
+
+impl LoginLimiter {
+    pub fn new(config: LimiterConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &LimiterConfig {
+        &self.config
+    }
+
+    /// Takes an attempt from every key, or none of them if any is out of
+    /// attempts or banned.
+    pub fn check(&self, keys: &[LimitKey]) -> Result<(), Limited> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_ABOVE {
+            let config = self.config;
+            buckets.retain(|_, bucket| {
+                bucket.refill(&config, now);
+                !bucket.idle(&config)
+            });
+        }
+
+        let mut retry_after = Duration::ZERO;
+        for key in keys {
+            let bucket = buckets.entry(key.clone()).or_insert_with(|| Bucket {
+                tokens: self.config.burst as f64,
+                updated: now,
+                failures: 0,
+                last_failure: None,
+                banned_until: None,
+            });
+            bucket.refill(&self.config, now);
+            retry_after = retry_after.max(bucket.wait(&self.config, now));
+        }
+        if !retry_after.is_zero() {
+            return Err(Limited { retry_after });
+        }
+
+        for key in keys {
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts a failed key against every key, bans the ones that reach
+    /// `max_failures` and returns them.
+    pub fn failure(&self, keys: &[LimitKey]) -> Vec<LimitKey> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        let mut banned = Vec::new();
+        for key in keys {
+            let Some(bucket) = buckets.get_mut(key) else {
+                continue;
+            };
+            bucket.failures += 1;
+            bucket.last_failure = Some(now);
+            if bucket.failures >= self.config.max_failures {
+                bucket.failures = 0;
+                bucket.banned_until = Some(now + self.config.ban);
+                banned.push(key.clone());
+            }
+        }
+        banned
+    }
+
+    /// A good key clears the failures counted against that key only. The
+    /// address keeps its count, so holding one valid key doesn't cover for
+    /// guessing others.
+    pub fn success(&self, keys: &[LimitKey]) {
+        let mut buckets = self.buckets.lock().unwrap();
+        for key in keys {
+            if !matches!(key, LimitKey::Key(..)) {
+                continue;
+            }
+            if let Some(bucket) = buckets.get_mut(key) {
+                bucket.failures = 0;
+                bucket.last_failure = None;
+            }
+        }
+    }
+
+    /// Clients that are limited, banned or have failed recently.
+    pub fn snapshot(&self) -> Vec<LimiterEntry> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        buckets
+            .iter_mut()
+            .filter_map(|(key, bucket)| {
+                bucket.refill(&self.config, now);
+                if bucket.idle(&self.config) {
+                    return None;
+                }
+                Some(LimiterEntry {
+                    key: key.to_string(),
+                    tokens: bucket.tokens,
+                    failures: bucket.failures,
+                    banned_for: bucket
+                        .banned_until
+                        .map(|until| (until - now).as_secs_f64().ceil() as u64),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,206 @@
+use dashmap::DashMap;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    message::{v0::MessageAddressTableLookup, VersionedMessage},
+    pubkey,
+    pubkey::Pubkey,
+};
+use std::{
+    borrow::Cow,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(2);
+
+// How long a table that failed to load is left alone before it is retried
+const MISS_TTL: Duration = Duration::from_secs(30);
+
+const LOOKUP_TABLE_PROGRAM: Pubkey = pubkey!("AddressLookupTab1e1111111111111111111111111");
+
+// Serialized `LookupTableMeta`, the addresses follow it back to back
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+// Instruction tags of the lookup table program (bincode u32 enum index)
+const EXTEND_LOOKUP_TABLE: u32 = 2;
+const DEACTIVATE_LOOKUP_TABLE: u32 = 3;
+const CLOSE_LOOKUP_TABLE: u32 = 4;
+
+/// Local copy of address lookup tables used by shredstream transactions.
+///
+/// Tables are fetched over RPC the first time a transaction references them and
+/// refetched when an index points past the cached addresses (the table was
+/// extended) or when an extend/deactivate/close instruction is seen in the stream.
+///
+/// Fetches run in the background so the stream is never held up by RPC; the
+/// transaction that triggered one is counted as unresolved. Tables that fail to
+/// load are not retried for [`MISS_TTL`].
+pub struct LookupTableCache {
+    rpc: RpcClient,
+    tables: DashMap<Pubkey, Arc<Vec<Pubkey>>>,
+    // Tables that failed to load, with when that happened
+    misses: DashMap<Pubkey, Instant>,
+    in_flight: DashMap<Pubkey, ()>,
+    fetched: AtomicU64,
+    unresolved: AtomicU64,
+}
+
+impl LookupTableCache {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            rpc: RpcClient::new(rpc_url),
+            tables: DashMap::new(),
+            misses: DashMap::new(),
+            in_flight: DashMap::new(),
+            fetched: AtomicU64::new(0),
+            unresolved: AtomicU64::new(0),
+        }
+    }
+
+    /// Transactions that referenced a lookup table we could not load.
+    pub fn unresolved(&self) -> u64 {
+        self.unresolved.load(Ordering::Relaxed)
+    }
+
+    /// Tables fetched from RPC since startup (including refreshes).
+    pub fn fetched(&self) -> u64 {
+        self.fetched.load(Ordering::Relaxed)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    pub fn invalidate(&self, table: &Pubkey) {
+        self.tables.remove(table);
+        self.misses.remove(table);
+    }
+
+    /// Full account list of a message: static keys followed by the loaded
+    /// writable and then loaded readonly addresses, in the order the runtime uses.
+    /// Returns `None` (and counts the transaction) when a table isn't cached yet,
+    /// in which case a fetch is started for the transactions that follow.
+    pub fn resolve<'a>(
+        self: &Arc<Self>,
+        message: &'a VersionedMessage,
+    ) -> Option<Cow<'a, [Pubkey]>> {
+        let static_keys = message.static_account_keys();
+
+        let lookups = match message.address_table_lookups() {
+            Some(lookups) if !lookups.is_empty() => lookups,
+            _ => return Some(Cow::Borrowed(static_keys)),
+        };
+
+        let mut writable = Vec::new();
+        let mut readonly = Vec::new();
+
+        for lookup in lookups {
+            let Some(table) = self.table_for(lookup) else {
+                self.unresolved.fetch_add(1, Ordering::Relaxed);
+                return None;
+            };
+
+            for &i in &lookup.writable_indexes {
+                writable.push(table[i as usize]);
+            }
+            for &i in &lookup.readonly_indexes {
+                readonly.push(table[i as usize]);
+            }
+        }
+
+        let mut keys = Vec::with_capacity(static_keys.len() + writable.len() + readonly.len());
+        keys.extend_from_slice(static_keys);
+        keys.extend(writable);
+        keys.extend(readonly);
+
+        Some(Cow::Owned(keys))
+    }
+
+    /// Drops cached tables touched by an address lookup table program instruction.
+    pub fn observe(&self, program_id: &Pubkey, data: &[u8], table: &Pubkey) {
+        if program_id != &LOOKUP_TABLE_PROGRAM || data.len() < 4 {
+            return;
+        }
+
+        let tag = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        if matches!(
+            tag,
+            EXTEND_LOOKUP_TABLE | DEACTIVATE_LOOKUP_TABLE | CLOSE_LOOKUP_TABLE
+        ) {
+            self.invalidate(table);
+        }
+    }
+
+    fn table_for(self: &Arc<Self>, lookup: &MessageAddressTableLookup) -> Option<Arc<Vec<Pubkey>>> {
+        let covers = |table: &[Pubkey]| {
+            lookup
+                .writable_indexes
+                .iter()
+                .chain(&lookup.readonly_indexes)
+                .all(|&i| (i as usize) < table.len())
+        };
+
+        if let Some(table) = self.tables.get(&lookup.account_key) {
+            if covers(&table) {
+                return Some(table.clone());
+            }
+        }
+
+        // Missing or extended since we cached it
+        self.spawn_fetch(lookup.account_key);
+        None
+    }
+
+    fn spawn_fetch(self: &Arc<Self>, key: Pubkey) {
+        if let Some(missed) = self.misses.get(&key) {
+            if missed.elapsed() < MISS_TTL {
+                return;
+            }
+        }
+        if self.in_flight.insert(key, ()).is_some() {
+            return;
+        }
+
+        let cache = self.clone();
+        tokio::spawn(async move {
+            match cache.fetch(&key).await {
+                Some(addresses) => {
+                    cache.fetched.fetch_add(1, Ordering::Relaxed);
+                    cache.tables.insert(key, Arc::new(addresses));
+                    cache.misses.remove(&key);
+                }
+                None => {
+                    cache.misses.insert(key, Instant::now());
+                }
+            }
+            cache.in_flight.remove(&key);
+        });
+    }
+
+    async fn fetch(&self, key: &Pubkey) -> Option<Vec<Pubkey>> {
+        let data = tokio::time::timeout(FETCH_TIMEOUT, self.rpc.get_account_data(key))
+            .await
+            .ok()?
+            .ok()?;
+
+        parse_addresses(&data)
+    }
+}
+
+fn parse_addresses(data: &[u8]) -> Option<Vec<Pubkey>> {
+    let raw = data.get(LOOKUP_TABLE_META_SIZE..)?;
+    if raw.len() % 32 != 0 {
+        return None;
+    }
+
+    raw.chunks_exact(32)
+        .map(|chunk| Pubkey::try_from(chunk).ok())
+        .collect()
+}
@@ -1,87 +1,281 @@
 use axum::{
+    async_trait,
     extract::{
-        ws::{Message, WebSocket, WebSocketUpgrade},
-        Query as AxQuery, State as AxState,
+        ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        ConnectInfo, FromRequestParts, Query as AxQuery, State as AxState,
     },
-    http::StatusCode,
-    response::IntoResponse,
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER, WWW_AUTHENTICATE},
+        request::Parts,
+        StatusCode,
+    },
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::collections::{HashSet, VecDeque};
 use std::env;
-use std::net::SocketAddr;
+use std::marker::PhantomData;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::Instant;
-use std::time::{Duration, SystemTime};
-use tokio::sync::{broadcast, mpsc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch, Mutex, RwLock};
 use tower_http::cors::{Any, CorsLayer};
 
 // Library imports
-use tokenir::database::{Database, DbToken};
+use tokenir::amm::AmmPoolCache;
+use tokenir::audit::AuditEvent;
+use tokenir::bundler::{Bundler, BundlerConfig};
+use tokenir::capture::CaptureWriter;
+use tokenir::create::{CommunityCache, CreateFlow, LiveLookups, TokenCache};
+use tokenir::database::Database;
+use tokenir::dispatch::{DispatchConfig, DispatchStats, Dispatcher};
+use tokenir::filters::FilterSet;
+use tokenir::health::HealthRegistry;
+use tokenir::history::TradeRecord;
+use tokenir::limiter::{LimitKey, Limited, LimiterConfig, LoginLimiter};
 use tokenir::logs::{CreateEvent, Event};
-use tokenir::{access::AddUserPayload, usd_mcap};
+use tokenir::lookup::LookupTableCache;
+use tokenir::rug::{DevTracker, RugConfig};
+use tokenir::sessions::{EvictionPolicy, SessionHandle, SessionLimit, SessionRegistry};
+use tokenir::source::{
+    EventEnvelope, LogsSource, Multiplexer, PumpPortalSource, ReplaySource, ShredSource, SourceKind,
+};
+use tokenir::updates::{TokenUpdate, UpdateTracker};
 use tokenir::{
-    constans::helper::{fetch_solana_price, get_community_by_id, get_metadata, parse_community_id},
-    requests::Metadata,
+    access::{key_prefix, AddUserPayload, Role, User},
+    usd_mcap,
 };
-use tokenir::{Client, DevPerformance, Token};
+use tokenir::constans::helper::{
+    fetch_solana_price, get_community_by_id, get_metadata, parse_community_id, pool_pda,
+};
+use tokenir::{Client, ParseStats, Token};
 
 // --- Optimized Data Types ---
 
 #[derive(Serialize, Clone)]
 #[serde(tag = "type", content = "data")]
-enum SocketMessage {
+enum SocketMessage<'a> {
+    NewToken(&'a Token),
+    TokenUpdate(&'a TokenUpdate),
+}
+
+/// What goes out to the sockets, serialized once per encoding and filtered
+/// per session.
+struct Broadcast {
+    /// Position in the feed, also in the message as `seq`
+    seq: u64,
+    json: String,
+    msgpack: Vec<u8>,
+    payload: Payload,
+}
+
+impl Broadcast {
+    /// axum 0.7 messages own their payload, so every socket gets its own
+    /// copy of the encoded bytes. Encoding still happens once.
+    fn frame(&self, encoding: Encoding) -> Message {
+        match encoding {
+            Encoding::Json => Message::Text(self.json.clone()),
+            Encoding::Msgpack => Message::Binary(self.msgpack.clone()),
+        }
+    }
+}
+
+/// How a session wants the feed. Replies and notices are always JSON text.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Encoding {
+    #[default]
+    Json,
+    /// Binary frames, same shape as the JSON
+    Msgpack,
+}
+
+enum Payload {
     NewToken(Token),
+    /// Only sent to sessions that were sent the token itself
+    Update(Pubkey),
 }
 
-struct AppState {
-    tx: broadcast::Sender<Arc<String>>,
-    db: Arc<Database>,
-    active_connections: Arc<Mutex<HashMap<String, u64>>>,
-    next_session_id: AtomicU64,
-    token_cache: Arc<Mutex<TokenCache>>,
-    community_cache: Arc<Mutex<CommunityCache>>,
-    // Add this:
-    shutdown_tx: mpsc::Sender<()>,
+// Tokens per session that keep receiving updates
+const SEEN_PER_SESSION: usize = 2_000;
+
+// Sessions are pinged this often and dropped when silent for PONG_TIMEOUT
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+const PONG_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The broadcast channel plus the most recent messages sent on it, so a
+/// client that reconnects with `resume_from` gets what it missed.
+struct Feed {
+    tx: broadcast::Sender<Arc<Broadcast>>,
+    history: std::sync::Mutex<History>,
 }
 
-#[derive(Default)]
-struct TokenCache {
-    images: HashMap<String, ()>,
-    ipfs: HashMap<String, ()>,
-    descriptions: HashMap<String, ()>,
-    names: HashMap<String, ()>,
-    tickers: HashMap<String, ()>,
-    name_ticker_pairs: HashMap<(String, String), ()>,
-    desc_name_pairs: HashMap<(String, String), ()>,
-    desc_ticker_pairs: HashMap<(String, String), ()>,
+struct History {
+    next_seq: u64,
+    recent: VecDeque<Arc<Broadcast>>,
+    capacity: usize,
+}
+
+impl Feed {
+    fn new(capacity: usize) -> Self {
+        // OPTIMIZATION: Massive channel buffer for burst handling
+        let (tx, _rx) = broadcast::channel(10000);
+        Self {
+            tx,
+            history: std::sync::Mutex::new(History {
+                next_seq: 0,
+                recent: VecDeque::with_capacity(capacity),
+                capacity,
+            }),
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<Broadcast>> {
+        self.tx.subscribe()
+    }
+
+    /// Numbers the message, keeps it and sends it to every socket.
+    fn publish(&self, mut message: serde_json::Value, payload: Payload) {
+        // Numbering and sending under one lock keeps the channel in seq order
+        let mut history = self.history.lock().unwrap();
+        let seq = history.next_seq;
+
+        if let Some(object) = message.as_object_mut() {
+            object.insert("seq".to_string(), seq.into());
+        }
+        let msgpack = match rmp_serde::to_vec(&message) {
+            Ok(msgpack) => msgpack,
+            Err(err) => {
+                eprintln!("[feed] failed to encode message {}: {}", seq, err);
+                return;
+            }
+        };
+        history.next_seq += 1;
+
+        let broadcast = Arc::new(Broadcast {
+            seq,
+            json: message.to_string(),
+            msgpack,
+            payload,
+        });
+
+        history.recent.push_back(broadcast.clone());
+        if history.recent.len() > history.capacity {
+            history.recent.pop_front();
+        }
+        // Fire and forget - non-blocking send
+        let _ = self.tx.send(broadcast);
+    }
+
+    /// Kept messages after `seq`, and whether some in between were already
+    /// dropped (or `seq` is from before a restart).
+    fn since(&self, seq: u64) -> (Vec<Arc<Broadcast>>, bool) {
+        let history = self.history.lock().unwrap();
+        let oldest = history
+            .recent
+            .front()
+            .map_or(history.next_seq, |broadcast| broadcast.seq);
+        let gap = seq.saturating_add(1) < oldest || seq >= history.next_seq;
+
+        let missed = history
+            .recent
+            .iter()
+            .filter(|broadcast| broadcast.seq > seq || gap)
+            .cloned()
+            .collect();
+        (missed, gap)
+    }
+
+    fn oldest_seq(&self) -> Option<u64> {
+        self.history.lock().unwrap().recent.front().map(|b| b.seq)
+    }
 }
 
+/// Tokens a session was sent, oldest forgotten first.
 #[derive(Default)]
-struct CommunityCache {
-    community_ids: HashMap<String, ()>,
+struct SeenTokens {
+    set: HashSet<Pubkey>,
+    order: VecDeque<Pubkey>,
 }
 
-type SharedState = Arc<AppState>;
+impl SeenTokens {
+    fn insert(&mut self, mint: Pubkey) {
+        if self.set.insert(mint) {
+            self.order.push_back(mint);
+            if self.order.len() > SEEN_PER_SESSION {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.set.remove(&oldest);
+                }
+            }
+        }
+    }
+}
 
+/// The frame to send this session, if the message is for it.
+fn outgoing(
+    broadcast: &Broadcast,
+    encoding: Encoding,
+    filters: &watch::Receiver<Option<FilterSet>>,
+    seen: &mut SeenTokens,
+) -> Option<Message> {
+    match &broadcast.payload {
+        Payload::NewToken(token) => {
+            let wanted = match &*filters.borrow() {
+                Some(filters) => filters.matches(token),
+                None => true,
+            };
+            if !wanted {
+                return None;
+            }
+            seen.insert(token.mint);
+        }
+        Payload::Update(mint) => {
+            if !seen.set.contains(mint) {
+                return None;
+            }
+        }
+    }
+    Some(broadcast.frame(encoding))
+}
+
+/// What a client can send over /ws.
 #[derive(Deserialize)]
-struct WsAuth {
-    key: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Only receive tokens matching every filter, replaces earlier filters
+    Subscribe { filters: FilterSet },
+    /// Back to receiving every token
+    Unsubscribe,
 }
 
-#[derive(Serialize)]
-struct ConnectionStatus {
-    key: String,
-    session_id: u64,
+struct AppState {
+    feed: Arc<Feed>,
+    db: Arc<Database>,
+    sessions: SessionRegistry,
+    session_policy: EvictionPolicy,
+    limiter: LoginLimiter,
+    token_cache: Arc<Mutex<TokenCache>>,
+    community_cache: Arc<Mutex<CommunityCache>>,
+    upstreams: Arc<HealthRegistry>,
+    // Add this:
+    shutdown_tx: mpsc::Sender<()>,
 }
 
+type SharedState = Arc<AppState>;
+
 #[derive(Deserialize)]
-struct RestartReq {
-    admin_key: String,
+struct WsAuth {
+    key: String,
+    /// Last `seq` the client received before it reconnected
+    resume_from: Option<u64>,
+    #[serde(default)]
+    encoding: Encoding,
 }
 
 // --- Main Server ---
@@ -90,22 +284,101 @@ struct RestartReq {
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     dotenv::dotenv().ok();
 
-    // OPTIMIZATION: Massive channel buffer for burst handling
-    let (broadcast_tx, _rx) = broadcast::channel(10000);
+    // Recent messages kept for clients resuming after a reconnect
+    let resume_buffer = env::var("RESUME_BUFFER")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4096);
+    let feed = Arc::new(Feed::new(resume_buffer));
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
 
+    // What to do with a new session of a key at its `max_sessions`
+    let session_policy = env::var("SESSION_POLICY")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_default();
+
+    // Login attempts per address and per key prefix from it, bans after failed keys
+    let defaults = LimiterConfig::default();
+    let limiter = LoginLimiter::new(LimiterConfig {
+        burst: env::var("LOGIN_BURST")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(defaults.burst),
+        refill_per_sec: env::var("LOGIN_REFILL_PER_SEC")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(defaults.refill_per_sec),
+        max_failures: env::var("LOGIN_MAX_FAILURES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(defaults.max_failures),
+        ban: env::var("LOGIN_BAN_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(defaults.ban),
+    });
+
     let database =
         Arc::new(Database::new(std::env::var("SQL").expect("SQL env var missing")).await?);
 
     let _ = database.initialize_tables().await?;
+    // First start: creates the admin from ADMIN_BOOTSTRAP_KEY, unless one exists
+    database
+        .bootstrap_admin(env::var("ADMIN_BOOTSTRAP_KEY").ok())
+        .await?;
+
+    // New tokens and ATH updates, written in batches
+    let bundler = Arc::new(Bundler::new(BundlerConfig {
+        max_batch: env::var("BUNDLE_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(BundlerConfig::default().max_batch),
+        interval: env::var("BUNDLE_INTERVAL_MS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map(Duration::from_millis)
+            .unwrap_or(BundlerConfig::default().interval),
+        max_pending: env::var("BUNDLE_MAX_PENDING")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(BundlerConfig::default().max_pending),
+        max_trades: env::var("BUNDLE_MAX_TRADES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(BundlerConfig::default().max_trades),
+    }));
+    let flusher = bundler.spawn(database.clone());
+
+    // Trade history is kept for this long, in daily partitions
+    let trades_retention = env::var("TRADES_RETENTION_DAYS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(30);
+    tokio::spawn({
+        let database = database.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+            loop {
+                interval.tick().await;
+                let retention = Duration::from_secs(trades_retention * 24 * 60 * 60);
+                if let Err(err) = database.maintain_trade_partitions(retention).await {
+                    eprintln!("[db] trades partition upkeep failed: {}", err);
+                }
+            }
+        }
+    });
 
     let shared_state = Arc::new(AppState {
-        tx: broadcast_tx.clone(),
+        feed: feed.clone(),
         db: database.clone(),
-        active_connections: Arc::new(Mutex::new(HashMap::new())),
-        next_session_id: AtomicU64::new(0),
+        sessions: SessionRegistry::default(),
+        session_policy,
+        limiter,
         token_cache: Arc::new(Mutex::new(TokenCache::default())),
         community_cache: Arc::new(Mutex::new(CommunityCache::default())),
+        upstreams: Arc::new(HealthRegistry::default()),
         shutdown_tx,
     });
 
@@ -115,6 +388,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let ipfs_local_node =
         Arc::new(env::var("IPFS").unwrap_or_else(|_| "http://127.0.0.1:5001".to_string()));
 
+    // Callbacks in flight per connection, events of one token run in order
+    let dispatch = DispatchConfig {
+        concurrency: env::var("DISPATCH_CONCURRENCY")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DispatchConfig::default().concurrency),
+        creates: env::var("DISPATCH_CREATES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(DispatchConfig::default().creates),
+        ..DispatchConfig::default()
+    };
+
     // --- Background Task: SOL Price Polling ---
     tokio::spawn({
         let sp_clone = sol_price.clone();
@@ -133,74 +419,239 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // CONNECTION 1: SERVING (Ultra-Fast Broadcast)
     // --------------------------------------------------------
     let url_serving = rpc_url.clone();
-    let b_tx_serving = broadcast_tx.clone();
-    let tw_serving = twitter_key.clone();
-    let db_serving = database.clone();
-    let cache_serving = shared_state.token_cache.clone();
-    let comm_cache_serving = shared_state.community_cache.clone();
-    let ipfs_local_node_clone = ipfs_local_node.clone();
+    let feed_serving = feed.clone();
+    let create_flow = Arc::new(CreateFlow::new(
+        LiveLookups::new(
+            database.clone(),
+            twitter_key.clone(),
+            ipfs_local_node.clone(),
+        ),
+        shared_state.token_cache.clone(),
+    ));
+    let sp_serving = sol_price.clone();
+    let upstreams_serving = shared_state.upstreams.clone();
+
+    // Live mcap/ATH of fresh tokens, pushed to the sockets at most once per interval
+    let updates_serving = Arc::new(UpdateTracker::new(Duration::from_secs(30 * 60)));
+    let update_interval = env::var("UPDATE_INTERVAL_MS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1000);
+    tokio::spawn({
+        let updates = updates_serving.clone();
+        let feed = feed.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(update_interval));
+            loop {
+                interval.tick().await;
+                for update in updates.drain() {
+                    let mint = update.mint;
+                    if let Ok(message) = serde_json::to_value(SocketMessage::TokenUpdate(&update)) {
+                        feed.publish(message, Payload::Update(mint));
+                    }
+                }
+            }
+        }
+    });
+    let rpc_http = env::var("RPC_HTTP").expect("RPC_HTTP env var missing");
+    let lookup_tables = Arc::new(LookupTableCache::new(rpc_http.clone()));
+    let amm_pools = Arc::new(AmmPoolCache::new(rpc_http));
+
+    // --- Background Task: Lookup Table / Pool Cache Stats ---
+    tokio::spawn({
+        let lookup_tables = lookup_tables.clone();
+        let amm_pools = amm_pools.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                println!(
+                    "[alt] cached tables: {} | fetched: {} | unresolved txs: {}",
+                    lookup_tables.len(),
+                    lookup_tables.fetched(),
+                    lookup_tables.unresolved()
+                );
+                println!(
+                    "[amm] cached pools: {} | unresolved swaps: {}",
+                    amm_pools.len(),
+                    amm_pools.unresolved()
+                );
+            }
+        }
+    });
 
     tokio::spawn(async move {
-        let client = Client::new("wss://pumpportal.fun/api/data".to_string());
         println!("[subscriber] Serving connection started...");
 
-        let jito_link = env::var("SHREDS").expect("No SHREDS link found in .env");
+        // Shreds first, program logs and PumpPortal as fallbacks. Fallback
+        // events wait far less than the 5s a create may take in total
+        let mut mux = Multiplexer::new(Duration::from_secs(5)).hold(Duration::from_millis(300));
+
+        if let Ok(path) = env::var("REPLAY") {
+            // Offline run: feed a capture through the same pipeline
+            let speed = env::var("REPLAY_SPEED")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(1.0);
+            println!("[replay] replaying {} at {}x", path, speed);
+
+            let mut streams = vec![];
+            for kind in [
+                SourceKind::Shredstream,
+                SourceKind::Logs,
+                SourceKind::PumpPortal,
+            ] {
+                let source = ReplaySource::new(path.clone(), kind)
+                    .speed(speed)
+                    .with_lookup_tables(lookup_tables.clone());
+                streams.push((format!("replay {:?}", kind), source.parse_stats()));
+                mux.add(source);
+            }
+            spawn_parse_stats_log(streams);
+        } else {
+            let jito_link = env::var("SHREDS").expect("No SHREDS link found in .env");
+
+            let capture = env::var("CAPTURE").ok().map(|path| {
+                println!("[capture] appending raw messages to {}", path);
+                Arc::new(CaptureWriter::open(path).expect("can't open capture file"))
+            });
+
+            let mut shreds = ShredSource::new(jito_link, lookup_tables);
+            let mut logs = LogsSource::new(url_serving, false);
+            let mut pumpportal =
+                PumpPortalSource::new("wss://pumpportal.fun/api/data".to_string());
+
+            shreds = shreds.with_health(upstreams_serving.clone());
+            logs = logs.with_health(upstreams_serving.clone());
+            pumpportal = pumpportal.with_health(upstreams_serving);
+
+            if let Some(capture) = capture {
+                shreds = shreds.with_capture(capture.clone());
+                logs = logs.with_capture(capture.clone());
+                pumpportal = pumpportal.with_capture(capture);
+            }
+
+            spawn_parse_stats_log(vec![
+                ("shreds".to_string(), shreds.parse_stats()),
+                ("logs".to_string(), logs.parse_stats()),
+                ("pumpportal".to_string(), pumpportal.parse_stats()),
+            ]);
 
-        let _ = client
-            .subscribe_jito(jito_link, move |(event, time)| {
-                let b_tx = b_tx_serving.clone();
-                let tw_key = tw_serving.clone();
-                let db = db_serving.clone();
-                let cache = cache_serving.clone();
-                let comm_cache = comm_cache_serving.clone();
-                let ipfs_local_node = ipfs_local_node_clone.clone();
-                async move {
-                    if let Event::Create(data) = event {
-                        // OPTIMIZATION: Inline fast-path processing
-                        tokio::spawn(async move {
-                            if let Some(token) = process_fast_create(
-                                data,
-                                &tw_key,
-                                time,
-                                db,
-                                cache,
-                                comm_cache,
-                                &ipfs_local_node,
-                            )
+            mux.add(shreds);
+            mux.add(logs);
+            mux.add(pumpportal);
+        }
+
+        let dispatcher = Dispatcher::new(dispatch, move |envelope: EventEnvelope| {
+            let feed = feed_serving.clone();
+            let create_flow = create_flow.clone();
+            let sp = sp_serving.clone();
+            let updates = updates_serving.clone();
+            async move {
+                match envelope.event {
+                    Event::Create(data) => {
+                        if let Some(token) = create_flow
+                            .process(data, envelope.slot, envelope.signature, envelope.received)
                             .await
-                            {
-                                // OPTIMIZATION: Pre-serialize and wrap in Arc for zero-copy broadcast
-                                if let Ok(json_string) = serde_json::to_string(&token) {
-                                    let shared_msg = Arc::new(json_string);
-                                    // Fire and forget - non-blocking send
-                                    let _ = b_tx.send(shared_msg);
-                                }
+                        {
+                            updates.track(&token);
+
+                            if let Ok(message) = serde_json::to_value(SocketMessage::NewToken(&token)) {
+                                feed.publish(message, Payload::NewToken(token));
                             }
-                        });
+                        }
                     }
+                    // Estimated when it comes from the shredstream, the analysis
+                    // connection confirms it once the trade lands
+                    Event::Buy(data) => {
+                        let mcap = usd_mcap(
+                            data.virtual_sol_reserves_before,
+                            data.virtual_token_reserves,
+                            sp.load(Ordering::Relaxed),
+                        );
+                        // Only pushed to the sockets, the stored ATH comes from
+                        // landed trades on the analysis connection
+                        if envelope.replaces_estimate {
+                            updates.correct(&data.mint, mcap);
+                        } else {
+                            updates.trade(&data.mint, true, data.sol_amount, mcap);
+                        }
+                    }
+                    Event::Sell(data) => {
+                        let mcap = usd_mcap(
+                            data.virtual_sol_reserves_before,
+                            data.virtual_token_reserves,
+                            sp.load(Ordering::Relaxed),
+                        );
+                        if envelope.replaces_estimate {
+                            updates.correct(&data.mint, mcap);
+                        } else {
+                            updates.trade(&data.mint, false, data.sol_amount, mcap);
+                        }
+                    }
+                    _ => {}
                 }
-            })
-            .await;
+            }
+        });
+        spawn_dispatch_stats_log(vec![("serving".to_string(), dispatcher.stats())]);
+
+        mux.run(move |envelope| {
+            dispatcher.dispatch(envelope);
+            async {}
+        })
+        .await;
     });
 
     // --------------------------------------------------------
     // CONNECTION 2: SAVING & ANALYZING (Database / Deep Logic)
     // --------------------------------------------------------
+    // Dev wallets of new tokens, a dump soon after creation marks a rug
+    let devs = Arc::new(DevTracker::new(RugConfig {
+        sell_percentage: env::var("RUG_SELL_PERCENT")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(RugConfig::default().sell_percentage),
+        window: env::var("RUG_WINDOW_SECS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(RugConfig::default().window),
+        ..RugConfig::default()
+    }));
+    tokio::spawn({
+        let devs = devs.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                devs.prune();
+            }
+        }
+    });
+
     let url_analysis = rpc_url.clone();
     let db_analysis = database.clone();
+    let bundler_analysis = bundler.clone();
     let tw_analysis = twitter_key.clone();
     let sp_analysis = sol_price.clone();
     let cache_analysis = shared_state.token_cache.clone();
     let comm_cache_analysis = shared_state.community_cache.clone();
+    let upstreams_analysis = shared_state.upstreams.clone();
 
     tokio::spawn(async move {
-        let client = Client::new(url_analysis);
+        let client = Client::new(url_analysis)
+            .with_amm_pools(amm_pools)
+            .with_dispatch(dispatch)
+            .with_health(upstreams_analysis, "analysis");
+        spawn_parse_stats_log(vec![("analysis".to_string(), client.parse_stats())]);
+        spawn_dispatch_stats_log(vec![("analysis".to_string(), client.dispatch_stats())]);
         println!("[subscriber] Analysis connection started...");
 
         let _ = client
             .subscribe_to_pump(
-                move |(_time, event)| {
+                move |envelope| {
                     let db = db_analysis.clone();
+                    let bundler = bundler_analysis.clone();
+                    let devs = devs.clone();
                     let tw_key = tw_analysis.clone();
                     let sp = sp_analysis.clone();
                     let cache = cache_analysis.clone();
@@ -208,19 +659,36 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     let ipfs_local_node_clone_clone = ipfs_local_node.clone();
 
                     async move {
-                        match event {
+                        // Every landed curve trade goes into the history
+                        if !envelope.failed {
+                            if let Some(trade) = TradeRecord::from_event(
+                                &envelope.event,
+                                envelope.slot,
+                                envelope.signature,
+                            ) {
+                                bundler.add_trade(trade);
+                            }
+                            if let Some((pool, activity)) = devs.record(&envelope.event) {
+                                bundler.add_dev_activity(pool, activity);
+                            }
+                        }
+
+                        match envelope.event {
                             Event::Create(data) => {
-                                tokio::spawn(async move {
-                                    let _ = process_slow_create(
-                                        data,
-                                        db,
-                                        &tw_key,
-                                        cache,
-                                        comm_cache,
-                                        &ipfs_local_node_clone_clone,
-                                    )
-                                    .await;
-                                });
+                                // Before the lookups below, the dev buy comes right after
+                                devs.watch(&data.mint, data.user, data.timestamp);
+                                let _ = process_slow_create(
+                                    data,
+                                    envelope.slot,
+                                    envelope.signature,
+                                    db,
+                                    &bundler,
+                                    &tw_key,
+                                    cache,
+                                    comm_cache,
+                                    &ipfs_local_node_clone_clone,
+                                )
+                                .await;
                             }
                             Event::Buy(data) => {
                                 let current_sol_price = sp.load(Ordering::Relaxed);
@@ -230,7 +698,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     current_sol_price,
                                 ) as i64;
 
-                                let _ = db.update_token_ath(&data.mint, mcap).await;
+                                bundler.add_ath(data.mint, mcap);
+                            }
+                            // Graduated tokens keep their ATH going on PumpSwap,
+                            // `pool_address` is the pool they migrated into
+                            Event::AmmTrade(data) if data.is_buy => {
+                                let current_sol_price = sp.load(Ordering::Relaxed);
+                                let mcap = data.usd_mcap(current_sol_price) as i64;
+
+                                bundler.add_ath(data.pool, mcap);
+                            }
+                            Event::Complete(data) => {
+                                println!("[graduation] {} completed its curve", data.mint);
+                                let _ = db.mark_token_complete(&data.mint, data.timestamp).await;
+                            }
+                            Event::Migrated(data) => {
+                                println!("[graduation] {} migrated to {}", data.mint, data.pool);
+                                let _ = db
+                                    .mark_token_migrated(&data.mint, &data.pool, data.timestamp)
+                                    .await;
+                            }
+                            // The PumpSwap side of a migration, covers a missed
+                            // migrate event. Only the canonical pool counts, anyone
+                            // can open other pools for a mint.
+                            Event::PoolCreated(data)
+                                if data.pool == pool_pda(&data.base_mint).0 =>
+                            {
+                                let _ = db
+                                    .mark_token_migrated(
+                                        &data.base_mint,
+                                        &data.pool,
+                                        data.timestamp,
+                                    )
+                                    .await;
                             }
                             _ => {}
                         }
@@ -246,9 +746,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/ws", get(ws_handler))
         .route("/admin/add_user", post(add_user_handler))
         .route("/admin/remove_user", post(remove_user_handler))
-        .route("/admin/users", post(get_users_handler))
+        .route("/admin/disable_user", post(disable_user_handler))
+        .route("/admin/rotate_key", post(rotate_key_handler))
+        .route(
+            "/admin/users",
+            get(get_users_handler).post(get_users_handler),
+        )
         .route("/admin/connections", get(get_connections_handler))
+        .route(
+            "/admin/disconnect_session",
+            post(disconnect_session_handler),
+        )
         .route("/admin/restart", post(restart_handler)) // New Route
+        .route("/admin/audit", get(get_audit_handler))
         .with_state(shared_state)
         .layer(
             CorsLayer::new()
@@ -263,83 +773,171 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let listener = tokio::net::TcpListener::bind(&addr).await?;
 
     // Wrap the serve with graceful shutdown
-    axum::serve(listener, app.into_make_service())
+    // Peer addresses go into the audit log
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    axum::serve(listener, service)
         .with_graceful_shutdown(async move {
-            shutdown_rx.recv().await;
-            println!("[server] shutdown signal received, closing server...");
+            shutdown_signal(&mut shutdown_rx).await;
+            println!("[server] closing server...");
         })
         .await?;
 
+    // The flusher may be mid-write, let it finish before the last flush
+    bundler.stop();
+    if let Err(err) = flusher.await {
+        eprintln!("[bundler] flusher task failed: {}", err);
+    }
+    match bundler.flush(&database).await {
+        Ok(written) => println!("[bundler] flushed {} writes before exiting", written),
+        Err(err) => eprintln!("[bundler] final flush failed: {}", err),
+    }
+
     println!("[server] server stopped. exiting with code 0 for systemd restart.");
     std::process::exit(0);
 }
 
+/// Waits for `/admin/restart`, Ctrl-C or SIGTERM (systemd stop).
+async fn shutdown_signal(restart: &mut mpsc::Receiver<()>) {
+    let terminate = async {
+        #[cfg(unix)]
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+                return;
+            }
+            Err(err) => eprintln!("[server] can't listen for SIGTERM: {}", err),
+        }
+        std::future::pending::<()>().await
+    };
+
+    tokio::select! {
+        _ = restart.recv() => println!("[server] restart requested"),
+        _ = tokio::signal::ctrl_c() => println!("[server] ctrl-c received"),
+        _ = terminate => println!("[server] SIGTERM received"),
+    }
+}
+
 // --- WS HANDLERS ---
 
 async fn ws_handler(
     ws: WebSocketUpgrade,
     AxQuery(auth): AxQuery<WsAuth>,
     AxState(state): AxState<SharedState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> impl IntoResponse {
-    println!("[ws] connection attempt with key: {}", auth.key);
-
-    match state.db.validate_user_key(&auth.key).await {
-        Ok(true) => {
-            // Generate session ID
-            let session_id = state.next_session_id.fetch_add(1, Ordering::SeqCst);
-
-            // CRITICAL FIX: Check if key already has an active connection
-            let mut connections = state.active_connections.lock().await;
+    // Only the clear prefix of a key is ever logged
+    let prefix = key_prefix(&auth.key).to_string();
+    println!("[ws] connection attempt with key: {}...", prefix);
 
-            if let Some(old_session_id) = connections.get(&auth.key).cloned() {
-                let total = connections.len();
-                drop(connections);
-                println!(
-                    "[ws] REJECTED: key {} already has active session {} (tried to start session {}) | total connections: {}",
-                    auth.key, old_session_id, session_id, total
-                );
-                return (
-                    StatusCode::CONFLICT, 
-                    "Another session is already active for this key. Please close the existing connection first."
-                ).into_response();
-            }
+    // Checked before the key costs a database lookup
+    let limit_keys = limit_keys(Some(addr.ip()), Some(&auth.key));
+    if let Err(limited) = state.limiter.check(&limit_keys) {
+        println!(
+            "[ws] rate limited: {}... from {}, retry in {}s",
+            prefix,
+            addr.ip(),
+            limited.retry_after_secs()
+        );
+        return too_many_requests(limited);
+    }
 
-            // Insert BEFORE upgrading websocket
-            connections.insert(auth.key.clone(), session_id);
-            let total = connections.len();
-            drop(connections);
+    match state.db.find_user_by_key(&auth.key).await {
+        Ok(Some(user)) => {
+            state.limiter.success(&limit_keys);
+            // Registered before upgrading so a full key gets a 409, the
+            // handle frees the seat if the upgrade never completes
+            let opened = state.sessions.open(
+                user.id,
+                &user.key_prefix,
+                user.max_sessions.max(0) as usize,
+                state.session_policy,
+            );
+            let session = match opened {
+                Ok(session) => session,
+                Err(SessionLimit { max }) => {
+                    println!(
+                        "[ws] REJECTED: key {}... already has {} active sessions | total connections: {}",
+                        prefix,
+                        max,
+                        state.sessions.len()
+                    );
+                    return (
+                        StatusCode::CONFLICT,
+                        format!(
+                            "This key allows {} active sessions. Please close an existing connection first.",
+                            max
+                        ),
+                    )
+                        .into_response();
+                }
+            };
 
             println!(
-                "[ws] authorized: {} | session_id: {} | total unique keys: {}",
-                auth.key, session_id, total
+                "[ws] authorized: {}... | session_id: {} | total connections: {}",
+                prefix,
+                session.id,
+                state.sessions.len()
             );
 
-            ws.on_upgrade(move |socket| handle_socket(socket, state, auth.key, session_id))
+            ws.on_upgrade(move |socket| {
+                handle_socket(
+                    socket,
+                    state,
+                    user,
+                    session,
+                    auth.resume_from,
+                    auth.encoding,
+                )
+            })
         }
-        _ => {
-            println!("[ws] forbidden: {}", auth.key);
+        found => {
+            println!("[ws] forbidden: {}...", prefix);
+            if let Ok(None) = found {
+                login_failed(&state, &limit_keys, Some(addr.ip())).await;
+            }
+            audit(
+                &state,
+                AuditEvent::new("ws_login_failed")
+                    .with_actor(prefix)
+                    .with_ip(Some(addr.ip())),
+            )
+            .await;
             (StatusCode::FORBIDDEN, "Unauthorized").into_response()
         }
     }
 }
 
-async fn handle_socket(socket: WebSocket, state: SharedState, key: String, session_id: u64) {
+async fn handle_socket(
+    socket: WebSocket,
+    state: SharedState,
+    user: User,
+    mut session: SessionHandle,
+    resume_from: Option<u64>,
+    encoding: Encoding,
+) {
+    let session_id = session.id;
+    let key = format!("{}...", user.key_prefix);
     println!(
-        "[ws] socket handler started for key: {} | session_id: {}",
-        key, session_id
+        "[ws] socket handler started for key: {} | session_id: {} | encoding: {:?}",
+        key, session_id, encoding
     );
 
-    let mut rx_broadcast = state.tx.subscribe();
+    // Subscribed before looking at the history, so nothing falls in between
+    let mut rx_broadcast = state.feed.subscribe();
     let (mut sink, mut stream) = socket.split();
+    // Filters of this session, `None` sends everything
+    let (filters_tx, filters_rx) = watch::channel(None::<FilterSet>);
+    // Replies to client messages, written by the send task
+    let (reply_tx, mut reply_rx) = mpsc::channel::<String>(16);
     let key_clone = key.clone();
     let key_clone2 = key.clone();
+    // Milliseconds after `started` the client was last heard from
+    let started = Instant::now();
+    let last_heard = Arc::new(AtomicU64::new(0));
+    let last_heard_recv = last_heard.clone();
 
     // Send autobuy status notification immediately after connection
-    let autobuy_status = state
-        .db
-        .get_user_autobuy_status(&key)
-        .await
-        .unwrap_or(false);
+    let autobuy_status = user.autobuy;
     let status_msg = serde_json::json!({
         "type": "connection_info",
         "autobuy": autobuy_status,
@@ -355,12 +953,90 @@ async fn handle_socket(socket: WebSocket, state: SharedState, key: String, sessi
         let _ = sink.send(Message::Text(json_str)).await;
     }
 
+    let missed = match resume_from {
+        Some(seq) => {
+            let (missed, gap) = state.feed.since(seq);
+            if gap {
+                println!(
+                    "[ws] session {} resumes from {}, too far back",
+                    session_id, seq
+                );
+                let notice = serde_json::json!({
+                    "type": "gap_too_large",
+                    "resume_from": seq,
+                    "oldest": state.feed.oldest_seq(),
+                });
+                let _ = sink.send(Message::Text(notice.to_string())).await;
+            }
+            missed
+        }
+        None => vec![],
+    };
+
     // OPTIMIZATION: Buffered writes for better throughput
-    let send_task = tokio::spawn(async move {
+    let mut send_task = tokio::spawn(async move {
+        let mut seen = SeenTokens::default();
+
+        // Missed messages first, live ones already replayed are skipped below
+        let mut replayed = None;
+        for broadcast in &missed {
+            if let Some(frame) = outgoing(broadcast, encoding, &filters_rx, &mut seen) {
+                if sink.send(frame).await.is_err() {
+                    return;
+                }
+            }
+            replayed = Some(broadcast.seq);
+        }
+
+        let mut ping = tokio::time::interval(PING_INTERVAL);
+        ping.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
         loop {
-            match rx_broadcast.recv().await {
+            let received = tokio::select! {
+                received = rx_broadcast.recv() => received,
+                Some(reply) = reply_rx.recv() => {
+                    if sink.send(Message::Text(reply)).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                reason = session.closed() => {
+                    println!("[ws] closing session {}: {}", session_id, reason);
+                    let _ = sink
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::POLICY,
+                            reason: reason.into(),
+                        })))
+                        .await;
+                    break;
+                }
+                _ = ping.tick() => {
+                    let heard = Duration::from_millis(last_heard.load(Ordering::Relaxed));
+                    if started.elapsed().saturating_sub(heard) > PONG_TIMEOUT {
+                        println!(
+                            "[ws] session {} timed out, no pong for {}s",
+                            session_id,
+                            PONG_TIMEOUT.as_secs()
+                        );
+                        break;
+                    }
+                    if sink.send(Message::Ping(vec![])).await.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+            };
+
+            match received {
                 Ok(arc_msg) => {
-                    if sink.send(Message::Text((*arc_msg).clone())).await.is_err() {
+                    if replayed.is_some_and(|seq| arc_msg.seq <= seq) {
+                        continue;
+                    }
+                    let Some(frame) = outgoing(&arc_msg, encoding, &filters_rx, &mut seen) else {
+                        continue;
+                    };
+
+                    if sink.send(frame).await.is_err() {
                         println!(
                             "[ws] send failed for key: {} session: {}",
                             key_clone, session_id
@@ -382,15 +1058,39 @@ async fn handle_socket(socket: WebSocket, state: SharedState, key: String, sessi
         }
     });
 
-    let recv_task = tokio::spawn(async move {
+    let mut recv_task = tokio::spawn(async move {
         while let Some(result) = stream.next().await {
-            if result.is_err() {
-                println!(
-                    "[ws] recv error for key: {} session: {}",
-                    key_clone2, session_id
-                );
-                break;
-            }
+            last_heard_recv.store(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+
+            let text = match result {
+                Ok(Message::Text(text)) => text,
+                Ok(_) => continue,
+                Err(_) => {
+                    println!(
+                        "[ws] recv error for key: {} session: {}",
+                        key_clone2, session_id
+                    );
+                    break;
+                }
+            };
+
+            let reply = match serde_json::from_str::<ClientMessage>(&text) {
+                Ok(ClientMessage::Subscribe { filters }) => {
+                    let count = filters.filters.len();
+                    println!(
+                        "[ws] session {} subscribed with {} filters",
+                        session_id, count
+                    );
+                    let _ = filters_tx.send(Some(filters));
+                    serde_json::json!({ "type": "subscribed", "filters": count })
+                }
+                Ok(ClientMessage::Unsubscribe) => {
+                    let _ = filters_tx.send(None);
+                    serde_json::json!({ "type": "unsubscribed" })
+                }
+                Err(e) => serde_json::json!({ "type": "error", "message": e.to_string() }),
+            };
+            let _ = reply_tx.send(reply.to_string()).await;
         }
         println!(
             "[ws] recv stream ended for key: {} session: {}",
@@ -398,309 +1098,38 @@ async fn handle_socket(socket: WebSocket, state: SharedState, key: String, sessi
         );
     });
 
-    // Wait for either task to complete
+    // Wait for either task to complete, then stop the other: a half-open
+    // socket would otherwise keep its read half forever
     tokio::select! {
-        _ = send_task => {
+        _ = &mut send_task => {
             println!("[ws] send task completed for key: {} session: {}", key, session_id);
+            recv_task.abort();
+            let _ = recv_task.await;
         }
-        _ = recv_task => {
+        _ = &mut recv_task => {
             println!("[ws] recv task completed for key: {} session: {}", key, session_id);
+            send_task.abort();
+            let _ = send_task.await;
         }
     }
 
-    // FIXED: Cleanup only if this session is still the active one
-    let mut connections = state.active_connections.lock().await;
-    if let Some(&current_session_id) = connections.get(&key) {
-        if current_session_id == session_id {
-            connections.remove(&key);
-            println!(
-                "[ws] cleaned up session {} for key: {} | remaining connections: {}",
-                session_id,
-                key,
-                connections.len()
-            );
-        } else {
-            println!(
-                "[ws] NOT cleaning up session {} for key: {} (current session is {})",
-                session_id, key, current_session_id
-            );
-        }
-    } else {
-        println!(
-            "[ws] session {} for key: {} already removed | remaining connections: {}",
-            session_id,
-            key,
-            connections.len()
-        );
-    }
+    // The send task held the session handle, dropping it freed the seat
+    println!(
+        "[ws] cleaned up session {} for key: {} | remaining connections: {}",
+        session_id,
+        key,
+        state.sessions.len()
+    );
 }
 
 // --- LOGIC HELPERS ---
 
-impl CommunityCache {
-    fn has_community(&self, community_id: &str) -> bool {
-        self.community_ids.contains_key(community_id)
-    }
-
-    fn insert_community(&mut self, community_id: &str) {
-        self.community_ids.insert(community_id.to_string(), ());
-    }
-}
-
-impl TokenCache {
-    fn check_duplicate(
-        &self,
-        image: Option<&str>,
-        ipfs: Option<&str>,
-        description: Option<&str>,
-        name: Option<&str>,
-        ticker: Option<&str>,
-    ) -> bool {
-        // Match SQL logic: EXISTS if ANY condition is true
-
-        // Check: image exists
-        if let Some(img) = image {
-            if self.images.contains_key(img) {
-                return true;
-            }
-        }
-
-        // Check: ipfs exists
-        if let Some(ipfs_val) = ipfs {
-            if self.ipfs.contains_key(ipfs_val) {
-                return true;
-            }
-        }
-
-        // Check: description + name pair exists
-        if let (Some(desc), Some(n)) = (description, name) {
-            if self
-                .desc_name_pairs
-                .contains_key(&(desc.to_string(), n.to_string()))
-            {
-                return true;
-            }
-        }
-
-        // Check: description + ticker pair exists
-        if let (Some(desc), Some(t)) = (description, ticker) {
-            if self
-                .desc_ticker_pairs
-                .contains_key(&(desc.to_string(), t.to_string()))
-            {
-                return true;
-            }
-        }
-
-        // Check: name + ticker pair exists
-        if let (Some(n), Some(t)) = (name, ticker) {
-            if self
-                .name_ticker_pairs
-                .contains_key(&(n.to_string(), t.to_string()))
-            {
-                return true;
-            }
-        }
-
-        // Check: name exists (standalone)
-        if let Some(n) = name {
-            if self.names.contains_key(n) {
-                return true;
-            }
-        }
-
-        false
-    }
-
-    fn insert_token(
-        &mut self,
-        image: Option<&str>,
-        ipfs: Option<&str>,
-        description: Option<&str>,
-        name: Option<&str>,
-        ticker: Option<&str>,
-    ) {
-        if let Some(img) = image {
-            self.images.insert(img.to_string(), ());
-        }
-        if let Some(ipfs_val) = ipfs {
-            self.ipfs.insert(ipfs_val.to_string(), ());
-        }
-        if let Some(desc) = description {
-            self.descriptions.insert(desc.to_string(), ());
-            if let Some(n) = name {
-                self.desc_name_pairs
-                    .insert((desc.to_string(), n.to_string()), ());
-            }
-            if let Some(t) = ticker {
-                self.desc_ticker_pairs
-                    .insert((desc.to_string(), t.to_string()), ());
-            }
-        }
-        if let Some(n) = name {
-            self.names.insert(n.to_string(), ());
-            if let Some(t) = ticker {
-                self.name_ticker_pairs
-                    .insert((n.to_string(), t.to_string()), ());
-            }
-        }
-        if let Some(t) = ticker {
-            self.tickers.insert(t.to_string(), ());
-        }
-    }
-}
-
-async fn process_fast_create(
-    data: CreateEvent,
-    twitter_key: &str,
-    time: Duration,
-    database: Arc<Database>,
-    cache: Arc<Mutex<TokenCache>>,
-    comm_cache: Arc<Mutex<CommunityCache>>,
-    ipfs_local_node: &str,
-) -> Option<Token> {
-    let now = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap();
-
-    // OPTIMIZATION: Run metadata fetch and DB check concurrently
-    let t0 = Instant::now();
-
-    let metadata_fut = async {
-        let t = Instant::now();
-        let res = get_metadata(ipfs_local_node, &data.uri).await;
-        (res, t.elapsed())
-    };
-
-    let db_check_fut = database.token_any_exists(
-        Some(&data.name),
-        Some(&data.symbol),
-        Some(&data.uri),
-        None,
-        None,
-    );
-
-    let ((metadata_res, meta_time), token_exists) = tokio::join!(metadata_fut, db_check_fut);
-
-    let metadata = match metadata_res {
-        Ok(m) => m,
-        Err(err) => {
-            // Fast cache check even without metadata
-            let cache_guard = cache.lock().await;
-            if cache_guard.check_duplicate(
-                None,
-                Some(&data.uri),
-                None,
-                Some(&data.name),
-                Some(&data.symbol),
-            ) {
-                return None;
-            }
-            drop(cache_guard);
-
-            let token = Token::fresh(
-                data.name.clone(),
-                data.symbol.clone(),
-                data.user,
-                data.bonding_curve,
-                None,
-                data.mint.clone(),
-                data.token_2022,
-                Some(data.uri.clone()),
-                None,
-            );
-            pretty_token_log(&token, None, meta_time, None, t0.elapsed());
-            return Some(token);
-        }
-    };
-
-    // OPTIMIZATION: Check cache first (fast path)
-    {
-        let cache_guard = cache.lock().await;
-        if cache_guard.check_duplicate(
-            metadata.image.as_deref(),
-            Some(&data.uri),
-            metadata.description.as_deref(),
-            Some(&data.name),
-            Some(&data.symbol),
-        ) {
-            return None;
-        }
-    }
-
-    if token_exists.unwrap_or(false) {
-        return None;
-    }
-
-    // OPTIMIZATION: Early timeout check to prevent slow tokens from broadcasting
-    if now.saturating_sub(time) > Duration::from_secs(5) {
-        println!(
-            "[break early {} took more than 5 seconds to load]",
-            &data.mint
-        );
-        return None;
-    }
-
-    let (community, twitter_time) = if let Some(tw) = &metadata.twitter {
-        if let Some(id) = parse_community_id(tw) {
-            let t = Instant::now();
-            let res = get_community_by_id(twitter_key, &id).await.ok();
-            (res, Some(t.elapsed()))
-        } else {
-            (None, None)
-        }
-    } else {
-        (None, None)
-    };
-
-    let dev_perf = if let Some(ref c) = community {
-        median_dev_mcap_excluding(&database, c.creator.id.clone(), &data.mint.to_string()).await
-    } else {
-        None
-    };
-
-    let mut token = Token::fresh(
-        data.name,
-        data.symbol,
-        data.user,
-        data.bonding_curve,
-        community.clone(),
-        data.mint.clone(),
-        data.token_2022,
-        Some(data.uri.clone()),
-        Some(metadata.clone()),
-    );
-
-    if let Some((avg, last, cnt)) = dev_perf {
-        token.dev_performance = Some(DevPerformance {
-            average_ath: avg,
-            last_tokens: last,
-            count: cnt,
-        });
-    }
-
-    // Add to cache after successful creation
-    {
-        let mut cache_guard = cache.lock().await;
-        cache_guard.insert_token(
-            metadata.image.as_deref(),
-            Some(&data.uri),
-            metadata.description.as_deref(),
-            Some(&token.name),
-            Some(&token.ticker),
-        );
-    }
-
-    let total_time = t0.elapsed();
-
-    pretty_token_log(&token, Some(&metadata), meta_time, twitter_time, total_time);
-
-    Some(token)
-}
-
 async fn process_slow_create(
     data: CreateEvent,
+    slot: Option<u64>,
+    signature: Option<Signature>,
     database: Arc<Database>,
+    bundler: &Bundler,
     twitter_key: &str,
     cache: Arc<Mutex<TokenCache>>,
     comm_cache: Arc<Mutex<CommunityCache>>,
@@ -747,7 +1176,8 @@ async fn process_slow_create(
         data.token_2022,
         Some(data.uri.clone()),
         Some(metadata.clone()),
-    );
+    )
+    .with_origin(slot, signature);
 
     if let Some(tw) = metadata.twitter {
         if let Some(id) = parse_community_id(&tw) {
@@ -776,15 +1206,13 @@ async fn process_slow_create(
                     comm_cache_guard.insert_community(&id);
                 }
 
-                let _ = database
-                    .add_token(
-                        &token.mint,
-                        &token.dbtoken(token.mint.clone()),
-                        comm.creator.id.clone(),
-                    )
-                    .await;
+                bundler.add_token(
+                    token.mint,
+                    token.dbtoken(token.mint),
+                    comm.creator.id.clone(),
+                );
 
-                // Add to token cache after successful DB insert
+                // Written with the next batch, the cache covers it until then
                 let mut cache_guard = cache.lock().await;
                 cache_guard.insert_token(
                     metadata.image.as_deref(),
@@ -800,181 +1228,495 @@ async fn process_slow_create(
     Some(())
 }
 
-pub async fn median_dev_mcap_excluding(
-    db: &Database,
-    dev_address: String,
-    exclude_mint: &str,
-) -> Option<(u64, Vec<DbToken>, usize)> {
-    let (median, count) = db
-        .get_dev_median_ath_excluding(&dev_address, exclude_mint)
-        .await
-        .ok()??;
-    let last_three = db
-        .get_last_tokens_by_dev_excluding(&dev_address, exclude_mint, 3)
-        .await
-        .ok()?;
-    Some((median as u64, last_three, count))
+/// Logs parse failure counters of the given streams once a minute.
+fn spawn_parse_stats_log(streams: Vec<(String, Arc<ParseStats>)>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            for (name, stats) in &streams {
+                let counts = stats.snapshot();
+                println!(
+                    "[parse] {}: base64 {} | short {} | unknown {} | entries {} | borsh failed {:?}",
+                    name,
+                    counts.base64,
+                    counts.too_short,
+                    counts.unknown_discriminator,
+                    counts.entries,
+                    counts.borsh_failed
+                );
+            }
+        }
+    });
+}
+
+fn spawn_dispatch_stats_log(dispatchers: Vec<(String, Arc<DispatchStats>)>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            for (name, stats) in &dispatchers {
+                println!(
+                    "[dispatch] {}: dispatched {} | shed {}",
+                    name,
+                    stats.dispatched(),
+                    stats.shed()
+                );
+            }
+        }
+    });
 }
 
 // --- ADMIN HANDLERS ---
 
-#[derive(Deserialize)]
-struct AddUserReq {
-    admin_key: String,
-    payload: AddUserPayload,
+/// Minimum role of an admin route, as a type so routes state it in their
+/// signature.
+trait MinRole {
+    const ROLE: Role;
+}
+
+mod require {
+    use super::{MinRole, Role};
+
+    pub struct Viewer;
+    pub struct Operator;
+    pub struct Admin;
+
+    impl MinRole for Viewer {
+        const ROLE: Role = Role::Viewer;
+    }
+
+    impl MinRole for Operator {
+        const ROLE: Role = Role::Operator;
+    }
+
+    impl MinRole for Admin {
+        const ROLE: Role = Role::Admin;
+    }
+}
+
+/// Caller of an admin route, authenticated by `Authorization: Bearer <key>`
+/// and holding at least the role `R` stands for.
+struct Auth<R> {
+    user: User,
+    ip: Option<IpAddr>,
+    _role: PhantomData<fn() -> R>,
+}
+
+impl<R> Auth<R> {
+    /// An audit entry for `action` done by this caller.
+    fn event(&self, action: &str) -> AuditEvent {
+        AuditEvent::new(action)
+            .with_user(&self.user)
+            .with_ip(self.ip)
+    }
+}
+
+#[async_trait]
+impl<R: MinRole> FromRequestParts<SharedState> for Auth<R> {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &SharedState,
+    ) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let route = parts.uri.path().to_string();
+
+        let key = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        let limit_keys = limit_keys(ip, key);
+        if let Err(limited) = state.limiter.check(&limit_keys) {
+            println!(
+                "[admin] rate limited: {} on {}, retry in {}s",
+                limit_keys
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                route,
+                limited.retry_after_secs()
+            );
+            return Err(too_many_requests(limited));
+        }
+
+        let Some(key) = key else {
+            let event = AuditEvent::new("admin_login_failed")
+                .with_target(route)
+                .with_details("missing bearer token")
+                .with_ip(ip);
+            audit(state, event).await;
+            return Err(unauthorized("missing bearer token"));
+        };
+
+        let user = match state.db.find_user_by_key(key).await {
+            Ok(Some(user)) => user,
+            Ok(None) => {
+                println!("[admin] rejected key {}...", key_prefix(key));
+                login_failed(state, &limit_keys, ip).await;
+                let event = AuditEvent::new("admin_login_failed")
+                    .with_actor(key_prefix(key))
+                    .with_target(route)
+                    .with_details("invalid key")
+                    .with_ip(ip);
+                audit(state, event).await;
+                return Err(unauthorized("invalid key"));
+            }
+            Err(err) => return Err(db_error(err)),
+        };
+        state.limiter.success(&limit_keys);
+
+        match user.role {
+            Some(role) if role >= R::ROLE => Ok(Self {
+                user,
+                ip,
+                _role: PhantomData,
+            }),
+            _ => {
+                println!(
+                    "[admin] key {}... lacks the {} role",
+                    user.key_prefix,
+                    R::ROLE
+                );
+                let event = AuditEvent::new("admin_forbidden")
+                    .with_user(&user)
+                    .with_target(route)
+                    .with_details(format!("requires the {} role", R::ROLE))
+                    .with_ip(ip);
+                audit(state, event).await;
+                Err((
+                    StatusCode::FORBIDDEN,
+                    Json(serde_json::json!({
+                        "error": format!("requires the {} role", R::ROLE)
+                    })),
+                )
+                    .into_response())
+            }
+        }
+    }
+}
+
+/// Writes an audit entry. A failure is logged, it never fails the request.
+async fn audit(state: &SharedState, event: AuditEvent) {
+    if let Err(err) = state.db.record_audit(event).await {
+        eprintln!("[audit] failed to record: {}", err);
+    }
+}
+
+/// What a login attempt is limited by: its address and the prefix of its key
+/// from that address.
+fn limit_keys(ip: Option<IpAddr>, key: Option<&str>) -> Vec<LimitKey> {
+    let Some(ip) = ip else {
+        return vec![];
+    };
+    std::iter::once(LimitKey::Ip(ip))
+        .chain(key.map(|key| LimitKey::Key(ip, key_prefix(key).to_string())))
+        .collect()
+}
+
+/// Counts a failed key, and records the bans it leads to.
+async fn login_failed(state: &SharedState, keys: &[LimitKey], ip: Option<IpAddr>) {
+    for banned in state.limiter.failure(keys) {
+        let ban = state.limiter.config().ban;
+        println!("[limiter] banned {} for {}s", banned, ban.as_secs());
+        let event = AuditEvent::new("login_banned")
+            .with_target(banned.to_string())
+            .with_details(format!("{}s after repeated failed keys", ban.as_secs()))
+            .with_ip(ip);
+        audit(state, event).await;
+    }
+}
+
+fn too_many_requests(limited: Limited) -> Response {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        [(RETRY_AFTER, limited.retry_after_secs().to_string())],
+        Json(serde_json::json!({ "error": "too many attempts" })),
+    )
+        .into_response()
+}
+
+fn unauthorized(message: &str) -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(WWW_AUTHENTICATE, "Bearer")],
+        Json(serde_json::json!({ "error": message })),
+    )
+        .into_response()
+}
+
+fn db_error(err: sqlx::Error) -> Response {
+    let (status, message) = match err {
+        sqlx::Error::RowNotFound => (StatusCode::NOT_FOUND, "not found".to_string()),
+        // Validation failures of the request
+        sqlx::Error::Protocol(message) => (StatusCode::BAD_REQUEST, message),
+        err => {
+            eprintln!("[admin] database error: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "database error".to_string(),
+            )
+        }
+    };
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+fn success() -> Response {
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({"status": "success"})),
+    )
+        .into_response()
 }
 
 #[derive(Deserialize)]
 struct RemoveUserReq {
-    admin_key: String,
     user_id: i32,
 }
 
 #[derive(Deserialize)]
-struct GetUsersReq {
-    admin_key: String,
+struct DisableUserReq {
+    user_id: i32,
+    disabled: bool,
+}
+
+#[derive(Deserialize)]
+struct RotateKeyReq {
+    user_id: i32,
+}
+
+#[derive(Deserialize)]
+struct DisconnectSessionReq {
+    session_id: u64,
+}
+
+#[derive(Deserialize)]
+struct AuditQuery {
+    /// Only entries older than this id, from `next_before` of the previous page
+    before: Option<i64>,
+    limit: Option<i64>,
 }
 
+const AUDIT_PAGE: i64 = 50;
+const AUDIT_MAX_PAGE: i64 = 200;
+
 async fn add_user_handler(
+    auth: Auth<require::Admin>,
     AxState(state): AxState<SharedState>,
-    Json(req): Json<AddUserReq>,
-) -> impl IntoResponse {
-    match state.db.add_user(&req.admin_key, req.payload).await {
-        Ok(_) => (
-            StatusCode::OK,
-            Json(serde_json::json!({"status": "success"})),
-        ),
-        _ => (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "unauthorized"})),
-        ),
+    Json(payload): Json<AddUserPayload>,
+) -> Response {
+    // Summary of the new user, without the key itself
+    let event = auth
+        .event("add_user")
+        .with_target(key_prefix(&payload.provided_key))
+        .with_details(format!(
+            "hint={} role={} max_sessions={} autobuy={} expires_at={}",
+            payload.hint,
+            payload.role.map_or("none", |role| role.as_str()),
+            payload.max_sessions,
+            payload.autobuy,
+            payload
+                .expires_at
+                .map_or("never".to_string(), |at| at.to_string()),
+        ));
+
+    match state.db.add_user(payload).await {
+        Ok(()) => {
+            println!("[admin] user added by key {}...", auth.user.key_prefix);
+            audit(&state, event).await;
+            success()
+        }
+        Err(err) => db_error(err),
     }
 }
 
 async fn remove_user_handler(
+    auth: Auth<require::Admin>,
     AxState(state): AxState<SharedState>,
     Json(req): Json<RemoveUserReq>,
-) -> impl IntoResponse {
-    let target_key = state.db.get_key_by_id(req.user_id).await.ok();
+) -> Response {
+    if let Err(err) = state.db.remove_user(req.user_id).await {
+        return db_error(err);
+    }
+    println!(
+        "[admin] revoking access for user {} by key {}...",
+        req.user_id, auth.user.key_prefix
+    );
 
-    if state
-        .db
-        .remove_user(&req.admin_key, req.user_id)
-        .await
-        .is_ok()
-    {
-        if let Some(key) = target_key {
-            println!("[admin] revoking access for key: {}", key);
+    let closed = state.sessions.close_user(req.user_id, "access revoked");
+    if closed > 0 {
+        println!("[admin] closed {} sessions of user {}", closed, req.user_id);
+    }
+    let event = auth
+        .event("remove_user")
+        .with_target(format!("user {}", req.user_id))
+        .with_details(format!("closed {} sessions", closed));
+    audit(&state, event).await;
+    success()
+}
 
-            // Just remove from map - the connection will die naturally when it tries to send
-            let mut connections = state.active_connections.lock().await;
-            if connections.remove(&key).is_some() {
-                println!("[admin] removed key {} from active connections", key);
-            }
+async fn disable_user_handler(
+    auth: Auth<require::Admin>,
+    AxState(state): AxState<SharedState>,
+    Json(req): Json<DisableUserReq>,
+) -> Response {
+    if let Err(err) = state.db.set_user_disabled(req.user_id, req.disabled).await {
+        return db_error(err);
+    }
+
+    let event = if req.disabled {
+        let closed = state.sessions.close_user(req.user_id, "access disabled");
+        println!(
+            "[admin] disabled user {} by key {}..., closed {} sessions",
+            req.user_id, auth.user.key_prefix, closed
+        );
+        auth.event("disable_user")
+            .with_details(format!("closed {} sessions", closed))
+    } else {
+        println!(
+            "[admin] enabled user {} by key {}...",
+            req.user_id, auth.user.key_prefix
+        );
+        auth.event("enable_user")
+    };
+    audit(&state, event.with_target(format!("user {}", req.user_id))).await;
+    success()
+}
+
+async fn rotate_key_handler(
+    auth: Auth<require::Admin>,
+    AxState(state): AxState<SharedState>,
+    Json(req): Json<RotateKeyReq>,
+) -> Response {
+    match state.db.rotate_user_key(req.user_id).await {
+        Ok(key) => {
+            let closed = state.sessions.close_user(req.user_id, "key rotated");
+            println!(
+                "[admin] rotated key of user {} to {}... by key {}..., closed {} sessions",
+                req.user_id,
+                key_prefix(&key),
+                auth.user.key_prefix,
+                closed
+            );
+            let event = auth
+                .event("rotate_key")
+                .with_target(format!("user {}", req.user_id))
+                .with_details(format!(
+                    "new prefix {}, closed {} sessions",
+                    key_prefix(&key),
+                    closed
+                ));
+            audit(&state, event).await;
+            // The only time the new key leaves the server
+            (StatusCode::OK, Json(serde_json::json!({"key": key}))).into_response()
         }
-        return (
-            StatusCode::OK,
-            Json(serde_json::json!({"status": "success"})),
-        )
-            .into_response();
+        Err(err) => db_error(err),
     }
+}
 
-    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+async fn disconnect_session_handler(
+    auth: Auth<require::Operator>,
+    AxState(state): AxState<SharedState>,
+    Json(req): Json<DisconnectSessionReq>,
+) -> Response {
+    if state
+        .sessions
+        .close(req.session_id, "disconnected by an admin")
+    {
+        println!(
+            "[admin] disconnected session {} by key {}...",
+            req.session_id, auth.user.key_prefix
+        );
+        let event = auth
+            .event("disconnect_session")
+            .with_target(format!("session {}", req.session_id));
+        audit(&state, event).await;
+        success()
+    } else {
+        (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": "no such session"})),
+        )
+            .into_response()
+    }
 }
 
 async fn get_users_handler(
+    auth: Auth<require::Viewer>,
     AxState(state): AxState<SharedState>,
-    Json(req): Json<GetUsersReq>,
-) -> impl IntoResponse {
-    match state.db.fetch_all_users(&req.admin_key).await {
-        Ok(users) => (StatusCode::OK, Json(serde_json::to_value(users).unwrap())),
-        _ => (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "forbidden"})),
-        ),
+) -> Response {
+    audit(&state, auth.event("list_users")).await;
+    match state.db.fetch_all_users().await {
+        Ok(users) => (StatusCode::OK, Json(serde_json::to_value(users).unwrap())).into_response(),
+        Err(err) => db_error(err),
     }
-    .into_response()
 }
 
-async fn get_connections_handler(AxState(state): AxState<SharedState>) -> impl IntoResponse {
-    let connections = state.active_connections.lock().await;
-    let conn_list: Vec<ConnectionStatus> = connections
-        .iter()
-        .map(|(key, session_id)| ConnectionStatus {
-            key: key.clone(),
-            session_id: *session_id,
-        })
-        .collect();
+async fn get_connections_handler(
+    auth: Auth<require::Viewer>,
+    AxState(state): AxState<SharedState>,
+) -> impl IntoResponse {
+    audit(&state, auth.event("list_connections")).await;
+    let conn_list = state.sessions.snapshot();
 
     Json(serde_json::json!({
         "count": conn_list.len(),
-        "connections": conn_list
+        "connections": conn_list,
+        "upstreams": state.upstreams.snapshot(),
+        "limiter": state.limiter.snapshot()
     }))
 }
 
-fn pretty_token_log(
-    token: &Token,
-    metadata: Option<&Metadata>,
-    meta_time: Duration,
-    twitter_time: Option<Duration>,
-    total_time: Duration,
-) {
-    println!(
-        "\n [data: {}]new token
-├─ name:      {} ({})
-├─ mint:      {}
-├─ twitter:   {}
-├─ twitter_data: {:?}
-├─ dev_perf:  {}
-├─ token2022: {}
-├─ timing:
-│  ├─ metadata: {:>4} ms
-│  ├─ twitter:  {:>4} ms
-│  └─ total:    {:>4} ms",
-        metadata.is_some(),
-        token.name,
-        token.ticker,
-        token.mint,
-        token.twitter.is_some(),
-        metadata
-            .and_then(|m| m.twitter.as_ref())
-            .map(|s| s.as_str())
-            .unwrap_or("None"),
-        token.dev_performance.is_some(),
-        token.token_2022,
-        meta_time.as_millis(),
-        twitter_time.map(|t| t.as_millis()).unwrap_or(0),
-        total_time.as_millis(),
-    );
+/// Pages through the audit log, newest first. Reading it is not audited.
+async fn get_audit_handler(
+    _auth: Auth<require::Admin>,
+    AxState(state): AxState<SharedState>,
+    AxQuery(query): AxQuery<AuditQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(AUDIT_PAGE).clamp(1, AUDIT_MAX_PAGE);
+    match state.db.fetch_audit(query.before, limit).await {
+        Ok(entries) => {
+            // A full page may have more behind it
+            let next_before = if entries.len() as i64 == limit {
+                entries.last().map(|entry| entry.id)
+            } else {
+                None
+            };
+            Json(serde_json::json!({
+                "entries": entries,
+                "next_before": next_before
+            }))
+            .into_response()
+        }
+        Err(err) => db_error(err),
+    }
 }
 
 async fn restart_handler(
+    auth: Auth<require::Operator>,
     AxState(state): AxState<SharedState>,
-    Json(req): Json<RestartReq>,
 ) -> impl IntoResponse {
-    match state.db.fetch_all_users(&req.admin_key).await {
-        Ok(_) => {
-            println!(
-                "[admin] restart triggered by admin key ending in ...{}",
-                &req.admin_key[req.admin_key.len().saturating_sub(4)..]
-            );
-
-            // Send the signal to shutdown
-            let _ = state.shutdown_tx.send(()).await;
-
-            (
-                StatusCode::OK,
-                Json(serde_json::json!({
-                    "status": "restarting",
-                    "message": "Server is shutting down for systemd restart"
-                })),
-            )
-                .into_response() // <--- Added .into_response() here
-        }
-        Err(_) => (
-            StatusCode::FORBIDDEN,
-            Json(serde_json::json!({"error": "unauthorized"})),
-        )
-            .into_response(), // <--- Both arms now return Response<Body>
-    }
+    println!(
+        "[admin] restart triggered by key {}...",
+        auth.user.key_prefix
+    );
+    audit(&state, auth.event("restart")).await;
+
+    // Send the signal to shutdown
+    let _ = state.shutdown_tx.send(()).await;
+
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "status": "restarting",
+            "message": "Server is shutting down for systemd restart"
+        })),
+    )
 }
@@ -0,0 +1,116 @@
+use sqlx::{Executor, PgPool};
+
+/// One step of the schema, applied once and recorded in `schema_version`.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: i32,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+/// Every migration this binary knows, oldest first. Versions only ever get
+/// appended, an applied migration is never edited.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        sql: include_str!("../migrations/0001_baseline.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "lookup_indexes",
+        sql: include_str!("../migrations/0002_lookup_indexes.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "trades",
+        sql: include_str!("../migrations/0003_trades.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "dev_activity",
+        sql: include_str!("../migrations/0004_dev_activity.sql"),
+    },
+];
+
+// Held while migrating so two servers starting together don't both apply
+const LOCK_ID: i64 = 0x746f6b656e6972;
+
+/// The database was migrated by a newer binary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaAhead {
+    pub database: i32,
+    pub binary: i32,
+}
+
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Migrations to apply to a database at `current`, zero when it has none.
+pub fn pending(current: i32) -> Result<&'static [Migration], SchemaAhead> {
+    if current > latest_version() {
+        return Err(SchemaAhead {
+            database: current,
+            binary: latest_version(),
+        });
+    }
+    let first = MIGRATIONS.partition_point(|migration| migration.version <= current);
+    Ok(&MIGRATIONS[first..])
+}
+
+/// Brings the schema up to `latest_version` in one transaction. Fails
+/// without touching anything if the schema is ahead.
+pub async fn run(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(LOCK_ID)
+        .execute(&mut *tx)
+        .await?;
+
+    // Under the lock, concurrent `CREATE TABLE IF NOT EXISTS` can still collide
+    (&mut *tx)
+        .execute(
+            r#"
+            CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at BIGINT NOT NULL DEFAULT EXTRACT(EPOCH FROM NOW())::BIGINT
+            );
+            "#,
+        )
+        .await?;
+
+    let (current,): (i32,) = sqlx::query_as("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let pending = pending(current).map_err(|ahead| {
+        sqlx::Error::Protocol(format!(
+            "database schema is at version {} but this binary only knows up to {}, \
+             refusing to start",
+            ahead.database, ahead.binary
+        ))
+    })?;
+
+    for migration in pending {
+        println!(
+            "[db] applying migration {} ({})",
+            migration.version, migration.name
+        );
+        // Plain query protocol, migrations hold several statements
+        (&mut *tx).execute(migration.sql).await?;
+        sqlx::query("INSERT INTO schema_version (version, name) VALUES ($1, $2)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    if pending.is_empty() {
+        println!("[db] schema at version {}", current);
+    }
+
+    Ok(())
+}
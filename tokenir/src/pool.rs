@@ -95,6 +95,8 @@ impl TokenPool {
         average_ath: u64,
         last_tokens: Vec<DbToken>,
         count: usize,
+        migrated: usize,
+        rug_rate: u64,
     ) {
         let Some(token) = self.pool.get_mut(mint) else {
             return;
@@ -104,6 +106,8 @@ impl TokenPool {
             last_tokens,
             average_ath,
             count,
+            migrated,
+            rug_rate,
         });
     }
 
@@ -0,0 +1,185 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use std::time::{Duration, Instant};
+
+use crate::{constans::helper::pool_pda, logs::Event};
+
+/// When a dev selling counts as a rug.
+#[derive(Debug, Clone, Copy)]
+pub struct RugConfig {
+    /// Share of the tokens the dev bought, 0..100, sold before it is a dump
+    pub sell_percentage: u64,
+    /// Seconds after creation a dump still counts as a rug
+    pub window: i64,
+    /// Tokens without dev trades for this long are no longer watched
+    pub idle_after: Duration,
+}
+
+impl Default for RugConfig {
+    fn default() -> Self {
+        Self {
+            sell_percentage: 50,
+            window: 300,
+            idle_after: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// Curve trades of a token's dev wallet.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DevActivity {
+    /// Lamports
+    pub bought_sol: u64,
+    /// Lamports
+    pub sold_sol: u64,
+    pub bought_tokens: u64,
+    pub sold_tokens: u64,
+    /// Unix seconds of the first dev sell
+    pub first_sell_at: Option<i64>,
+    /// Unix seconds of the sell that made it a rug
+    pub rugged_at: Option<i64>,
+}
+
+impl DevActivity {
+    /// Share of the bought tokens the dev sold, 0..100. Selling tokens that
+    /// were never bought on the curve counts as selling everything.
+    pub fn sold_percentage(&self) -> u64 {
+        if self.bought_tokens == 0 {
+            return if self.sold_tokens > 0 { 100 } else { 0 };
+        }
+        (self.sold_tokens as u128 * 100 / self.bought_tokens as u128).min(100) as u64
+    }
+}
+
+struct Watched {
+    dev: Pubkey,
+    created_at: i64,
+    activity: DevActivity,
+    last_trade: Instant,
+}
+
+/// Dev wallets of tokens created while the server is up, and what they did
+/// on their own curve.
+pub struct DevTracker {
+    // Keyed by pool address, that's what the curve trades carry
+    tokens: DashMap<Pubkey, Watched>,
+    config: RugConfig,
+}
+
+impl Default for DevTracker {
+    fn default() -> Self {
+        Self::new(RugConfig::default())
+    }
+}
+
+impl DevTracker {
+    pub fn new(config: RugConfig) -> Self {
+        Self {
+            tokens: DashMap::new(),
+            config,
+        }
+    }
+
+    pub fn config(&self) -> &RugConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn watch(&self, mint: &Pubkey, dev: Pubkey, created_at: i64) {
+        self.tokens.insert(
+            pool_pda(mint).0,
+            Watched {
+                dev,
+                created_at,
+                activity: DevActivity::default(),
+                last_trade: Instant::now(),
+            },
+        );
+    }
+
+    /// `trade` for curve buys and sells, with the pool the totals belong to.
+    /// Trades estimated from the shredstream are left out, they may never land.
+    pub fn record(&self, event: &Event) -> Option<(Pubkey, DevActivity)> {
+        let activity = match event {
+            Event::Buy(data) if !data.estimated => self.trade(
+                &data.mint,
+                &data.user,
+                true,
+                data.sol_amount,
+                data.token_amount,
+                data.timestamp,
+            ),
+            Event::Sell(data) if !data.estimated => self.trade(
+                &data.mint,
+                &data.user,
+                false,
+                data.sol_amount,
+                data.token_amount,
+                data.timestamp,
+            ),
+            _ => None,
+        }?;
+        Some((*event.mint(), activity))
+    }
+
+    /// Records a curve trade. Returns the dev's totals when the trade was
+    /// theirs, None for everyone else and for unwatched tokens.
+    pub fn trade(
+        &self,
+        pool: &Pubkey,
+        user: &Pubkey,
+        is_buy: bool,
+        sol_amount: u64,
+        token_amount: u64,
+        timestamp: i64,
+    ) -> Option<DevActivity> {
+        let mut watched = self.tokens.get_mut(pool)?;
+        if watched.dev != *user {
+            return None;
+        }
+        watched.last_trade = Instant::now();
+        let created_at = watched.created_at;
+
+        let activity = &mut watched.activity;
+        if is_buy {
+            activity.bought_sol = activity.bought_sol.saturating_add(sol_amount);
+            activity.bought_tokens = activity.bought_tokens.saturating_add(token_amount);
+            return Some(activity.clone());
+        }
+
+        activity.sold_sol = activity.sold_sol.saturating_add(sol_amount);
+        activity.sold_tokens = activity.sold_tokens.saturating_add(token_amount);
+        activity.first_sell_at.get_or_insert(timestamp);
+
+        if activity.rugged_at.is_none()
+            && timestamp - created_at <= self.config.window
+            && activity.sold_tokens as u128 * 100
+                > activity.bought_tokens as u128 * self.config.sell_percentage as u128
+        {
+            activity.rugged_at = Some(timestamp);
+            println!(
+                "[rug] dev {} sold {}% of pool {} within {}s",
+                user,
+                activity.sold_percentage(),
+                pool,
+                timestamp - created_at
+            );
+        }
+
+        Some(activity.clone())
+    }
+
+    /// Stops watching tokens whose dev has been quiet for `idle_after`.
+    pub fn prune(&self) {
+        self.tokens
+            .retain(|_, watched| watched.last_trade.elapsed() < self.config.idle_after);
+    }
+}
@@ -0,0 +1,228 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::watch;
+
+use crate::fetcher::since_epoch;
+
+/// What happens to a new session when its user already has `max_sessions` open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    /// Refuse the new session
+    #[default]
+    RejectNew,
+    /// Close the user's oldest sessions to make room
+    KickOldest,
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject_new" => Ok(Self::RejectNew),
+            "kick_oldest" => Ok(Self::KickOldest),
+            other => Err(format!("unknown eviction policy: {}", other)),
+        }
+    }
+}
+
+/// The user is at its session limit and the policy is `RejectNew`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionLimit {
+    pub max: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub user_id: i32,
+    /// Clear part of the key the session logged in with
+    pub key_prefix: String,
+    pub session_id: u64,
+    /// Unix milliseconds
+    pub started_at: u64,
+}
+
+/// Held by the socket task of a session, tells it when it was closed from
+/// outside: kicked for a newer session, revoked, or disconnected by an admin.
+/// Dropping it frees the seat, also when the socket never came up.
+pub struct SessionHandle {
+    pub id: u64,
+    user_id: i32,
+    closed: watch::Receiver<Option<String>>,
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+impl Drop for SessionHandle {
+    fn drop(&mut self) {
+        remove_session(&self.sessions, self.user_id, self.id);
+    }
+}
+
+impl SessionHandle {
+    /// Resolves with the reason once the registry closes this session.
+    pub async fn closed(&mut self) -> String {
+        loop {
+            if let Some(reason) = self.closed.borrow_and_update().clone() {
+                return reason;
+            }
+            if self.closed.changed().await.is_err() {
+                // Forgotten without a reason, nothing will ever come
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+struct Entry {
+    id: u64,
+    key_prefix: String,
+    started_at: u64,
+    close: watch::Sender<Option<String>>,
+}
+
+impl Entry {
+    fn close(&self, reason: &str) {
+        let _ = self.close.send(Some(reason.to_string()));
+    }
+}
+
+type Sessions = HashMap<i32, Vec<Entry>>;
+
+/// Open client sessions by user, oldest first.
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: Arc<Mutex<Sessions>>,
+    next_id: AtomicU64,
+}
+
+impl SessionRegistry {
+    /// Registers a new session of `user_id`, who may have at most `max` open.
+    pub fn open(
+        &self,
+        user_id: i32,
+        key_prefix: &str,
+        max: usize,
+        policy: EvictionPolicy,
+    ) -> Result<SessionHandle, SessionLimit> {
+        if max == 0 {
+            return Err(SessionLimit { max });
+        }
+
+        let mut sessions = self.sessions.lock().unwrap();
+        let open = sessions.entry(user_id).or_default();
+
+        if open.len() >= max {
+            match policy {
+                EvictionPolicy::RejectNew => return Err(SessionLimit { max }),
+                EvictionPolicy::KickOldest => {
+                    let excess = open.len() + 1 - max;
+                    for entry in open.drain(..excess) {
+                        println!("[sessions] kicking session {} to make room", entry.id);
+                        entry.close("replaced by a newer session");
+                    }
+                }
+            }
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (close, closed) = watch::channel(None);
+        open.push(Entry {
+            id,
+            key_prefix: key_prefix.to_string(),
+            started_at: since_epoch().as_millis() as u64,
+            close,
+        });
+
+        Ok(SessionHandle {
+            id,
+            user_id,
+            closed,
+            sessions: self.sessions.clone(),
+        })
+    }
+
+    /// Forgets a session that ended. False if it was already gone.
+    pub fn remove(&self, user_id: i32, id: u64) -> bool {
+        remove_session(&self.sessions, user_id, id)
+    }
+
+    /// Closes one session with `reason`. False if there is none with that id.
+    pub fn close(&self, id: u64, reason: &str) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some((user_id, position)) = sessions.iter().find_map(|(user_id, open)| {
+            let position = open.iter().position(|entry| entry.id == id)?;
+            Some((*user_id, position))
+        }) else {
+            return false;
+        };
+
+        let open = sessions.get_mut(&user_id).unwrap();
+        open.remove(position).close(reason);
+        if open.is_empty() {
+            sessions.remove(&user_id);
+        }
+        true
+    }
+
+    /// Closes every session of a user with `reason`, returns how many there were.
+    pub fn close_user(&self, user_id: i32, reason: &str) -> usize {
+        let open = self
+            .sessions
+            .lock()
+            .unwrap()
+            .remove(&user_id)
+            .unwrap_or_default();
+        for entry in &open {
+            entry.close(reason);
+        }
+        open.len()
+    }
+
+    /// Open sessions over all users.
+    pub fn len(&self) -> usize {
+        self.sessions.lock().unwrap().values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn snapshot(&self) -> Vec<SessionInfo> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|(user_id, open)| {
+                open.iter().map(|entry| SessionInfo {
+                    user_id: *user_id,
+                    key_prefix: entry.key_prefix.clone(),
+                    session_id: entry.id,
+                    started_at: entry.started_at,
+                })
+            })
+            .collect()
+    }
+}
+
+fn remove_session(sessions: &Mutex<Sessions>, user_id: i32, id: u64) -> bool {
+    let mut sessions = sessions.lock().unwrap();
+    let Some(open) = sessions.get_mut(&user_id) else {
+        return false;
+    };
+
+    let before = open.len();
+    open.retain(|entry| entry.id != id);
+    let removed = open.len() < before;
+    if open.is_empty() {
+        sessions.remove(&user_id);
+    }
+    removed
+}
@@ -0,0 +1,556 @@
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use futures::future::BoxFuture;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    hash::Hash,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    sync::mpsc,
+};
+
+use crate::{
+    amm::AmmPoolCache,
+    capture::{CaptureRecord, CaptureWriter},
+    fetcher::Decoder,
+    health::HealthRegistry,
+    logs::Event,
+    lookup::LookupTableCache,
+    Client, ParseStats,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceKind {
+    Shredstream,
+    Logs,
+    PumpPortal,
+}
+
+/// An event together with where and when it was seen.
+#[derive(Debug)]
+pub struct EventEnvelope {
+    pub source: SourceKind,
+    pub slot: Option<u64>,
+    pub signature: Option<Signature>,
+    /// The transaction errored on chain. Only the logs source can tell, the
+    /// shredstream sees transactions before they execute.
+    pub failed: bool,
+    /// Top-level instruction of the transaction the event came from, keeps
+    /// several trades of one token in one transaction apart
+    pub instruction: Option<usize>,
+    /// Local receive time since the unix epoch
+    pub received: Duration,
+    /// Set by the [`Multiplexer`] on a landed trade it forwards after the
+    /// estimate of the same trade
+    pub replaces_estimate: bool,
+    pub event: Event,
+}
+
+/// Anything that can feed events into a [`Multiplexer`]. `run` reconnects on
+/// its own and only returns when the source gives up for good.
+pub trait EventSource: Send + 'static {
+    fn kind(&self) -> SourceKind;
+
+    fn run(self: Box<Self>, sink: mpsc::Sender<EventEnvelope>) -> BoxFuture<'static, ()>;
+}
+
+pub struct ShredSource {
+    client: Client,
+    jito_url: String,
+    tables: Arc<LookupTableCache>,
+}
+
+impl ShredSource {
+    pub fn new(jito_url: String, tables: Arc<LookupTableCache>) -> Self {
+        Self {
+            client: Client::new(jito_url.clone()),
+            jito_url,
+            tables,
+        }
+    }
+
+    pub fn with_capture(mut self, capture: Arc<CaptureWriter>) -> Self {
+        self.client = self.client.with_capture(capture);
+        self
+    }
+
+    pub fn with_health(mut self, registry: Arc<HealthRegistry>) -> Self {
+        self.client = self.client.with_health(registry, "shreds");
+        self
+    }
+
+    pub fn parse_stats(&self) -> Arc<ParseStats> {
+        self.client.parse_stats()
+    }
+}
+
+impl EventSource for ShredSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Shredstream
+    }
+
+    fn run(self: Box<Self>, sink: mpsc::Sender<EventEnvelope>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let _ = self
+                .client
+                .subscribe_jito(self.jito_url, self.tables, move |envelope| {
+                    let sink = sink.clone();
+                    async move {
+                        let _ = sink.send(envelope).await;
+                    }
+                })
+                .await;
+        })
+    }
+}
+
+pub struct LogsSource {
+    client: Client,
+    amm: bool,
+}
+
+impl LogsSource {
+    pub fn new(url: String, amm: bool) -> Self {
+        Self {
+            client: Client::new(url),
+            amm,
+        }
+    }
+
+    pub fn with_capture(mut self, capture: Arc<CaptureWriter>) -> Self {
+        self.client = self.client.with_capture(capture);
+        self
+    }
+
+    pub fn with_health(mut self, registry: Arc<HealthRegistry>) -> Self {
+        self.client = self.client.with_health(registry, "logs");
+        self
+    }
+
+    pub fn parse_stats(&self) -> Arc<ParseStats> {
+        self.client.parse_stats()
+    }
+}
+
+impl EventSource for LogsSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::Logs
+    }
+
+    fn run(self: Box<Self>, sink: mpsc::Sender<EventEnvelope>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let _ = self
+                .client
+                .subscribe_to_pump(
+                    move |envelope| {
+                        let sink = sink.clone();
+                        async move {
+                            let _ = sink.send(envelope).await;
+                        }
+                    },
+                    self.amm,
+                )
+                .await;
+        })
+    }
+}
+
+pub struct PumpPortalSource {
+    client: Client,
+}
+
+impl PumpPortalSource {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Client::new(url),
+        }
+    }
+
+    pub fn with_capture(mut self, capture: Arc<CaptureWriter>) -> Self {
+        self.client = self.client.with_capture(capture);
+        self
+    }
+
+    pub fn with_health(mut self, registry: Arc<HealthRegistry>) -> Self {
+        self.client = self.client.with_health(registry, "pumpportal");
+        self
+    }
+
+    pub fn parse_stats(&self) -> Arc<ParseStats> {
+        self.client.parse_stats()
+    }
+}
+
+impl EventSource for PumpPortalSource {
+    fn kind(&self) -> SourceKind {
+        SourceKind::PumpPortal
+    }
+
+    fn run(self: Box<Self>, sink: mpsc::Sender<EventEnvelope>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let _ = self
+                .client
+                .subscribe_new_tokens(move |envelope| {
+                    let sink = sink.clone();
+                    async move {
+                        let _ = sink.send(envelope).await;
+                    }
+                })
+                .await;
+        })
+    }
+}
+
+/// Feeds a capture file back through the decoder, as if the messages of one
+/// source were arriving live. Receive times are the replay's own, so the
+/// downstream latency checks behave like they would live.
+pub struct ReplaySource {
+    path: PathBuf,
+    kind: SourceKind,
+    speed: f64,
+    pools: Option<Arc<AmmPoolCache>>,
+    tables: Option<Arc<LookupTableCache>>,
+    stats: Arc<ParseStats>,
+}
+
+impl ReplaySource {
+    /// Replays the records of `kind` found in the capture at `path`.
+    pub fn new(path: impl Into<PathBuf>, kind: SourceKind) -> Self {
+        Self {
+            path: path.into(),
+            kind,
+            speed: 1.0,
+            pools: None,
+            tables: None,
+            stats: Arc::new(ParseStats::default()),
+        }
+    }
+
+    /// Playback speed relative to the capture, `0.0` replays without delays.
+    pub fn speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn with_amm_pools(mut self, pools: Arc<AmmPoolCache>) -> Self {
+        self.pools = Some(pools);
+        self
+    }
+
+    pub fn with_lookup_tables(mut self, tables: Arc<LookupTableCache>) -> Self {
+        self.tables = Some(tables);
+        self
+    }
+
+    pub fn parse_stats(&self) -> Arc<ParseStats> {
+        self.stats.clone()
+    }
+}
+
+impl EventSource for ReplaySource {
+    fn kind(&self) -> SourceKind {
+        self.kind
+    }
+
+    fn run(self: Box<Self>, sink: mpsc::Sender<EventEnvelope>) -> BoxFuture<'static, ()> {
+        Box::pin(async move {
+            let file = match tokio::fs::File::open(&self.path).await {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("[replay] can't open {}: {}", self.path.display(), e);
+                    return;
+                }
+            };
+
+            let mut lines = BufReader::new(file).lines();
+            let mut decoder = Decoder::new(false, self.pools, self.tables, self.stats);
+            let started = Instant::now();
+            let mut first_at = None;
+            let mut replayed = 0usize;
+
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(record) = serde_json::from_str::<CaptureRecord>(&line) else {
+                    continue;
+                };
+
+                if record.source() != self.kind {
+                    continue;
+                }
+
+                if self.speed > 0.0 {
+                    let first = *first_at.get_or_insert(record.at());
+                    let offset = Duration::from_micros(record.at().saturating_sub(first));
+                    tokio::time::sleep_until((started + offset.div_f64(self.speed)).into()).await;
+                }
+
+                let envelopes = match record {
+                    CaptureRecord::Logs { text, .. } => decoder.logs(&text),
+                    CaptureRecord::PumpPortal { text, .. } => {
+                        decoder.pumpportal(&text).into_iter().collect()
+                    }
+                    CaptureRecord::Shred { slot, entries, .. } => {
+                        let Ok(entries) = BASE64_STANDARD.decode(entries) else {
+                            continue;
+                        };
+                        decoder.shred(slot, &entries)
+                    }
+                };
+
+                for envelope in envelopes {
+                    if sink.send(envelope).await.is_err() {
+                        return;
+                    }
+                }
+
+                replayed += 1;
+            }
+
+            println!("[replay] {:?}: {} messages replayed", self.kind, replayed);
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum DedupKey {
+    Create(Pubkey),
+    Trade(Signature, Option<usize>, Pubkey, bool),
+    Complete(Pubkey),
+    Migrated(Pubkey),
+    PoolCreated(Pubkey),
+}
+
+impl DedupKey {
+    fn of(envelope: &EventEnvelope) -> Option<Self> {
+        match &envelope.event {
+            Event::Create(create) => Some(Self::Create(create.mint)),
+            Event::Buy(buy) => Some(Self::Trade(
+                envelope.signature?,
+                envelope.instruction,
+                buy.mint,
+                true,
+            )),
+            Event::Sell(sell) => Some(Self::Trade(
+                envelope.signature?,
+                envelope.instruction,
+                sell.mint,
+                false,
+            )),
+            Event::Complete(complete) => Some(Self::Complete(complete.mint)),
+            Event::Migrated(migrated) => Some(Self::Migrated(migrated.mint)),
+            Event::PoolCreated(pool) => Some(Self::PoolCreated(pool.pool)),
+            Event::AmmTrade(trade) => Some(Self::Trade(
+                envelope.signature?,
+                envelope.instruction,
+                trade.pool,
+                trade.is_buy,
+            )),
+        }
+    }
+}
+
+enum Seen {
+    New,
+    /// Only an estimate of it was seen so far
+    ReplacesEstimate,
+    Duplicate,
+}
+
+/// Insertion-ordered set that forgets its oldest entries past `max_size`.
+/// Each key remembers whether only an estimate of it was seen so far.
+struct SeenSet<K> {
+    keys: HashMap<K, bool>,
+    order: VecDeque<K>,
+    max_size: usize,
+}
+
+impl<K: Hash + Eq + Clone> SeenSet<K> {
+    fn new(max_size: usize) -> Self {
+        Self {
+            keys: HashMap::new(),
+            order: VecDeque::new(),
+            max_size,
+        }
+    }
+
+    fn insert(&mut self, key: K, estimated: bool) -> Seen {
+        if let Some(seen_estimated) = self.keys.get_mut(&key) {
+            if *seen_estimated && !estimated {
+                *seen_estimated = false;
+                return Seen::ReplacesEstimate;
+            }
+            return Seen::Duplicate;
+        }
+        self.keys.insert(key.clone(), estimated);
+
+        self.order.push_back(key);
+        while self.order.len() > self.max_size {
+            if let Some(front) = self.order.pop_front() {
+                self.keys.remove(&front);
+            }
+        }
+
+        Seen::New
+    }
+}
+
+const DEDUP_CAPACITY: usize = 100_000;
+const HELD_CAPACITY: usize = 10_000;
+
+// Well below the 5s a create may take end to end before it's dropped
+const DEFAULT_HOLD: Duration = Duration::from_millis(300);
+
+/// Runs several event sources at once and hands a single, deduplicated stream
+/// to the callback.
+///
+/// Sources are ranked in the order they are added. The first source that has
+/// produced something within `quiet_after` is the active one; its events (and
+/// those of higher-ranked sources) are forwarded immediately. Events from
+/// lower-ranked sources are held back for `hold` and only forwarded if no
+/// other source delivered the same create/trade in the meantime, or right
+/// away once every source above them has gone quiet. A landed trade still
+/// goes through after an estimate of it, marked `replaces_estimate`.
+pub struct Multiplexer {
+    sources: Vec<Box<dyn EventSource>>,
+    quiet_after: Duration,
+    hold: Duration,
+}
+
+impl Multiplexer {
+    pub fn new(quiet_after: Duration) -> Self {
+        Self {
+            sources: vec![],
+            quiet_after,
+            hold: DEFAULT_HOLD,
+        }
+    }
+
+    /// How long events of lower-ranked sources wait for the active one.
+    pub fn hold(mut self, hold: Duration) -> Self {
+        self.hold = hold;
+        self
+    }
+
+    pub fn add(&mut self, source: impl EventSource) {
+        self.sources.push(Box::new(source));
+    }
+
+    pub async fn run<F, Fut>(self, mut func: F)
+    where
+        F: FnMut(EventEnvelope) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        if self.sources.is_empty() {
+            return;
+        }
+
+        let kinds: Vec<SourceKind> = self.sources.iter().map(|s| s.kind()).collect();
+        let (tx, mut rx) = mpsc::channel::<EventEnvelope>(4096);
+
+        let handles: Vec<_> = self
+            .sources
+            .into_iter()
+            .map(|source| tokio::spawn(source.run(tx.clone())))
+            .collect();
+        drop(tx);
+
+        // Every source gets a grace period on startup
+        let mut last_seen = vec![Instant::now(); kinds.len()];
+        let mut active = 0;
+        let mut seen = SeenSet::new(DEDUP_CAPACITY);
+        let mut held: VecDeque<(Instant, usize, EventEnvelope)> = VecDeque::new();
+        let mut tick = tokio::time::interval(
+            (self.hold / 4).clamp(Duration::from_millis(10), Duration::from_millis(250)),
+        );
+        let mut closed = false;
+
+        println!("[mux] active source: {:?}", kinds[active]);
+
+        loop {
+            let mut ready = vec![];
+
+            tokio::select! {
+                msg = rx.recv() => match msg {
+                    Some(envelope) => {
+                        let rank = kinds
+                            .iter()
+                            .position(|k| *k == envelope.source)
+                            .unwrap_or(0);
+                        last_seen[rank] = Instant::now();
+
+                        if rank <= active {
+                            ready.push(envelope);
+                        } else {
+                            held.push_back((Instant::now(), rank, envelope));
+                            if held.len() > HELD_CAPACITY {
+                                if let Some((_, _, envelope)) = held.pop_front() {
+                                    ready.push(envelope);
+                                }
+                            }
+                        }
+                    }
+                    None => {
+                        // Every source is done, release what is still held
+                        closed = true;
+                        ready.extend(held.drain(..).map(|(_, _, envelope)| envelope));
+                    }
+                },
+                _ = tick.tick() => {
+                    let now = Instant::now();
+
+                    let next = last_seen
+                        .iter()
+                        .position(|t| now.duration_since(*t) < self.quiet_after)
+                        .unwrap_or(kinds.len() - 1);
+
+                    if next != active {
+                        println!(
+                            "[mux] active source: {:?} -> {:?}",
+                            kinds[active], kinds[next]
+                        );
+                        active = next;
+                    }
+
+                    let mut kept = VecDeque::with_capacity(held.len());
+                    for (at, rank, envelope) in held.drain(..) {
+                        if rank <= active || now.duration_since(at) >= self.hold {
+                            ready.push(envelope);
+                        } else {
+                            kept.push_back((at, rank, envelope));
+                        }
+                    }
+                    held = kept;
+                }
+            }
+
+            for mut envelope in ready {
+                let outcome = match DedupKey::of(&envelope) {
+                    Some(key) => seen.insert(key, envelope.event.estimated()),
+                    None => Seen::New,
+                };
+
+                match outcome {
+                    Seen::New => func(envelope).await,
+                    Seen::ReplacesEstimate => {
+                        envelope.replaces_estimate = true;
+                        func(envelope).await;
+                    }
+                    Seen::Duplicate => {}
+                }
+            }
+
+            if closed {
+                break;
+            }
+        }
+
+        for handle in handles {
+            handle.abort();
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use rust_decimal::{dec, Decimal};
 use serde::{Deserialize, Serialize};
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
 
 use crate::{
     constans::helper::{pool_pda, CommunityInfo},
@@ -26,6 +26,9 @@ pub struct Token {
     pub token_2022: bool,
     pub metadata_ipfs: Option<String>,
     pub metadata: Option<Metadata>,
+    /// Slot and signature of the create transaction, when the source knows them
+    pub slot: Option<u64>,
+    pub signature: Option<Signature>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +36,10 @@ pub struct DevPerformance {
     pub average_ath: u64,
     pub last_tokens: Vec<DbToken>,
     pub count: usize,
+    /// How many of the dev's tokens migrated to PumpSwap
+    pub migrated: usize,
+    /// Share of the dev's tokens they dumped right after creation, 0..100
+    pub rug_rate: u64,
 }
 
 impl Token {
@@ -65,9 +72,17 @@ impl Token {
             token_2022,
             metadata_ipfs,
             metadata,
+            slot: None,
+            signature: None,
         }
     }
 
+    pub fn with_origin(mut self, slot: Option<u64>, signature: Option<Signature>) -> Self {
+        self.slot = slot;
+        self.signature = signature;
+        self
+    }
+
     pub fn update(&mut self, event: Trade, price: u64) {
         self.reserves = event.reserves();
         self.mcap = event.mcap();
@@ -106,6 +121,11 @@ impl Token {
             description,
             community_id: twitter,
             pool_address: pool_pda(&mint).0.to_string(),
+            slot: self.slot.map(|s| s as i64),
+            signature: self.signature.map(|s| s.to_string()),
+            completed_at: None,
+            migrated_at: None,
+            rugged_at: None,
         }
     }
 }
@@ -0,0 +1,94 @@
+use dashmap::DashMap;
+use serde::Serialize;
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Why a raw message did not produce an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// `Program data` payload is not valid base64
+    Base64,
+    /// Payload too short to hold a discriminator
+    TooShort { len: usize },
+    /// Pump.fun discriminator we don't know, usually a new instruction or event
+    UnknownDiscriminator { discriminator: [u8; 8] },
+    /// Known discriminator but the layout (arguments or accounts) did not
+    /// match, usually a program upgrade
+    BorshFailed { discriminator: [u8; 8] },
+    /// Shredstream entries that failed to deserialize
+    Entries,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base64 => write!(f, "payload is not valid base64"),
+            Self::TooShort { len } => write!(f, "payload of {} bytes has no discriminator", len),
+            Self::UnknownDiscriminator { discriminator } => {
+                write!(f, "unknown discriminator {:?}", discriminator)
+            }
+            Self::BorshFailed { discriminator } => {
+                write!(f, "layout of {:?} did not match", discriminator)
+            }
+            Self::Entries => write!(f, "shredstream entries failed to deserialize"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Failure counters of one stream, by kind. Borsh failures are split per
+/// discriminator so a changed event layout stands out.
+#[derive(Default)]
+pub struct ParseStats {
+    base64: AtomicU64,
+    too_short: AtomicU64,
+    unknown_discriminator: AtomicU64,
+    entries: AtomicU64,
+    borsh_failed: DashMap<[u8; 8], u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParseErrorCounts {
+    pub base64: u64,
+    pub too_short: u64,
+    pub unknown_discriminator: u64,
+    pub entries: u64,
+    pub borsh_failed: Vec<([u8; 8], u64)>,
+}
+
+impl ParseStats {
+    pub fn record(&self, error: &ParseError) {
+        match error {
+            ParseError::Base64 => self.base64.fetch_add(1, Ordering::Relaxed),
+            ParseError::TooShort { .. } => self.too_short.fetch_add(1, Ordering::Relaxed),
+            ParseError::UnknownDiscriminator { .. } => {
+                self.unknown_discriminator.fetch_add(1, Ordering::Relaxed)
+            }
+            ParseError::Entries => self.entries.fetch_add(1, Ordering::Relaxed),
+            ParseError::BorshFailed { discriminator } => {
+                *self.borsh_failed.entry(*discriminator).or_insert(0) += 1;
+                return;
+            }
+        };
+    }
+
+    pub fn snapshot(&self) -> ParseErrorCounts {
+        let mut borsh_failed: Vec<_> = self
+            .borsh_failed
+            .iter()
+            .map(|entry| (*entry.key(), *entry.value()))
+            .collect();
+        borsh_failed.sort_by(|a, b| b.1.cmp(&a.1));
+
+        ParseErrorCounts {
+            base64: self.base64.load(Ordering::Relaxed),
+            too_short: self.too_short.load(Ordering::Relaxed),
+            unknown_discriminator: self.unknown_discriminator.load(Ordering::Relaxed),
+            entries: self.entries.load(Ordering::Relaxed),
+            borsh_failed,
+        }
+    }
+}
@@ -1,12 +1,23 @@
+use crate::{constans::helper::pool_pda, usd_mcap};
 use borsh::{io, BorshDeserialize};
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey, pubkey::Pubkey};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+pub const WSOL_MINT: Pubkey = pubkey!("So11111111111111111111111111111111111111112");
+
 #[derive(Debug)]
 pub enum Event {
     Create(CreateEvent),
     Buy(BuyEvent),
     Sell(SellEvent),
+    /// Bonding curve reached its target, trading on it stops
+    Complete(CompleteEvent),
+    /// Liquidity moved from the bonding curve into a PumpSwap pool
+    Migrated(MigratedEvent),
+    /// Any PumpSwap pool creation, migrations included
+    PoolCreated(PoolCreatedEvent),
+    /// Trade on the PumpSwap pool of a graduated token
+    AmmTrade(AmmTrade),
 }
 
 impl Event {
@@ -15,6 +26,19 @@ impl Event {
             Event::Create(create_event) => &create_event.mint,
             Event::Buy(buy_event) => &buy_event.mint,
             Event::Sell(sell_event) => &sell_event.mint,
+            Event::Complete(complete_event) => &complete_event.mint,
+            Event::Migrated(migrated_event) => &migrated_event.mint,
+            Event::PoolCreated(pool_event) => &pool_event.base_mint,
+            Event::AmmTrade(amm_trade) => &amm_trade.mint,
+        }
+    }
+
+    /// Decoded from an instruction that has not executed yet, see `BuyEvent::estimated`.
+    pub fn estimated(&self) -> bool {
+        match self {
+            Event::Buy(buy_event) => buy_event.estimated,
+            Event::Sell(sell_event) => sell_event.estimated,
+            _ => false,
         }
     }
 }
@@ -181,7 +205,10 @@ impl From<CreateEventV2> for CreateEvent {
 
 #[derive(Clone, Debug, BorshDeserialize)]
 pub struct BuyEvent {
+    /// PumpSwap pool of the token, what trades are keyed by
     pub mint: Pubkey,
+    /// The token itself
+    pub token_mint: Pubkey,
     pub sol_amount: u64,
     pub token_amount: u64,
     pub user: Pubkey,
@@ -189,11 +216,18 @@ pub struct BuyEvent {
     pub virtual_sol_reserves_before: u64,
     pub virtual_sol_reserves_after: u64,
     pub virtual_token_reserves: u64,
+    /// Set when the event was decoded from an unexecuted instruction (shredstream).
+    /// `sol_amount` and the reserve fields are then derived locally, not reported
+    /// by the program.
+    pub estimated: bool,
 }
 
 #[derive(Clone, Debug, BorshDeserialize)]
 pub struct SellEvent {
+    /// PumpSwap pool of the token, what trades are keyed by
     pub mint: Pubkey,
+    /// The token itself
+    pub token_mint: Pubkey,
     pub sol_amount: u64,
     pub token_amount: u64,
     pub user: Pubkey,
@@ -201,6 +235,10 @@ pub struct SellEvent {
     pub virtual_sol_reserves_before: u64,
     pub virtual_sol_reserves_after: u64,
     pub virtual_token_reserves: u64,
+    /// Set when the event was decoded from an unexecuted instruction (shredstream).
+    /// `sol_amount` and the reserve fields are then derived locally, not reported
+    /// by the program.
+    pub estimated: bool,
 }
 
 #[derive(Clone, Debug, BorshDeserialize)]
@@ -215,7 +253,104 @@ pub struct TradeEvent {
     pub virtual_token_reserves: u64,
 }
 
+#[derive(Clone, Debug)]
+pub struct CompleteEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    /// PumpSwap pool the curve migrates into
+    pub pool: Pubkey,
+    pub timestamp: i64,
+    /// Final curve reserves, taken from the trade that completed the curve.
+    /// Zero when that trade was not seen.
+    pub virtual_sol_reserves: u64,
+    pub virtual_token_reserves: u64,
+}
+
+#[derive(Clone, Debug, BorshDeserialize)]
+pub struct CurveCompleteEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub bonding_curve: Pubkey,
+    pub timestamp: i64,
+}
+
+impl From<CurveCompleteEvent> for CompleteEvent {
+    fn from(e: CurveCompleteEvent) -> Self {
+        Self {
+            user: e.user,
+            pool: pool_pda(&e.mint).0,
+            mint: e.mint,
+            bonding_curve: e.bonding_curve,
+            timestamp: e.timestamp,
+            virtual_sol_reserves: 0,
+            virtual_token_reserves: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, BorshDeserialize)]
+pub struct MigratedEvent {
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    /// Tokens and lamports deposited into the pool
+    pub mint_amount: u64,
+    pub sol_amount: u64,
+    pub pool_migration_fee: u64,
+    pub bonding_curve: Pubkey,
+    pub timestamp: i64,
+    pub pool: Pubkey,
+}
+
+#[derive(Clone, Debug)]
+pub struct PoolCreatedEvent {
+    pub pool: Pubkey,
+    pub creator: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+    pub timestamp: i64,
+}
+
 // AMM events
+#[derive(Clone, Debug, BorshDeserialize)]
+pub struct CreatePoolEventAMM {
+    pub timestamp: i64,
+    pub index: u16,
+    pub creator: Pubkey,
+    pub base_mint: Pubkey,
+    pub quote_mint: Pubkey,
+    pub base_mint_decimals: u8,
+    pub quote_mint_decimals: u8,
+    pub base_amount_in: u64,
+    pub quote_amount_in: u64,
+    pub pool_base_amount: u64,
+    pub pool_quote_amount: u64,
+    pub minimum_liquidity: u64,
+    pub initial_liquidity: u64,
+    pub lp_token_amount_out: u64,
+    pub pool_bump: u8,
+    pub pool: Pubkey,
+    pub lp_mint: Pubkey,
+    pub user_base_token_account: Pubkey,
+    pub user_quote_token_account: Pubkey,
+}
+
+impl From<CreatePoolEventAMM> for PoolCreatedEvent {
+    fn from(e: CreatePoolEventAMM) -> Self {
+        Self {
+            pool: e.pool,
+            creator: e.creator,
+            base_mint: e.base_mint,
+            quote_mint: e.quote_mint,
+            base_amount: e.pool_base_amount,
+            quote_amount: e.pool_quote_amount,
+            timestamp: e.timestamp,
+        }
+    }
+}
+
 #[derive(Clone, Debug, BorshDeserialize)]
 pub struct BuyEventAMM {
     pub timestamp: i64,
@@ -264,46 +399,129 @@ pub struct SellEventAMM {
     pub protocol_fee_recipient_token_account: Pubkey,
 }
 
-// Conversions from AMM
-impl From<BuyEventAMM> for BuyEvent {
+/// PumpSwap trade in the pool's own base/quote terms, before the mints behind
+/// the pool are known.
+#[derive(Clone, Debug)]
+pub struct AmmSwap {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub timestamp: i64,
+    /// `true` when base was bought with quote
+    pub base_bought: bool,
+    pub base_amount: u64,
+    /// Quote paid or received by the user, fees included
+    pub quote_amount: u64,
+    /// Pool reserves before the swap
+    pub pool_base_reserves: u64,
+    pub pool_quote_reserves: u64,
+}
+
+impl From<BuyEventAMM> for AmmSwap {
     fn from(e: BuyEventAMM) -> Self {
-        if e.pool_base_token_reserves > e.pool_quote_token_reserves {
-            BuyEvent {
-                mint: e.pool,
-                sol_amount: e.base_amount_out,
-                token_amount: e.quote_amount_in,
-                user: e.user,
-                timestamp: e.timestamp,
-                virtual_sol_reserves_before: e.pool_quote_token_reserves,
-                virtual_sol_reserves_after: e.pool_quote_token_reserves,
-                virtual_token_reserves: e.pool_base_token_reserves,
-            }
-        } else {
-            BuyEvent {
-                mint: e.pool,
-                sol_amount: e.base_amount_out,
-                token_amount: e.quote_amount_in,
-                user: e.user,
-                timestamp: e.timestamp,
-                virtual_sol_reserves_before: e.pool_base_token_reserves,
-                virtual_sol_reserves_after: e.pool_base_token_reserves,
-                virtual_token_reserves: e.pool_quote_token_reserves,
-            }
+        Self {
+            pool: e.pool,
+            user: e.user,
+            timestamp: e.timestamp,
+            base_bought: true,
+            base_amount: e.base_amount_out,
+            quote_amount: e.user_quote_amount_in,
+            pool_base_reserves: e.pool_base_token_reserves,
+            pool_quote_reserves: e.pool_quote_token_reserves,
         }
     }
 }
 
-impl From<SellEventAMM> for SellEvent {
+impl From<SellEventAMM> for AmmSwap {
     fn from(e: SellEventAMM) -> Self {
-        SellEvent {
-            mint: e.pool,
-            sol_amount: e.base_amount_in,
-            token_amount: e.quote_amount_out,
+        Self {
+            pool: e.pool,
             user: e.user,
             timestamp: e.timestamp,
-            virtual_sol_reserves_before: e.pool_base_token_reserves,
-            virtual_sol_reserves_after: e.pool_base_token_reserves,
-            virtual_token_reserves: e.pool_quote_token_reserves,
+            base_bought: false,
+            base_amount: e.base_amount_in,
+            quote_amount: e.user_quote_amount_out,
+            pool_base_reserves: e.pool_base_token_reserves,
+            pool_quote_reserves: e.pool_quote_token_reserves,
+        }
+    }
+}
+
+impl AmmSwap {
+    /// Turns the swap into a token/SOL trade. `None` when neither side of the
+    /// pool is wrapped SOL.
+    pub fn resolve(self, base_mint: Pubkey, quote_mint: Pubkey) -> Option<AmmTrade> {
+        // Token side and whether the token was bought
+        let (mint, is_buy, sol_amount, token_amount, sol_reserves, token_reserves) =
+            if quote_mint == WSOL_MINT {
+                (
+                    base_mint,
+                    self.base_bought,
+                    self.quote_amount,
+                    self.base_amount,
+                    self.pool_quote_reserves,
+                    self.pool_base_reserves,
+                )
+            } else if base_mint == WSOL_MINT {
+                (
+                    quote_mint,
+                    !self.base_bought,
+                    self.base_amount,
+                    self.quote_amount,
+                    self.pool_base_reserves,
+                    self.pool_quote_reserves,
+                )
+            } else {
+                return None;
+            };
+
+        let (pool_sol_reserves, pool_token_reserves) = if is_buy {
+            (
+                sol_reserves.saturating_add(sol_amount),
+                token_reserves.saturating_sub(token_amount),
+            )
+        } else {
+            (
+                sol_reserves.saturating_sub(sol_amount),
+                token_reserves.saturating_add(token_amount),
+            )
+        };
+
+        Some(AmmTrade {
+            pool: self.pool,
+            mint,
+            is_buy,
+            sol_amount,
+            token_amount,
+            user: self.user,
+            timestamp: self.timestamp,
+            pool_sol_reserves,
+            pool_token_reserves,
+        })
+    }
+}
+
+/// Trade on a PumpSwap pool (a graduated token), expressed like a curve trade.
+#[derive(Clone, Debug)]
+pub struct AmmTrade {
+    pub pool: Pubkey,
+    /// The token mint, never wrapped SOL
+    pub mint: Pubkey,
+    pub is_buy: bool,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub user: Pubkey,
+    pub timestamp: i64,
+    /// Pool reserves after the trade
+    pub pool_sol_reserves: u64,
+    pub pool_token_reserves: u64,
+}
+
+impl AmmTrade {
+    /// Market cap in USD after the trade, same scale as the bonding curve one.
+    pub fn usd_mcap(&self, sol_price: u64) -> u64 {
+        if self.pool_token_reserves == 0 {
+            return 0;
         }
+        usd_mcap(self.pool_sol_reserves, self.pool_token_reserves, sol_price)
     }
 }
@@ -1,6 +1,8 @@
 pub mod logs;
 pub mod requests;
 
+mod errors;
 mod trade;
 
+pub use errors::*;
 pub use trade::*;
@@ -15,22 +15,30 @@ pub struct Params {
 
 #[derive(Debug, Deserialize)]
 pub struct ResultField {
-    context: Context,
+    pub context: Context,
     pub value: LogValue,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Context {
-    slot: u64,
+    pub slot: u64,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct LogValue {
-    signature: String,
-    err: Option<serde_json::Value>,
+    pub signature: String,
+    pub err: Option<serde_json::Value>,
     pub logs: Vec<String>,
 }
 
+impl LogValue {
+    /// The transaction landed but its execution failed, any events it logged
+    /// before failing never took effect.
+    pub fn failed(&self) -> bool {
+        self.err.is_some()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct PriceResponse {
     pub solana: SolanaPrice,
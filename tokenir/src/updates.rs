@@ -0,0 +1,117 @@
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::time::{Duration, Instant};
+
+use crate::{constans::helper::pool_pda, Token};
+
+/// Live numbers of a token, sent to clients after its creation message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenUpdate {
+    pub mint: Pubkey,
+    /// Market cap after the last trade, in USD
+    pub mcap: u64,
+    /// Highest market cap seen since creation, in USD
+    pub ath: u64,
+    pub buys: u64,
+    pub sells: u64,
+    /// SOL traded, in lamports
+    pub volume: u64,
+}
+
+struct Tracked {
+    update: TokenUpdate,
+    changed: bool,
+    last_trade: Instant,
+}
+
+/// Trade totals of tokens created while the server is up. Trades mark a
+/// token as changed, `drain` hands out each changed token once, so however
+/// many trades happen in between clients get one update per interval.
+pub struct UpdateTracker {
+    // Keyed by pool address, that's what the curve trades carry
+    tokens: DashMap<Pubkey, Tracked>,
+    idle_after: Duration,
+}
+
+impl UpdateTracker {
+    /// Tokens without trades for `idle_after` are dropped.
+    pub fn new(idle_after: Duration) -> Self {
+        Self {
+            tokens: DashMap::new(),
+            idle_after,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn track(&self, token: &Token) {
+        self.tokens.insert(
+            pool_pda(&token.mint).0,
+            Tracked {
+                update: TokenUpdate {
+                    mint: token.mint,
+                    mcap: token.ath,
+                    ath: token.ath,
+                    buys: 0,
+                    sells: 0,
+                    volume: 0,
+                },
+                changed: false,
+                last_trade: Instant::now(),
+            },
+        );
+    }
+
+    /// Records a curve trade. Trades of untracked tokens are ignored.
+    pub fn trade(&self, pool: &Pubkey, is_buy: bool, sol_amount: u64, mcap: u64) {
+        let Some(mut tracked) = self.tokens.get_mut(pool) else {
+            return;
+        };
+
+        let update = &mut tracked.update;
+        update.mcap = mcap;
+        update.ath = update.ath.max(mcap);
+        update.volume = update.volume.saturating_add(sol_amount);
+        if is_buy {
+            update.buys += 1;
+        } else {
+            update.sells += 1;
+        }
+
+        tracked.changed = true;
+        tracked.last_trade = Instant::now();
+    }
+
+    /// The landed numbers of a trade already counted from its estimate.
+    pub fn correct(&self, pool: &Pubkey, mcap: u64) {
+        let Some(mut tracked) = self.tokens.get_mut(pool) else {
+            return;
+        };
+
+        tracked.update.mcap = mcap;
+        tracked.update.ath = tracked.update.ath.max(mcap);
+        tracked.changed = true;
+    }
+
+    /// Tokens that traded since the last call. Also forgets idle tokens.
+    pub fn drain(&self) -> Vec<TokenUpdate> {
+        let mut updates = vec![];
+
+        self.tokens.retain(|_, tracked| {
+            if tracked.changed {
+                tracked.changed = false;
+                updates.push(tracked.update.clone());
+            }
+            tracked.last_trade.elapsed() < self.idle_after
+        });
+
+        updates
+    }
+}
@@ -0,0 +1,43 @@
+//! How access keys are stored and checked.
+
+use tokenir::access::{generate_key, key_prefix, HashedKey, Role, KEY_PREFIX_LEN};
+
+#[test]
+fn hashed_keys_verify_only_the_original_key() {
+    let key = generate_key();
+    assert_eq!(key.len(), 32);
+
+    let hashed = HashedKey::new(&key);
+    assert_eq!(hashed.prefix, &key[..KEY_PREFIX_LEN]);
+    assert!(!hashed.hash.contains(&key));
+    assert!(HashedKey::verify(&key, &hashed.salt, &hashed.hash));
+    assert!(!HashedKey::verify(
+        &generate_key(),
+        &hashed.salt,
+        &hashed.hash
+    ));
+
+    // Same key, different salt, different hash
+    let again = HashedKey::new(&key);
+    assert_ne!(hashed.hash, again.hash);
+}
+
+#[test]
+fn prefix_of_short_and_non_ascii_keys() {
+    assert_eq!(key_prefix("abc"), "abc");
+    assert_eq!(key_prefix("ключключключ"), "ключключ");
+}
+
+#[test]
+fn roles_include_the_ones_below() {
+    assert!(Role::Admin > Role::Operator && Role::Operator > Role::Viewer);
+
+    for role in [Role::Viewer, Role::Operator, Role::Admin] {
+        assert_eq!(role.as_str().parse::<Role>(), Ok(role));
+        assert_eq!(
+            serde_json::to_string(&role).unwrap(),
+            format!("\"{}\"", role)
+        );
+    }
+    assert!("root".parse::<Role>().is_err());
+}
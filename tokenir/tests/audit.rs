@@ -0,0 +1,58 @@
+//! Audit entries as admin routes build them.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use tokenir::{
+    access::{Role, User},
+    audit::{AuditEntry, AuditEvent},
+};
+
+#[test]
+fn events_carry_the_caller_but_not_its_key() {
+    let user = User {
+        id: 7,
+        key_prefix: "abcd1234".to_string(),
+        hint: "ops".to_string(),
+        role: Some(Role::Operator),
+        autobuy: false,
+        max_sessions: 1,
+        expires_at: None,
+        disabled: false,
+    };
+
+    let event = AuditEvent::new("disconnect_session")
+        .with_user(&user)
+        .with_target("session 3")
+        .with_ip(Some(IpAddr::V4(Ipv4Addr::LOCALHOST)));
+
+    assert_eq!(event.actor_id, Some(7));
+    assert_eq!(event.actor.as_deref(), Some("abcd1234"));
+    assert_eq!(event.target.as_deref(), Some("session 3"));
+    assert_eq!(event.ip.as_deref(), Some("127.0.0.1"));
+    assert_eq!(event.details, None);
+
+    let failed = AuditEvent::new("ws_login_failed")
+        .with_actor("deadbeef")
+        .with_ip(None::<IpAddr>);
+    assert_eq!(failed.actor_id, None);
+    assert_eq!(failed.ip, None);
+}
+
+#[test]
+fn entries_read_back_from_the_admin_api() {
+    let entry: AuditEntry = serde_json::from_value(serde_json::json!({
+        "id": 12,
+        "at": 1_700_000_000,
+        "actor_id": null,
+        "actor": "deadbeef",
+        "action": "admin_login_failed",
+        "target": "/admin/users",
+        "details": "invalid key",
+        "ip": "10.0.0.1"
+    }))
+    .unwrap();
+
+    assert_eq!(entry.id, 12);
+    assert_eq!(entry.action, "admin_login_failed");
+    assert_eq!(entry.actor_id, None);
+}
@@ -0,0 +1,138 @@
+//! Batched token and ATH writes.
+
+use solana_sdk::pubkey::Pubkey;
+use tokenir::{
+    bundler::{Batch, Bundler, BundlerConfig},
+    constans::helper::pool_pda,
+    database::{Database, DbToken},
+    history::TradeRecord,
+};
+
+fn db_token(mint: &Pubkey, ath: i64) -> DbToken {
+    DbToken {
+        mint: mint.to_string(),
+        dev_address: "Dev".to_string(),
+        ath,
+        name: "Test".to_string(),
+        ticker: "TST".to_string(),
+        ipfs: None,
+        image: None,
+        description: None,
+        community_id: None,
+        pool_address: pool_pda(mint).0.to_string(),
+        slot: None,
+        signature: None,
+        completed_at: None,
+        migrated_at: None,
+        rugged_at: None,
+    }
+}
+
+#[test]
+fn batch_keeps_the_highest_ath_per_pool() {
+    let mut batch = Batch::default();
+    let pool = Pubkey::new_unique();
+
+    batch.add_ath(pool, 10);
+    batch.add_ath(pool, 30);
+    batch.add_ath(pool, 20);
+    batch.add_ath(Pubkey::new_unique(), 5);
+
+    assert_eq!(batch.ath(&pool), Some(30));
+    assert_eq!(batch.len(), 2);
+}
+
+#[test]
+fn merging_back_keeps_newer_writes() {
+    let mint = Pubkey::new_unique();
+    let pool = Pubkey::new_unique();
+
+    let mut failed = Batch::default();
+    failed.add_token(mint, db_token(&mint, 1), "Dev".to_string());
+    failed.add_ath(pool, 50);
+
+    let mut newer = Batch::default();
+    newer.add_token(mint, db_token(&mint, 2), "Dev".to_string());
+    newer.add_ath(pool, 40);
+
+    newer.merge(failed);
+    assert_eq!(newer.token(&mint).unwrap().ath, 2);
+    assert_eq!(newer.ath(&pool), Some(50));
+    assert_eq!(newer.len(), 2);
+}
+
+#[test]
+fn writes_past_the_cap_are_dropped() {
+    let bundler = Bundler::new(BundlerConfig {
+        max_pending: 2,
+        ..BundlerConfig::default()
+    });
+
+    for _ in 0..5 {
+        bundler.add_ath(Pubkey::new_unique(), 10);
+    }
+
+    assert_eq!(bundler.pending(), 2);
+    assert_eq!(bundler.shed(), 3);
+}
+
+#[test]
+fn trades_are_capped_on_their_own() {
+    let bundler = Bundler::new(BundlerConfig {
+        max_trades: 2,
+        ..BundlerConfig::default()
+    });
+
+    for timestamp in 0..4 {
+        bundler.add_trade(TradeRecord {
+            mint: "Mint".to_string(),
+            pool: "Pool".to_string(),
+            user: "User".to_string(),
+            is_buy: true,
+            sol_amount: 1,
+            token_amount: 1,
+            virtual_sol_reserves: 0,
+            virtual_token_reserves: 0,
+            slot: None,
+            signature: None,
+            timestamp,
+        });
+    }
+    // Still room for the rest
+    bundler.add_ath(Pubkey::new_unique(), 10);
+
+    assert_eq!(bundler.pending(), 3);
+    assert_eq!(bundler.shed(), 2);
+}
+
+/// Needs a scratch Postgres database in `TEST_DATABASE_URL`, run with
+/// `cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn flush_writes_tokens_then_aths() {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+    let database = Database::new(url).await.unwrap();
+    database.initialize_tables().await.unwrap();
+
+    let bundler = Bundler::new(BundlerConfig::default());
+    let mints: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+    for mint in &mints {
+        bundler.add_token(*mint, db_token(mint, 100), "Dev".to_string());
+        bundler.add_ath(pool_pda(mint).0, 500);
+    }
+    // Only the highest of these is written
+    bundler.add_ath(pool_pda(&mints[0]).0, 900);
+    bundler.add_ath(pool_pda(&mints[0]).0, 700);
+
+    assert_eq!(bundler.flush(&database).await.unwrap(), 6);
+    assert_eq!(bundler.pending(), 0);
+
+    for (mint, ath) in mints.iter().zip([900, 500, 500]) {
+        let (stored,): (i64,) = sqlx::query_as("SELECT ath FROM tokens WHERE mint = $1")
+            .bind(mint.to_string())
+            .fetch_one(database.connection())
+            .await
+            .unwrap();
+        assert_eq!(stored, ath);
+    }
+}
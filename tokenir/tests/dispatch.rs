@@ -0,0 +1,194 @@
+//! Per-token ordering and load shedding of the event dispatcher.
+
+use solana_sdk::pubkey::Pubkey;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokenir::{
+    constans::helper::pool_pda,
+    dispatch::{DispatchConfig, Dispatcher},
+    logs::{BuyEvent, CreateEvent, Event},
+    source::{EventEnvelope, SourceKind},
+};
+use tokio::sync::Semaphore;
+
+fn buy(pool: Pubkey, sol_amount: u64) -> EventEnvelope {
+    EventEnvelope {
+        source: SourceKind::Logs,
+        slot: None,
+        signature: None,
+        failed: false,
+        instruction: None,
+        received: Duration::ZERO,
+        replaces_estimate: false,
+        event: Event::Buy(BuyEvent {
+            mint: pool,
+            token_mint: Pubkey::new_unique(),
+            sol_amount,
+            token_amount: 0,
+            user: Pubkey::new_unique(),
+            timestamp: 0,
+            virtual_sol_reserves_before: 0,
+            virtual_sol_reserves_after: 0,
+            virtual_token_reserves: 0,
+            estimated: false,
+        }),
+    }
+}
+
+fn create(mint: Pubkey) -> EventEnvelope {
+    EventEnvelope {
+        event: Event::Create(CreateEvent {
+            name: String::new(),
+            symbol: String::new(),
+            uri: String::new(),
+            mint,
+            bonding_curve: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            token_2022: false,
+            timestamp: 0,
+        }),
+        ..buy(Pubkey::new_unique(), 0)
+    }
+}
+
+#[tokio::test]
+async fn buys_of_one_token_run_in_order() {
+    let pools: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+    let seen = Arc::new(Mutex::new(Vec::<(Pubkey, u64)>::new()));
+
+    let config = DispatchConfig {
+        concurrency: 8,
+        creates: 1,
+        queue: 1024,
+    };
+    let dispatcher = Dispatcher::new(config, {
+        let seen = seen.clone();
+        move |envelope: EventEnvelope| {
+            let seen = seen.clone();
+            async move {
+                let Event::Buy(buy) = envelope.event else {
+                    return;
+                };
+                // Uneven work, so lanes interleave
+                tokio::time::sleep(Duration::from_micros(buy.sol_amount % 7 * 100)).await;
+                seen.lock().unwrap().push((buy.mint, buy.sol_amount));
+            }
+        }
+    });
+
+    for i in 0..50 {
+        for pool in &pools {
+            dispatcher.dispatch(buy(*pool, i));
+        }
+    }
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while seen.lock().unwrap().len() < 200 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+
+    let seen = seen.lock().unwrap();
+    for pool in &pools {
+        let amounts: Vec<u64> = seen
+            .iter()
+            .filter(|(p, _)| p == pool)
+            .map(|(_, amount)| *amount)
+            .collect();
+        assert_eq!(amounts, (0..50).collect::<Vec<_>>());
+    }
+    assert_eq!(dispatcher.stats().shed(), 0);
+}
+
+#[tokio::test]
+async fn full_lanes_shed_instead_of_waiting() {
+    // Keeps the only lane busy until the test lets it go
+    let gate = Arc::new(Semaphore::new(0));
+
+    let config = DispatchConfig {
+        concurrency: 1,
+        creates: 1,
+        queue: 2,
+    };
+    let dispatcher = Dispatcher::new(config, {
+        let gate = gate.clone();
+        move |_| {
+            let gate = gate.clone();
+            async move {
+                let _ = gate.acquire().await.unwrap();
+            }
+        }
+    });
+
+    let pool = Pubkey::new_unique();
+    for i in 0..10 {
+        dispatcher.dispatch(buy(pool, i));
+    }
+
+    let stats = dispatcher.stats();
+    // The worker may or may not have taken the first event off the queue yet
+    assert!(stats.dispatched() == 2 || stats.dispatched() == 3);
+    assert_eq!(stats.dispatched() + stats.shed(), 10);
+
+    gate.add_permits(10);
+}
+
+#[tokio::test]
+async fn slow_creates_hold_up_only_their_own_token() {
+    // Keeps the create running until the test lets it go
+    let gate = Arc::new(Semaphore::new(0));
+    let seen = Arc::new(Mutex::new(Vec::<u64>::new()));
+
+    let config = DispatchConfig {
+        concurrency: 1,
+        creates: 1,
+        queue: 16,
+    };
+    let dispatcher = Dispatcher::new(config, {
+        let gate = gate.clone();
+        let seen = seen.clone();
+        move |envelope: EventEnvelope| {
+            let gate = gate.clone();
+            let seen = seen.clone();
+            async move {
+                match envelope.event {
+                    Event::Create(_) => {
+                        let _ = gate.acquire().await.unwrap();
+                        seen.lock().unwrap().push(0);
+                    }
+                    Event::Buy(buy) => seen.lock().unwrap().push(buy.sol_amount),
+                    _ => {}
+                }
+            }
+        }
+    });
+
+    let mint = Pubkey::new_unique();
+    let pool = pool_pda(&mint).0;
+    dispatcher.dispatch(create(mint));
+    dispatcher.dispatch(buy(pool, 1));
+    // Another token shares the only trade lane
+    dispatcher.dispatch(buy(Pubkey::new_unique(), 2));
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while seen.lock().unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(*seen.lock().unwrap(), vec![2]);
+
+    gate.add_permits(1);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while seen.lock().unwrap().len() < 3 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(*seen.lock().unwrap(), vec![2, 0, 1]);
+    assert_eq!(dispatcher.stats().dispatched(), 3);
+}
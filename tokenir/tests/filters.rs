@@ -0,0 +1,130 @@
+//! Subscription filters as clients send them over /ws.
+
+use solana_sdk::pubkey::Pubkey;
+use tokenir::{filters::FilterSet, DevPerformance, Token};
+
+fn token(name: &str, ticker: &str, performance: Option<(u64, usize, usize)>) -> Token {
+    let mut token = Token::fresh(
+        name.to_string(),
+        ticker.to_string(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        None,
+        Pubkey::new_unique(),
+        true,
+        None,
+        None,
+    );
+    token.dev_performance = performance.map(|(average_ath, count, migrated)| DevPerformance {
+        average_ath,
+        last_tokens: vec![],
+        count,
+        migrated,
+        rug_rate: 0,
+    });
+    token
+}
+
+fn filters(json: &str) -> FilterSet {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn every_filter_has_to_pass() {
+    let set = filters(
+        r#"{"filters": {
+            "AverageDevMarketCap": {"AverageDevMarketCap": {"start": 20000, "end": 1000000}},
+            "TokenCount": {"TokenCount": {"start": 1, "end": 10}},
+            "MigrationPercentage": {"MigrationPercentage": {"start": 25, "end": 101}}
+        }}"#,
+    );
+
+    assert!(set.matches(&token("a", "A", Some((50_000, 4, 1)))));
+    // Median ATH too low
+    assert!(!set.matches(&token("a", "A", Some((10_000, 4, 1)))));
+    // Too few migrations
+    assert!(!set.matches(&token("a", "A", Some((50_000, 5, 1)))));
+    // Fresh dev, no median to compare
+    assert!(!set.matches(&token("a", "A", None)));
+}
+
+#[test]
+fn fresh_devs_count_as_zero_tokens() {
+    let set = filters(r#"{"filters": {"TokenCount": {"TokenCount": {"start": 0, "end": 1}}}}"#);
+
+    assert!(set.matches(&token("a", "A", None)));
+    assert!(!set.matches(&token("a", "A", Some((50_000, 3, 0)))));
+}
+
+#[test]
+fn rug_rate_filter_skips_devs_that_dump() {
+    let set = filters(r#"{"filters": {"RugRate": {"RugRate": {"start": 0, "end": 25}}}}"#);
+
+    let mut dumper = token("a", "A", Some((50_000, 4, 1)));
+    if let Some(perf) = dumper.dev_performance.as_mut() {
+        perf.rug_rate = 50;
+    }
+    assert!(!set.matches(&dumper));
+    assert!(set.matches(&token("a", "A", Some((50_000, 4, 1)))));
+    // Fresh devs have not rugged anything yet
+    assert!(set.matches(&token("a", "A", None)));
+}
+
+#[test]
+fn name_pattern_matches_name_or_ticker() {
+    let set = filters(r#"{"filters": {"Name": {"Name": "(?i)^pepe"}}}"#);
+
+    assert!(set.matches(&token("Pepe Coin", "PC", None)));
+    assert!(set.matches(&token("Frog", "PEPE2", None)));
+    assert!(!set.matches(&token("Frog", "FROG", None)));
+}
+
+#[test]
+fn twitter_filter_checks_presence() {
+    let set = filters(r#"{"filters": {"Twitter": {"Twitter": false}}}"#);
+    assert!(set.matches(&token("a", "A", None)));
+
+    let set = filters(r#"{"filters": {"Twitter": {"Twitter": true}}}"#);
+    assert!(!set.matches(&token("a", "A", None)));
+}
+
+#[test]
+fn invalid_patterns_are_rejected() {
+    assert!(serde_json::from_str::<FilterSet>(r#"{"filters": {"Name": {"Name": "("}}}"#).is_err());
+}
+
+#[test]
+fn new_devs_can_pass_the_dev_filters() {
+    let set = filters(
+        r#"{"filters": {
+            "AverageDevMarketCap": {"AverageDevMarketCap": {"start": 20000, "end": 1000000}},
+            "Name": {"Name": "^a"}
+        }, "new_devs_pass": true}"#,
+    );
+
+    assert!(set.matches(&token("a", "A", None)));
+    // The other filters still apply
+    assert!(!set.matches(&token("b", "B", None)));
+    assert!(!set.matches(&token("a", "A", Some((10_000, 4, 1)))));
+}
+
+/// What the desktop client saves as `view_filters.json` and sends in its
+/// subscribe message, field for field.
+const CLIENT_FILTERS: &str = r#"{"filters":{"AverageDevMarketCap":{"AverageDevMarketCap":{"start":20000,"end":100000}},"TokenCount":{"TokenCount":{"start":0,"end":10}},"MigrationPercentage":{"MigrationPercentage":{"start":0,"end":100}},"RugRate":{"RugRate":{"start":0,"end":30}}},"new_devs_pass":true}"#;
+
+#[test]
+fn client_filter_json_round_trips() {
+    let set = filters(CLIENT_FILTERS);
+    assert_eq!(set.filters.len(), 4);
+    assert!(set.new_devs_pass);
+
+    let json = serde_json::to_value(&set).unwrap();
+    assert_eq!(
+        json,
+        serde_json::from_str::<serde_json::Value>(CLIENT_FILTERS).unwrap()
+    );
+
+    // Saved sets from before the flag still load
+    let saved = filters(r#"{"filters":{"RugRate":{"RugRate":{"start":0,"end":30}}}}"#);
+    assert!(!saved.new_devs_pass);
+}
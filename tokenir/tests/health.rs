@@ -0,0 +1,60 @@
+//! Reconnect backoff and the upstream health records.
+
+use std::time::Duration;
+use tokenir::health::{Backoff, ConnectionState, HealthRegistry};
+
+#[test]
+fn backoff_doubles_up_to_the_cap_with_jitter() {
+    let base = Duration::from_millis(100);
+    let max = Duration::from_secs(2);
+    let mut backoff = Backoff::new(base, max);
+
+    let mut ceiling = base;
+    for _ in 0..10 {
+        let delay = backoff.next_delay();
+        assert!(delay >= ceiling / 2 && delay <= ceiling, "{:?}", delay);
+        ceiling = (ceiling * 2).min(max);
+    }
+
+    backoff.reset();
+    assert!(backoff.next_delay() <= base);
+}
+
+#[test]
+fn backoff_starts_over_only_after_a_stable_connection() {
+    let base = Duration::from_millis(100);
+    let mut backoff =
+        Backoff::new(base, Duration::from_secs(2)).with_stable_after(Duration::from_millis(50));
+
+    backoff.next_delay();
+    backoff.next_delay();
+
+    // Dropped right after connecting, keeps backing off
+    backoff.connected();
+    assert!(backoff.next_delay() >= base * 2);
+
+    backoff.connected();
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(backoff.next_delay() <= base);
+}
+
+#[test]
+fn health_tracks_state_reconnects_and_errors() {
+    let registry = HealthRegistry::default();
+    let health = registry.register("logs pump");
+
+    health.connected();
+    health.message();
+    health.disconnected("connection reset");
+    health.connecting();
+
+    let snapshots = registry.snapshot();
+    assert_eq!(snapshots.len(), 1);
+
+    let snapshot = &snapshots[0];
+    assert_eq!(snapshot.name, "logs pump");
+    assert_eq!(snapshot.state, ConnectionState::Connecting);
+    assert_eq!(snapshot.reconnects, 1);
+    assert_eq!(snapshot.last_error.as_deref(), Some("connection reset"));
+    assert!(snapshot.last_message_at.is_some());
+}
@@ -0,0 +1,120 @@
+//! Trade records and the daily partitions they are stored in.
+
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::time::Duration;
+use tokenir::{
+    database::Database,
+    history::{Partition, TradeRecord, DAY},
+    logs::{BuyEvent, Event, SellEvent},
+};
+
+/// 2024-03-01 00:00:00 UTC
+const MARCH_FIRST: i64 = 1_709_251_200;
+
+fn sell(token_mint: Pubkey, timestamp: i64, estimated: bool) -> Event {
+    Event::Sell(SellEvent {
+        mint: Pubkey::new_unique(),
+        token_mint,
+        sol_amount: 1_000,
+        token_amount: 2_000,
+        user: Pubkey::new_unique(),
+        timestamp,
+        virtual_sol_reserves_before: 30_000,
+        virtual_sol_reserves_after: 29_000,
+        virtual_token_reserves: 1_000_000,
+        estimated,
+    })
+}
+
+#[test]
+fn partitions_cover_whole_utc_days() {
+    let partition = Partition::containing(MARCH_FIRST + 12 * 60 * 60);
+    assert_eq!(partition.name, "trades_20240301");
+    assert_eq!(partition.from, MARCH_FIRST);
+    assert_eq!(partition.to, MARCH_FIRST + DAY);
+
+    // The last second of the day is still in it, the next one is not
+    assert_eq!(Partition::containing(MARCH_FIRST + DAY - 1), partition);
+    assert_eq!(
+        Partition::containing(MARCH_FIRST + DAY).name,
+        "trades_20240302"
+    );
+}
+
+#[test]
+fn partitions_round_trip_through_their_name() {
+    let partition = Partition::containing(MARCH_FIRST);
+    assert_eq!(Partition::from_name(&partition.name), Some(partition));
+
+    assert_eq!(Partition::from_name("trades"), None);
+    assert_eq!(Partition::from_name("trades_2024"), None);
+    assert_eq!(Partition::from_name("tokens_20240301"), None);
+}
+
+#[test]
+fn only_landed_curve_trades_are_recorded() {
+    let token = Pubkey::new_unique();
+    let signature = Signature::new_unique();
+
+    let record =
+        TradeRecord::from_event(&sell(token, MARCH_FIRST, false), Some(42), Some(signature))
+            .unwrap();
+    assert_eq!(record.mint, token.to_string());
+    assert!(!record.is_buy);
+    assert_eq!(record.sol_amount, 1_000);
+    assert_eq!(record.slot, Some(42));
+    assert_eq!(record.signature, Some(signature.to_string()));
+
+    // Shredstream estimates may never land
+    assert_eq!(
+        TradeRecord::from_event(&sell(token, MARCH_FIRST, true), None, None),
+        None
+    );
+}
+
+/// Needs a scratch Postgres database in `TEST_DATABASE_URL`, run with
+/// `cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn old_partitions_are_dropped() {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+    let database = Database::new(url).await.unwrap();
+    database.initialize_tables().await.unwrap();
+
+    let token = Pubkey::new_unique();
+    let buy = Event::Buy(BuyEvent {
+        mint: Pubkey::new_unique(),
+        token_mint: token,
+        sol_amount: 5_000,
+        token_amount: 10_000,
+        user: Pubkey::new_unique(),
+        timestamp: MARCH_FIRST,
+        virtual_sol_reserves_before: 30_000,
+        virtual_sol_reserves_after: 35_000,
+        virtual_token_reserves: 1_000_000,
+        estimated: false,
+    });
+    let trades: Vec<_> = [buy, sell(token, MARCH_FIRST + DAY, false)]
+        .iter()
+        .filter_map(|event| TradeRecord::from_event(event, None, None))
+        .collect();
+    database.add_trades(&trades).await.unwrap();
+
+    let count = || async {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM trades WHERE mint = $1")
+            .bind(token.to_string())
+            .fetch_one(database.connection())
+            .await
+            .unwrap();
+        count
+    };
+    assert_eq!(count().await, 2);
+
+    // Both days are long past a year of retention
+    let dropped = database
+        .maintain_trade_partitions(Duration::from_secs(365 * DAY as u64))
+        .await
+        .unwrap();
+    assert!(dropped >= 2);
+    assert_eq!(count().await, 0);
+}
@@ -0,0 +1,147 @@
+//! Login rate limits and bans.
+
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    thread::sleep,
+    time::Duration,
+};
+use tokenir::limiter::{LimitKey, LimiterConfig, LoginLimiter};
+
+fn addr(last: u8) -> IpAddr {
+    IpAddr::V4(Ipv4Addr::new(10, 0, 0, last))
+}
+
+fn ip(last: u8) -> LimitKey {
+    LimitKey::Ip(addr(last))
+}
+
+fn key(last: u8, prefix: &str) -> LimitKey {
+    LimitKey::Key(addr(last), prefix.to_string())
+}
+
+#[test]
+fn attempts_refill_after_the_burst() {
+    let limiter = LoginLimiter::new(LimiterConfig {
+        burst: 3,
+        refill_per_sec: 20.0,
+        ..LimiterConfig::default()
+    });
+    let keys = [ip(1)];
+
+    for _ in 0..3 {
+        limiter.check(&keys).unwrap();
+    }
+    let limited = limiter.check(&keys).unwrap_err();
+    assert!(limited.retry_after <= Duration::from_millis(50));
+    assert_eq!(limited.retry_after_secs(), 1);
+
+    // Other addresses have their own bucket
+    limiter.check(&[ip(2)]).unwrap();
+
+    sleep(Duration::from_millis(60));
+    limiter.check(&keys).unwrap();
+}
+
+#[test]
+fn a_limited_key_takes_no_attempt_from_the_others() {
+    let limiter = LoginLimiter::new(LimiterConfig {
+        burst: 1,
+        refill_per_sec: 0.0,
+        ..LimiterConfig::default()
+    });
+    let prefix = key(1, "abcd1234");
+
+    limiter.check(&[prefix.clone()]).unwrap();
+    // The key is used up, the address keeps its attempt
+    assert!(limiter.check(&[ip(1), prefix]).is_err());
+    limiter.check(&[ip(1)]).unwrap();
+}
+
+#[test]
+fn repeated_failures_ban_until_the_ban_ends() {
+    let limiter = LoginLimiter::new(LimiterConfig {
+        burst: 100,
+        max_failures: 3,
+        ban: Duration::from_millis(100),
+        ..LimiterConfig::default()
+    });
+    let keys = [ip(1), key(1, "abcd1234")];
+
+    for _ in 0..2 {
+        limiter.check(&keys).unwrap();
+        assert!(limiter.failure(&keys).is_empty());
+    }
+    limiter.check(&keys).unwrap();
+    assert_eq!(limiter.failure(&keys), keys.to_vec());
+
+    let limited = limiter.check(&[ip(1)]).unwrap_err();
+    assert!(limited.retry_after > Duration::from_millis(50));
+    let snapshot = limiter.snapshot();
+    assert_eq!(snapshot.len(), 2);
+    assert!(snapshot.iter().all(|entry| entry.banned_for == Some(1)));
+
+    sleep(Duration::from_millis(120));
+    limiter.check(&keys).unwrap();
+}
+
+#[test]
+fn failures_with_a_prefix_never_lock_out_other_addresses() {
+    let limiter = LoginLimiter::new(LimiterConfig {
+        burst: 100,
+        max_failures: 3,
+        ..LimiterConfig::default()
+    });
+    let attacker = [ip(1), key(1, "abcd1234")];
+
+    for _ in 0..3 {
+        limiter.check(&attacker).unwrap();
+        limiter.failure(&attacker);
+    }
+    assert!(limiter.check(&attacker).is_err());
+
+    // The owner of the prefix logs in from elsewhere
+    limiter.check(&[ip(2), key(2, "abcd1234")]).unwrap();
+}
+
+#[test]
+fn a_good_key_does_not_clear_the_address() {
+    let limiter = LoginLimiter::new(LimiterConfig {
+        burst: 100,
+        max_failures: 3,
+        ..LimiterConfig::default()
+    });
+    let valid = [ip(1), key(1, "good0000")];
+
+    // Guesses with fresh prefixes, a valid login in between each
+    for guess in ["aaaa0000", "bbbb0000"] {
+        let guess = [ip(1), key(1, guess)];
+        limiter.check(&guess).unwrap();
+        assert!(limiter.failure(&guess).is_empty());
+
+        limiter.check(&valid).unwrap();
+        limiter.success(&valid);
+    }
+
+    let guess = [ip(1), key(1, "cccc0000")];
+    limiter.check(&guess).unwrap();
+    assert_eq!(limiter.failure(&guess), vec![ip(1)]);
+    assert!(limiter.check(&valid).is_err());
+}
+
+#[test]
+fn a_good_key_clears_its_own_failures() {
+    let limiter = LoginLimiter::new(LimiterConfig {
+        burst: 100,
+        max_failures: 2,
+        ..LimiterConfig::default()
+    });
+    let own = key(1, "abcd1234");
+
+    limiter.check(&[own.clone()]).unwrap();
+    assert!(limiter.failure(&[own.clone()]).is_empty());
+    limiter.success(&[own.clone()]);
+
+    // Starts over, one more failure doesn't ban
+    assert!(limiter.failure(&[own.clone()]).is_empty());
+    assert_eq!(limiter.failure(&[own.clone()]), vec![own]);
+}
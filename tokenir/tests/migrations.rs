@@ -0,0 +1,42 @@
+//! Which migrations a database gets, by the version it is at.
+
+use tokenir::migrations::{latest_version, pending, SchemaAhead, MIGRATIONS};
+
+#[test]
+fn versions_count_up_from_one() {
+    for (index, migration) in MIGRATIONS.iter().enumerate() {
+        assert_eq!(migration.version, index as i32 + 1);
+        assert!(!migration.name.is_empty());
+        assert!(!migration.sql.trim().is_empty());
+    }
+    assert_eq!(latest_version(), MIGRATIONS.len() as i32);
+}
+
+#[test]
+fn pending_migrations_by_current_version() {
+    // A fresh database gets everything, an up to date one nothing
+    assert_eq!(pending(0).unwrap().len(), MIGRATIONS.len());
+    assert!(pending(latest_version()).unwrap().is_empty());
+
+    let newer = latest_version() + 1;
+    assert_eq!(
+        pending(newer).err(),
+        Some(SchemaAhead {
+            database: newer,
+            binary: latest_version()
+        })
+    );
+}
+
+#[test]
+fn baseline_has_the_columns_queries_use() {
+    let baseline = MIGRATIONS[0].sql;
+    for column in [
+        "pool_address TEXT",
+        "autobuy BOOLEAN",
+        "key_hash TEXT",
+        "role TEXT",
+    ] {
+        assert!(baseline.contains(column), "baseline lacks {}", column);
+    }
+}
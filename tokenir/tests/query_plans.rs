@@ -0,0 +1,98 @@
+//! Lookups on the create path stay on indexes.
+//!
+//! Needs a scratch Postgres database in `TEST_DATABASE_URL`, the test is
+//! ignored by default; run it with `cargo test -- --ignored`.
+
+use sqlx::{pool::PoolConnection, Postgres, Row};
+use tokenir::database::{
+    Database, DEV_STATS_EXCLUDING, DEV_TOKENS_EXCLUDING, TOKEN_ANY_EXISTS, TOKEN_COMMUNITY_EXISTS,
+    UPDATE_TOKEN_ATH,
+};
+
+const DEV: &str = "Dev1111111111111111111111111111111111111111";
+const MINT: &str = "Mint111111111111111111111111111111111111111";
+
+enum Arg {
+    Text(&'static str),
+    Int(i64),
+}
+
+/// The plan Postgres picks when sequential scans are as expensive as it gets.
+async fn plan(conn: &mut PoolConnection<Postgres>, sql: &str, args: &[Arg]) -> String {
+    let explain = format!("EXPLAIN {}", sql);
+    let mut query = sqlx::query(&explain);
+    for arg in args {
+        query = match arg {
+            Arg::Text(value) => query.bind(*value),
+            Arg::Int(value) => query.bind(*value),
+        };
+    }
+
+    let rows = query.fetch_all(&mut **conn).await.unwrap();
+    rows.iter()
+        .map(|row| row.get::<String, _>(0))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn hot_queries_use_indexes() {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+    let database = Database::new(url).await.unwrap();
+    database.initialize_tables().await.unwrap();
+
+    let mut conn = database.connection().acquire().await.unwrap();
+    // With no rows a scan is always cheapest, so make it the last resort
+    sqlx::query("SET enable_seqscan = off")
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+    let queries = [
+        (
+            "token_any_exists",
+            TOKEN_ANY_EXISTS,
+            vec![
+                Arg::Text("https://ipfs.io/ipfs/image"),
+                Arg::Text("https://ipfs.io/ipfs/meta"),
+                Arg::Text("a description"),
+                Arg::Text("Name"),
+                Arg::Text("TICK"),
+            ],
+        ),
+        (
+            "dev_stats",
+            DEV_STATS_EXCLUDING,
+            vec![Arg::Text(DEV), Arg::Text(MINT)],
+        ),
+        (
+            "dev_tokens",
+            DEV_TOKENS_EXCLUDING,
+            vec![Arg::Text(DEV), Arg::Text(MINT), Arg::Int(3)],
+        ),
+        (
+            "community_exists",
+            TOKEN_COMMUNITY_EXISTS,
+            vec![Arg::Text("1234567890")],
+        ),
+        (
+            "update_ath",
+            UPDATE_TOKEN_ATH,
+            vec![
+                Arg::Text("Pool111111111111111111111111111111111111111"),
+                Arg::Int(1_000),
+            ],
+        ),
+    ];
+
+    for (name, sql, args) in &queries {
+        let plan = plan(&mut conn, sql, args).await;
+        assert!(
+            !plan.contains("Seq Scan"),
+            "{} falls back to a sequential scan:\n{}",
+            name,
+            plan
+        );
+    }
+}
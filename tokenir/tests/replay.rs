@@ -0,0 +1,1037 @@
+//! Offline regression tests for the ingestion pipeline: raw messages are written
+//! with the capture writer and fed back through `ReplaySource`, the same path
+//! `REPLAY=<file>` takes in the server.
+
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use borsh::BorshSerialize;
+use futures::future::BoxFuture;
+use solana_entry::entry::Entry;
+use solana_sdk::{
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    message::{Message, VersionedMessage},
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokenir::{
+    capture::CaptureWriter,
+    constans::helper::{pool_pda, CommunityInfo},
+    create::{CreateFlow, CreateLookups, TokenCache},
+    logs::{CreateEvent, Event},
+    requests::Metadata,
+    source::{EventEnvelope, EventSource, Multiplexer, ReplaySource, SourceKind},
+    DevPerformance,
+};
+use tokio::sync::{mpsc, Mutex};
+
+// sha256("event:<Name>")[..8]
+const CREATE_DISCRIMINATOR: [u8; 8] = [27, 114, 169, 77, 222, 235, 99, 118];
+const TRADE_DISCRIMINATOR: [u8; 8] = [189, 219, 127, 211, 78, 230, 97, 238];
+const COMPLETE_DISCRIMINATOR: [u8; 8] = [95, 114, 97, 156, 212, 46, 152, 8];
+
+// sha256("global:<name>")[..8]
+const CREATE_IX_DISCRIMINATOR: [u8; 8] = [24, 30, 200, 40, 5, 28, 7, 119];
+const BUY_IX_DISCRIMINATOR: [u8; 8] = [102, 6, 61, 18, 1, 218, 235, 234];
+const CREATE_V2_IX_DISCRIMINATOR: [u8; 8] = [214, 144, 76, 236, 95, 139, 49, 180];
+const SELL_IX_DISCRIMINATOR: [u8; 8] = [51, 230, 133, 164, 1, 127, 131, 173];
+
+const PUMP_PROGRAM: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+const TOKEN_2022_PROGRAM: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+
+fn capture_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "tokenir-{}-{}-{}.jsonl",
+        name,
+        std::process::id(),
+        rand_suffix()
+    ));
+    let _ = std::fs::remove_file(&path);
+    path
+}
+
+fn rand_suffix() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+}
+
+fn put<T: BorshSerialize>(buf: &mut Vec<u8>, value: T) {
+    value.serialize(buf).unwrap();
+}
+
+fn program_data(discriminator: [u8; 8], body: Vec<u8>) -> String {
+    let mut data = discriminator.to_vec();
+    data.extend(body);
+    format!("Program data: {}", BASE64_STANDARD.encode(data))
+}
+
+fn logs_notification(slot: u64, signature: &Signature, failed: bool, logs: &[String]) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "logsNotification",
+        "params": {
+            "result": {
+                "context": { "slot": slot },
+                "value": {
+                    "signature": signature.to_string(),
+                    "err": if failed {
+                        serde_json::json!({ "InstructionError": [2, { "Custom": 6002 }] })
+                    } else {
+                        serde_json::Value::Null
+                    },
+                    "logs": logs,
+                }
+            },
+            "subscription": 1
+        }
+    })
+    .to_string()
+}
+
+struct Trade {
+    mint: Pubkey,
+    sol_amount: u64,
+    token_amount: u64,
+    is_buy: bool,
+    user: Pubkey,
+    virtual_sol_reserves: u64,
+    virtual_token_reserves: u64,
+}
+
+fn trade_log(trade: &Trade) -> String {
+    let mut body = vec![];
+    put(&mut body, trade.mint);
+    put(&mut body, trade.sol_amount);
+    put(&mut body, trade.token_amount);
+    put(&mut body, trade.is_buy);
+    put(&mut body, trade.user);
+    put(&mut body, 1_700_000_000i64);
+    put(&mut body, trade.virtual_sol_reserves);
+    put(&mut body, trade.virtual_token_reserves);
+    program_data(TRADE_DISCRIMINATOR, body)
+}
+
+fn legacy_create_log(name: &str, mint: Pubkey, curve: Pubkey, user: Pubkey) -> String {
+    let mut body = vec![];
+    put(&mut body, name.to_string());
+    put(&mut body, "TST".to_string());
+    put(&mut body, "https://ipfs.io/ipfs/legacy".to_string());
+    put(&mut body, mint);
+    put(&mut body, curve);
+    put(&mut body, user);
+    program_data(CREATE_DISCRIMINATOR, body)
+}
+
+fn create_v2_log(mint: Pubkey, curve: Pubkey, user: Pubkey) -> String {
+    let mut body = vec![];
+    put(&mut body, "Second".to_string());
+    put(&mut body, "TWO".to_string());
+    put(&mut body, "https://ipfs.io/ipfs/v2".to_string());
+    put(&mut body, mint);
+    put(&mut body, curve);
+    put(&mut body, user);
+    put(&mut body, user);
+    put(&mut body, 1_700_000_000i64);
+    put(&mut body, 1_073_000_000_000_000u64);
+    put(&mut body, 30_000_000_000u64);
+    put(&mut body, 793_100_000_000_000u64);
+    put(&mut body, 1_000_000_000_000_000u64);
+    put(&mut body, TOKEN_2022_PROGRAM.parse::<Pubkey>().unwrap());
+    put(&mut body, false);
+    program_data(CREATE_DISCRIMINATOR, body)
+}
+
+fn complete_log(mint: Pubkey, curve: Pubkey, user: Pubkey) -> String {
+    let mut body = vec![];
+    put(&mut body, user);
+    put(&mut body, mint);
+    put(&mut body, curve);
+    put(&mut body, 1_700_000_000i64);
+    program_data(COMPLETE_DISCRIMINATOR, body)
+}
+
+fn pumpportal_create(mint: Pubkey, signature: &Signature) -> String {
+    serde_json::json!({
+        "signature": signature.to_string(),
+        "mint": mint.to_string(),
+        "traderPublicKey": Pubkey::new_unique().to_string(),
+        "txType": "create",
+        "name": "Portal",
+        "symbol": "PRT",
+        "uri": "https://ipfs.io/ipfs/portal",
+        "solAmount": 1.0,
+        "initialBuy": 1000.0,
+        "marketCapSol": 30.0,
+        "bondingCurveKey": Pubkey::new_unique().to_string(),
+        "vTokensInBondingCurve": 1_000_000_000.0,
+        "vSolInBondingCurve": 30.0,
+        "is_mayhem_mode": false,
+        "pool": "pump"
+    })
+    .to_string()
+}
+
+/// Account metas in the order the Pump.fun instruction lists them, all read-only
+/// except the signing user.
+fn pump_instruction(data: Vec<u8>, accounts: &[Pubkey], user: Pubkey) -> Instruction {
+    Instruction {
+        program_id: PUMP_PROGRAM.parse().unwrap(),
+        accounts: accounts
+            .iter()
+            .map(|key| {
+                if *key == user {
+                    AccountMeta::new(*key, true)
+                } else {
+                    AccountMeta::new_readonly(*key, false)
+                }
+            })
+            .collect(),
+        data,
+    }
+}
+
+/// Legacy `create` exactly as the program takes it: name, symbol, uri, creator.
+fn legacy_create_instruction(
+    name: &str,
+    mint: Pubkey,
+    curve: Pubkey,
+    user: Pubkey,
+    creator: Pubkey,
+) -> Instruction {
+    let mut data = CREATE_IX_DISCRIMINATOR.to_vec();
+    put(&mut data, name.to_string());
+    put(&mut data, "OLD".to_string());
+    put(&mut data, "https://ipfs.io/ipfs/legacy-ix".to_string());
+    put(&mut data, creator);
+
+    // mint, mint_authority, bonding_curve, associated_bonding_curve, global,
+    // mpl_token_metadata, metadata, user, then programs and the event authority
+    let mut accounts = vec![mint, Pubkey::new_unique(), curve, Pubkey::new_unique()];
+    accounts.extend((0..3).map(|_| Pubkey::new_unique()));
+    accounts.push(user);
+    accounts.extend((0..6).map(|_| Pubkey::new_unique()));
+
+    pump_instruction(data, &accounts, user)
+}
+
+/// `create_v2`: name, symbol, uri, creator, mayhem mode.
+fn create_v2_instruction(
+    mint: Pubkey,
+    curve: Pubkey,
+    user: Pubkey,
+    creator: Pubkey,
+) -> Instruction {
+    let mut data = CREATE_V2_IX_DISCRIMINATOR.to_vec();
+    put(&mut data, "Shred".to_string());
+    put(&mut data, "SHR".to_string());
+    put(&mut data, "https://ipfs.io/ipfs/v2-ix".to_string());
+    put(&mut data, creator);
+    put(&mut data, false);
+
+    // mint, mint_authority, bonding_curve, associated_bonding_curve, global,
+    // user, system_program, token_program, then the rest
+    let mut accounts = vec![mint, Pubkey::new_unique(), curve, Pubkey::new_unique()];
+    accounts.push(Pubkey::new_unique());
+    accounts.push(user);
+    accounts.push(Pubkey::new_unique());
+    accounts.push(TOKEN_2022_PROGRAM.parse().unwrap());
+    accounts.extend((0..6).map(|_| Pubkey::new_unique()));
+
+    pump_instruction(data, &accounts, user)
+}
+
+/// `sell` of `amount` tokens, accepting at least `min_sol`.
+fn sell_instruction(
+    mint: Pubkey,
+    curve: Pubkey,
+    user: Pubkey,
+    amount: u64,
+    min_sol: u64,
+) -> Instruction {
+    let mut instruction = buy_instruction(mint, curve, user, amount, min_sol);
+    instruction.data[..8].copy_from_slice(&SELL_IX_DISCRIMINATOR);
+    instruction
+}
+
+/// `buy` of `amount` tokens, paying at most `max_sol`.
+fn buy_instruction(
+    mint: Pubkey,
+    curve: Pubkey,
+    user: Pubkey,
+    amount: u64,
+    max_sol: u64,
+) -> Instruction {
+    let mut data = BUY_IX_DISCRIMINATOR.to_vec();
+    put(&mut data, amount);
+    put(&mut data, max_sol);
+
+    // global, fee_recipient, mint, bonding_curve, associated_bonding_curve,
+    // associated_user, user, then programs, creator vault and event authority
+    let mut accounts = vec![Pubkey::new_unique(), Pubkey::new_unique(), mint, curve];
+    accounts.extend((0..2).map(|_| Pubkey::new_unique()));
+    accounts.push(user);
+    accounts.extend((0..5).map(|_| Pubkey::new_unique()));
+
+    pump_instruction(data, &accounts, user)
+}
+
+/// Logs of a top-level Pump.fun instruction that emitted `data`.
+fn pump_invocation(data: String) -> Vec<String> {
+    vec![
+        format!("Program {} invoke [1]", PUMP_PROGRAM),
+        data,
+        format!("Program {} success", PUMP_PROGRAM),
+    ]
+}
+
+/// Bincode entries holding one transaction, as the shredstream sends them.
+fn shred_entries(instructions: &[Instruction], payer: Pubkey, signature: Signature) -> Vec<u8> {
+    let message = Message::new(instructions, Some(&payer));
+    let transaction = VersionedTransaction {
+        signatures: vec![signature],
+        message: VersionedMessage::Legacy(message),
+    };
+    let entries = vec![Entry {
+        num_hashes: 1,
+        hash: Hash::default(),
+        transactions: vec![transaction],
+    }];
+    bincode::serialize(&entries).unwrap()
+}
+
+/// Answers of the create flow's lookups, without IPFS, Twitter or a database.
+#[derive(Default)]
+struct StubLookups {
+    metadata: Option<Metadata>,
+    /// Names of tokens already in the database
+    stored: Vec<String>,
+}
+
+impl CreateLookups for StubLookups {
+    fn metadata<'a>(&'a self, _uri: &'a str) -> BoxFuture<'a, Option<Metadata>> {
+        Box::pin(async move { self.metadata.clone() })
+    }
+
+    fn token_exists<'a>(&'a self, create: &'a CreateEvent) -> BoxFuture<'a, bool> {
+        Box::pin(async move { self.stored.contains(&create.name) })
+    }
+
+    fn community<'a>(&'a self, _id: &'a str) -> BoxFuture<'a, Option<CommunityInfo>> {
+        Box::pin(async { None })
+    }
+
+    fn dev_performance<'a>(
+        &'a self,
+        _creator_id: &'a str,
+        _mint: &'a str,
+    ) -> BoxFuture<'a, Option<DevPerformance>> {
+        Box::pin(async { None })
+    }
+}
+
+fn metadata(description: &str) -> Metadata {
+    Metadata {
+        name: "Meta".to_string(),
+        symbol: "MTA".to_string(),
+        description: Some(description.to_string()),
+        twitter: None,
+        website: None,
+        image: Some(format!("https://ipfs.io/ipfs/{}.png", description)),
+    }
+}
+
+async fn replay(path: &Path, kind: SourceKind) -> Vec<EventEnvelope> {
+    let (tx, mut rx) = mpsc::channel(64);
+    Box::new(ReplaySource::new(path, kind).speed(0.0))
+        .run(tx)
+        .await;
+
+    let mut envelopes = vec![];
+    while let Ok(envelope) = rx.try_recv() {
+        envelopes.push(envelope);
+    }
+    envelopes
+}
+
+#[tokio::test]
+async fn trade_logs_decode_into_buys_and_sells() {
+    let path = capture_path("trades");
+    let mint = Pubkey::new_unique();
+    let user = Pubkey::new_unique();
+    let signature = Signature::from([7u8; 64]);
+
+    let buy = Trade {
+        mint,
+        sol_amount: 1_000_000_000,
+        token_amount: 34_000_000_000_000,
+        is_buy: true,
+        user,
+        virtual_sol_reserves: 31_000_000_000,
+        virtual_token_reserves: 1_039_000_000_000_000,
+    };
+    let sell = Trade {
+        is_buy: false,
+        ..buy
+    };
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(
+        42,
+        &signature,
+        false,
+        &["Program log: Instruction: Buy".to_string(), trade_log(&buy)],
+    ));
+    capture.logs(&logs_notification(
+        43,
+        &signature,
+        false,
+        &[trade_log(&sell)],
+    ));
+    capture.close();
+
+    let envelopes = replay(&path, SourceKind::Logs).await;
+    assert_eq!(envelopes.len(), 2);
+
+    let first = &envelopes[0];
+    assert_eq!(first.source, SourceKind::Logs);
+    assert_eq!(first.slot, Some(42));
+    assert_eq!(first.signature, Some(signature));
+    assert!(!first.failed);
+
+    let Event::Buy(decoded) = &first.event else {
+        panic!("expected a buy, got {:?}", first.event);
+    };
+    // Trades are keyed by the PumpSwap pool the curve migrates into
+    assert_eq!(decoded.mint, pool_pda(&mint).0);
+    assert_eq!(decoded.sol_amount, buy.sol_amount);
+    assert_eq!(decoded.token_amount, buy.token_amount);
+    assert_eq!(decoded.user, user);
+    assert_eq!(
+        decoded.virtual_sol_reserves_before,
+        buy.virtual_sol_reserves
+    );
+    assert_eq!(decoded.virtual_token_reserves, buy.virtual_token_reserves);
+    assert!(!decoded.estimated);
+
+    assert!(matches!(envelopes[1].event, Event::Sell(_)));
+    assert_eq!(envelopes[1].slot, Some(43));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn failed_transactions_are_dropped() {
+    let path = capture_path("failed");
+    let trade = Trade {
+        mint: Pubkey::new_unique(),
+        sol_amount: 1,
+        token_amount: 1,
+        is_buy: true,
+        user: Pubkey::new_unique(),
+        virtual_sol_reserves: 30_000_000_000,
+        virtual_token_reserves: 1_073_000_000_000_000,
+    };
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(
+        1,
+        &Signature::from([1u8; 64]),
+        true,
+        &[trade_log(&trade)],
+    ));
+    capture.close();
+
+    assert!(replay(&path, SourceKind::Logs).await.is_empty());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn legacy_and_v2_creates_decode() {
+    let path = capture_path("creates");
+    let (legacy_mint, v2_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (curve, user) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(
+        5,
+        &Signature::from([2u8; 64]),
+        false,
+        &[legacy_create_log("First", legacy_mint, curve, user)],
+    ));
+    capture.logs(&logs_notification(
+        6,
+        &Signature::from([3u8; 64]),
+        false,
+        &[create_v2_log(v2_mint, curve, user)],
+    ));
+    capture.close();
+
+    let envelopes = replay(&path, SourceKind::Logs).await;
+    assert_eq!(envelopes.len(), 2);
+
+    let Event::Create(legacy) = &envelopes[0].event else {
+        panic!("expected a create, got {:?}", envelopes[0].event);
+    };
+    assert_eq!(legacy.name, "First");
+    assert_eq!(legacy.mint, legacy_mint);
+    assert_eq!(legacy.bonding_curve, curve);
+    assert_eq!(legacy.user, user);
+    assert!(!legacy.token_2022);
+
+    let Event::Create(v2) = &envelopes[1].event else {
+        panic!("expected a create, got {:?}", envelopes[1].event);
+    };
+    assert_eq!(v2.name, "Second");
+    assert_eq!(v2.symbol, "TWO");
+    assert_eq!(v2.mint, v2_mint);
+    assert_eq!(v2.timestamp, 1_700_000_000);
+    assert!(v2.token_2022);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn legacy_create_instruction_decodes_from_shreds() {
+    let path = capture_path("shred-legacy-create");
+    let (mint, curve) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (user, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let signature = Signature::from([9u8; 64]);
+
+    let create = legacy_create_instruction("Legacy", mint, curve, user, creator);
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.shred(77, &shred_entries(&[create], user, signature));
+    capture.close();
+
+    let envelopes = replay(&path, SourceKind::Shredstream).await;
+    assert_eq!(envelopes.len(), 1);
+    assert_eq!(envelopes[0].source, SourceKind::Shredstream);
+    assert_eq!(envelopes[0].slot, Some(77));
+    assert_eq!(envelopes[0].signature, Some(signature));
+
+    let Event::Create(decoded) = &envelopes[0].event else {
+        panic!("expected a create, got {:?}", envelopes[0].event);
+    };
+    assert_eq!(decoded.name, "Legacy");
+    assert_eq!(decoded.symbol, "OLD");
+    assert_eq!(decoded.uri, "https://ipfs.io/ipfs/legacy-ix");
+    assert_eq!(decoded.mint, mint);
+    assert_eq!(decoded.bonding_curve, curve);
+    // The creator argument wins over the signer
+    assert_eq!(decoded.user, creator);
+    assert!(!decoded.token_2022);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn complete_takes_reserves_from_the_final_trade() {
+    let path = capture_path("complete");
+    let (mint, curve, user) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let trade = Trade {
+        mint,
+        sol_amount: 2_000_000_000,
+        token_amount: 10_000_000_000_000,
+        is_buy: true,
+        user,
+        virtual_sol_reserves: 115_005_359_056,
+        virtual_token_reserves: 279_900_000_000_000,
+    };
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(
+        9,
+        &Signature::from([4u8; 64]),
+        false,
+        &[trade_log(&trade), complete_log(mint, curve, user)],
+    ));
+    capture.close();
+
+    let envelopes = replay(&path, SourceKind::Logs).await;
+    assert_eq!(envelopes.len(), 2);
+
+    let Event::Complete(complete) = &envelopes[1].event else {
+        panic!("expected complete, got {:?}", envelopes[1].event);
+    };
+    assert_eq!(complete.mint, mint);
+    assert_eq!(complete.pool, pool_pda(&mint).0);
+    assert_eq!(complete.virtual_sol_reserves, trade.virtual_sol_reserves);
+    assert_eq!(
+        complete.virtual_token_reserves,
+        trade.virtual_token_reserves
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn pumpportal_creates_replay_and_other_kinds_are_skipped() {
+    let path = capture_path("pumpportal");
+    let mint = Pubkey::new_unique();
+    let signature = Signature::from([5u8; 64]);
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.pumpportal(&pumpportal_create(mint, &signature));
+    capture.pumpportal(r#"{"message":"Successfully subscribed to token creation events."}"#);
+    capture.logs(&logs_notification(1, &signature, false, &[]));
+    capture.close();
+
+    let envelopes = replay(&path, SourceKind::PumpPortal).await;
+    assert_eq!(envelopes.len(), 1);
+    assert_eq!(envelopes[0].source, SourceKind::PumpPortal);
+    assert_eq!(envelopes[0].signature, Some(signature));
+
+    let Event::Create(create) = &envelopes[0].event else {
+        panic!("expected a create, got {:?}", envelopes[0].event);
+    };
+    assert_eq!(create.mint, mint);
+    assert_eq!(create.name, "Portal");
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn multiplexer_dedups_creates_across_sources() {
+    let path = capture_path("mux");
+    let mint = Pubkey::new_unique();
+    let signature = Signature::from([6u8; 64]);
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(
+        3,
+        &signature,
+        false,
+        &[legacy_create_log(
+            "Dup",
+            mint,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        )],
+    ));
+    capture.pumpportal(&pumpportal_create(mint, &signature));
+    capture.close();
+
+    let mut mux = Multiplexer::new(Duration::from_millis(200));
+    mux.add(ReplaySource::new(path.clone(), SourceKind::Logs).speed(0.0));
+    mux.add(ReplaySource::new(path.clone(), SourceKind::PumpPortal).speed(0.0));
+
+    let mut creates = vec![];
+    mux.run(|envelope| {
+        if let Event::Create(create) = envelope.event {
+            creates.push(create.mint);
+        }
+        async {}
+    })
+    .await;
+
+    assert_eq!(creates, vec![mint]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn parse_failures_are_counted_by_kind() {
+    let path = capture_path("parse-errors");
+    let signature = Signature::from([8u8; 64]);
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(
+        1,
+        &signature,
+        false,
+        &[
+            // Trade discriminator with a truncated body
+            program_data(TRADE_DISCRIMINATOR, vec![1, 2, 3]),
+            program_data([9u8; 8], vec![]),
+            "Program data: !!!".to_string(),
+            format!("Program data: {}", BASE64_STANDARD.encode([1u8, 2])),
+        ],
+    ));
+    capture.close();
+
+    let source = ReplaySource::new(path.clone(), SourceKind::Logs).speed(0.0);
+    let stats = source.parse_stats();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    Box::new(source).run(tx).await;
+    assert!(rx.try_recv().is_err());
+
+    let counts = stats.snapshot();
+    assert_eq!(counts.borsh_failed, vec![(TRADE_DISCRIMINATOR, 1)]);
+    assert_eq!(counts.unknown_discriminator, 1);
+    assert_eq!(counts.base64, 1);
+    assert_eq!(counts.too_short, 1);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn ignored_and_foreign_program_data_is_not_counted() {
+    let path = capture_path("parse-ignored");
+    let signature = Signature::from([9u8; 64]);
+    let other_program = Pubkey::new_unique();
+
+    // sha256("event:CollectCreatorFeeEvent")[..8]
+    let mut logs = pump_invocation(program_data([122, 2, 127, 1, 14, 191, 12, 175], vec![]));
+    logs.extend([
+        format!("Program {} invoke [1]", other_program),
+        program_data([9u8; 8], vec![]),
+        format!("Program {} success", other_program),
+    ]);
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(1, &signature, false, &logs));
+    capture.close();
+
+    let source = ReplaySource::new(path.clone(), SourceKind::Logs).speed(0.0);
+    let stats = source.parse_stats();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    Box::new(source).run(tx).await;
+    assert!(rx.try_recv().is_err());
+
+    let counts = stats.snapshot();
+    assert_eq!(counts.unknown_discriminator, 0);
+    assert!(counts.borsh_failed.is_empty());
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn multiplexer_keeps_buys_of_one_transaction_apart() {
+    let path = capture_path("mux-bundle");
+    let mint = Pubkey::new_unique();
+    let signature = Signature::from([10u8; 64]);
+    let buy = Trade {
+        mint,
+        sol_amount: 1_000_000_000,
+        token_amount: 34_000_000_000_000,
+        is_buy: true,
+        user: Pubkey::new_unique(),
+        virtual_sol_reserves: 31_000_000_000,
+        virtual_token_reserves: 1_039_000_000_000_000,
+    };
+
+    // Two snipes of the same token in one transaction
+    let mut logs = pump_invocation(trade_log(&buy));
+    logs.extend(pump_invocation(trade_log(&buy)));
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.logs(&logs_notification(11, &signature, false, &logs));
+    capture.close();
+
+    let mut mux = Multiplexer::new(Duration::from_millis(200));
+    mux.add(ReplaySource::new(path.clone(), SourceKind::Logs).speed(0.0));
+
+    let mut buys = vec![];
+    mux.run(|envelope| {
+        buys.push(envelope.instruction);
+        async {}
+    })
+    .await;
+
+    assert_eq!(buys, vec![Some(0), Some(1)]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn landed_trades_replace_their_estimate() {
+    let path = capture_path("mux-estimate");
+    let (mint, curve, user) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let signature = Signature::from([11u8; 64]);
+    let landed = Trade {
+        mint,
+        sol_amount: 1_000_000_000,
+        token_amount: 34_000_000_000_000,
+        is_buy: true,
+        user,
+        virtual_sol_reserves: 31_000_000_000,
+        virtual_token_reserves: 1_039_000_000_000_000,
+    };
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.shred(
+        20,
+        &shred_entries(
+            &[legacy_create_instruction("Est", mint, curve, user, user)],
+            user,
+            Signature::from([12u8; 64]),
+        ),
+    );
+    capture.shred(
+        21,
+        &shred_entries(
+            &[buy_instruction(mint, curve, user, landed.token_amount, 2_000_000_000)],
+            user,
+            signature,
+        ),
+    );
+    capture.logs(&logs_notification(
+        21,
+        &signature,
+        false,
+        &pump_invocation(trade_log(&landed)),
+    ));
+    capture.close();
+
+    let mut mux = Multiplexer::new(Duration::from_secs(5));
+    mux.add(ReplaySource::new(path.clone(), SourceKind::Shredstream).speed(0.0));
+    mux.add(ReplaySource::new(path.clone(), SourceKind::Logs).speed(0.0));
+
+    let mut buys = vec![];
+    mux.run(|envelope| {
+        if let Event::Buy(buy) = envelope.event {
+            buys.push((buy.estimated, envelope.replaces_estimate));
+        }
+        async {}
+    })
+    .await;
+
+    // The estimate first, the landed trade still goes through after it
+    assert_eq!(buys, vec![(true, false), (false, true)]);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn shred_decode_failures_are_counted_by_discriminator() {
+    let path = capture_path("shred-parse-errors");
+    let user = Pubkey::new_unique();
+
+    // `create_v2` whose arguments stop after the name
+    let mut data = CREATE_V2_IX_DISCRIMINATOR.to_vec();
+    put(&mut data, "Cut".to_string());
+    let truncated = pump_instruction(data, &[Pubkey::new_unique(), user], user);
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.shred(
+        1,
+        &shred_entries(&[truncated], user, Signature::from([13u8; 64])),
+    );
+    capture.shred(2, &[1, 2, 3]);
+    capture.close();
+
+    let source = ReplaySource::new(path.clone(), SourceKind::Shredstream).speed(0.0);
+    let stats = source.parse_stats();
+
+    let (tx, mut rx) = mpsc::channel(8);
+    Box::new(source).run(tx).await;
+    assert!(rx.try_recv().is_err());
+
+    let counts = stats.snapshot();
+    assert_eq!(counts.borsh_failed, vec![(CREATE_V2_IX_DISCRIMINATOR, 1)]);
+    assert_eq!(counts.entries, 1);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn shred_capture_replays_creates_and_estimated_trades() {
+    let path = capture_path("shred-fixture");
+    let (mint, curve) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (user, creator) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (bought, sold) = (34_000_000_000_000u64, 10_000_000_000_000u64);
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    capture.shred(
+        30,
+        &shred_entries(
+            &[
+                create_v2_instruction(mint, curve, user, creator),
+                buy_instruction(mint, curve, user, bought, 5_000_000_000),
+            ],
+            user,
+            Signature::from([14u8; 64]),
+        ),
+    );
+    capture.shred(
+        31,
+        &shred_entries(
+            &[sell_instruction(mint, curve, user, sold, 0)],
+            user,
+            Signature::from([15u8; 64]),
+        ),
+    );
+    // A curve created before the capture started has no estimate
+    capture.shred(
+        32,
+        &shred_entries(
+            &[buy_instruction(
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                user,
+                bought,
+                5_000_000_000,
+            )],
+            user,
+            Signature::from([16u8; 64]),
+        ),
+    );
+    capture.close();
+
+    let envelopes = replay(&path, SourceKind::Shredstream).await;
+    assert_eq!(envelopes.len(), 3);
+
+    let Event::Create(create) = &envelopes[0].event else {
+        panic!("expected a create, got {:?}", envelopes[0].event);
+    };
+    assert_eq!(create.name, "Shred");
+    assert_eq!(create.mint, mint);
+    assert_eq!(create.bonding_curve, curve);
+    assert_eq!(create.user, creator);
+    assert!(create.token_2022);
+    assert_eq!(envelopes[0].instruction, Some(0));
+
+    // Constant product from the initial reserves of a fresh curve
+    let (sol, tokens) = (30_000_000_000u128, 1_073_000_000_000_000u128);
+    let cost = sol * bought as u128 / (tokens - bought as u128);
+    let (sol, tokens) = (sol + cost, tokens - bought as u128);
+    let out = sol * sold as u128 / (tokens + sold as u128);
+
+    let Event::Buy(buy) = &envelopes[1].event else {
+        panic!("expected a buy, got {:?}", envelopes[1].event);
+    };
+    assert_eq!(envelopes[1].instruction, Some(1));
+    assert_eq!(buy.mint, pool_pda(&mint).0);
+    assert_eq!(buy.token_mint, mint);
+    assert_eq!(buy.token_amount, bought);
+    assert_eq!(buy.sol_amount, cost as u64);
+    assert_eq!(buy.user, user);
+    assert!(buy.estimated);
+
+    let Event::Sell(sell) = &envelopes[2].event else {
+        panic!("expected a sell, got {:?}", envelopes[2].event);
+    };
+    assert_eq!(envelopes[2].slot, Some(31));
+    assert_eq!(sell.token_amount, sold);
+    assert_eq!(sell.sol_amount, out as u64);
+    assert_eq!(sell.virtual_token_reserves, (tokens + sold as u128) as u64);
+    assert!(sell.estimated);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[tokio::test]
+async fn shred_creates_drive_the_create_flow() {
+    let path = capture_path("shred-create-flow");
+    let user = Pubkey::new_unique();
+    let fresh = Pubkey::new_unique();
+
+    let capture = CaptureWriter::open(&path).unwrap();
+    for (slot, name, mint) in [
+        (40, "Fresh", fresh),
+        // Same name and uri as the token just before, caught by the cache
+        (41, "Fresh", Pubkey::new_unique()),
+    ] {
+        capture.shred(
+            slot,
+            &shred_entries(
+                &[legacy_create_instruction(
+                    name,
+                    mint,
+                    Pubkey::new_unique(),
+                    user,
+                    user,
+                )],
+                user,
+                Signature::from([slot as u8; 64]),
+            ),
+        );
+    }
+    capture.close();
+
+    let flow = CreateFlow::new(
+        StubLookups {
+            metadata: Some(metadata("fresh")),
+            ..StubLookups::default()
+        },
+        Arc::new(Mutex::new(TokenCache::default())),
+    );
+
+    let mut tokens = vec![];
+    for envelope in replay(&path, SourceKind::Shredstream).await {
+        let Event::Create(create) = envelope.event else {
+            panic!("expected a create, got {:?}", envelope.event);
+        };
+        if let Some(token) = flow
+            .process(create, envelope.slot, envelope.signature, envelope.received)
+            .await
+        {
+            tokens.push(token);
+        }
+    }
+
+    assert_eq!(tokens.len(), 1);
+    let token = &tokens[0];
+    assert_eq!(token.mint, fresh);
+    assert_eq!(token.name, "Fresh");
+    assert_eq!(token.ticker, "OLD");
+    assert_eq!(token.dev, user);
+    assert_eq!(token.slot, Some(40));
+    assert_eq!(token.signature, Some(Signature::from([40u8; 64])));
+    assert_eq!(
+        token.metadata.as_ref().and_then(|m| m.description.as_deref()),
+        Some("fresh")
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+fn create_event(name: &str) -> CreateEvent {
+    CreateEvent {
+        name: name.to_string(),
+        symbol: "TST".to_string(),
+        uri: format!("https://ipfs.io/ipfs/{}", name),
+        mint: Pubkey::new_unique(),
+        bonding_curve: Pubkey::new_unique(),
+        user: Pubkey::new_unique(),
+        token_2022: true,
+        timestamp: 0,
+    }
+}
+
+#[tokio::test]
+async fn stored_and_stale_creates_are_not_broadcast() {
+    let flow = CreateFlow::new(
+        StubLookups {
+            metadata: Some(metadata("checked")),
+            stored: vec!["Stored".to_string()],
+        },
+        Arc::new(Mutex::new(TokenCache::default())),
+    );
+    assert!(flow
+        .process(create_event("Stored"), None, None, since_epoch())
+        .await
+        .is_none());
+
+    // Received at the epoch, far past the delay a create may have
+    assert!(flow
+        .process(create_event("Late"), None, None, Duration::ZERO)
+        .await
+        .is_none());
+    assert!(flow
+        .process(create_event("Fresh"), None, None, since_epoch())
+        .await
+        .is_some());
+}
+
+fn since_epoch() -> Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+}
@@ -0,0 +1,166 @@
+//! Dev wallet trades and rug detection.
+
+use solana_sdk::pubkey::Pubkey;
+use tokenir::{
+    bundler::Bundler,
+    constans::helper::pool_pda,
+    database::Database,
+    logs::{Event, SellEvent},
+    rug::{DevTracker, RugConfig},
+    Token,
+};
+
+const CREATED: i64 = 1_709_251_200;
+
+fn tracker() -> (DevTracker, Pubkey, Pubkey, Pubkey) {
+    let tracker = DevTracker::new(RugConfig::default());
+    let mint = Pubkey::new_unique();
+    let dev = Pubkey::new_unique();
+    tracker.watch(&mint, dev, CREATED);
+    (tracker, mint, pool_pda(&mint).0, dev)
+}
+
+#[test]
+fn only_dev_trades_are_counted() {
+    let (tracker, _, pool, dev) = tracker();
+
+    assert_eq!(
+        tracker.trade(&pool, &Pubkey::new_unique(), true, 5, 500, CREATED),
+        None
+    );
+    assert_eq!(
+        tracker.trade(&Pubkey::new_unique(), &dev, true, 5, 500, CREATED),
+        None
+    );
+
+    tracker.trade(&pool, &dev, true, 1_000, 100_000, CREATED);
+    let activity = tracker
+        .trade(&pool, &dev, false, 300, 20_000, CREATED + 600)
+        .unwrap();
+    assert_eq!(activity.bought_sol, 1_000);
+    assert_eq!(activity.sold_sol, 300);
+    assert_eq!(activity.bought_tokens, 100_000);
+    assert_eq!(activity.sold_tokens, 20_000);
+    assert_eq!(activity.first_sell_at, Some(CREATED + 600));
+    assert_eq!(activity.rugged_at, None);
+}
+
+#[test]
+fn dumping_inside_the_window_is_a_rug() {
+    let (tracker, _, pool, dev) = tracker();
+    tracker.trade(&pool, &dev, true, 1_000, 100_000, CREATED);
+
+    // Half is not more than the threshold
+    let activity = tracker
+        .trade(&pool, &dev, false, 400, 50_000, CREATED + 10)
+        .unwrap();
+    assert_eq!(activity.rugged_at, None);
+
+    let activity = tracker
+        .trade(&pool, &dev, false, 300, 30_000, CREATED + 20)
+        .unwrap();
+    assert_eq!(activity.first_sell_at, Some(CREATED + 10));
+    assert_eq!(activity.rugged_at, Some(CREATED + 20));
+    assert_eq!(activity.sold_percentage(), 80);
+}
+
+#[test]
+fn dumping_after_the_window_is_not() {
+    let (tracker, _, pool, dev) = tracker();
+    tracker.trade(&pool, &dev, true, 1_000, 100_000, CREATED);
+
+    let window = RugConfig::default().window;
+    let activity = tracker
+        .trade(&pool, &dev, false, 900, 100_000, CREATED + window + 1)
+        .unwrap();
+    assert_eq!(activity.rugged_at, None);
+}
+
+#[test]
+fn estimated_sells_are_left_out() {
+    let (tracker, mint, pool, dev) = tracker();
+    let sell = |estimated| {
+        Event::Sell(SellEvent {
+            mint: pool,
+            token_mint: mint,
+            sol_amount: 100,
+            token_amount: 1_000,
+            user: dev,
+            timestamp: CREATED + 5,
+            virtual_sol_reserves_before: 0,
+            virtual_sol_reserves_after: 0,
+            virtual_token_reserves: 0,
+            estimated,
+        })
+    };
+
+    assert!(tracker.record(&sell(true)).is_none());
+
+    // Sold without buying on the curve, which counts as selling everything
+    let (recorded_pool, activity) = tracker.record(&sell(false)).unwrap();
+    assert_eq!(recorded_pool, pool);
+    assert_eq!(activity.rugged_at, Some(CREATED + 5));
+}
+
+/// Needs a scratch Postgres database in `TEST_DATABASE_URL`, run with
+/// `cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "needs TEST_DATABASE_URL"]
+async fn rugs_count_into_the_dev_rug_rate() {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL not set");
+    let database = Database::new(url).await.unwrap();
+    database.initialize_tables().await.unwrap();
+
+    let tracker = DevTracker::default();
+    let bundler = Bundler::default();
+    let dev_address = Pubkey::new_unique().to_string();
+    let wallet = Pubkey::new_unique();
+
+    // Two tokens of one dev, the first one dumped
+    let mints: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+    for mint in &mints {
+        let token = Token::fresh(
+            "Test".to_string(),
+            "TST".to_string(),
+            wallet,
+            Pubkey::new_unique(),
+            None,
+            *mint,
+            false,
+            None,
+            None,
+        );
+        bundler.add_token(*mint, token.dbtoken(*mint), dev_address.clone());
+        tracker.watch(mint, wallet, CREATED);
+
+        let pool = pool_pda(mint).0;
+        let activity = tracker
+            .trade(&pool, &wallet, true, 1_000, 100_000, CREATED)
+            .unwrap();
+        bundler.add_dev_activity(pool, activity);
+    }
+    let pool = pool_pda(&mints[0]).0;
+    let activity = tracker
+        .trade(&pool, &wallet, false, 900, 100_000, CREATED + 30)
+        .unwrap();
+    bundler.add_dev_activity(pool, activity);
+    bundler.flush(&database).await.unwrap();
+
+    let (sold, first_sell, rugged): (i64, Option<i64>, Option<i64>) = sqlx::query_as(
+        "SELECT dev_sold_tokens, first_dev_sell_at, rugged_at FROM tokens WHERE mint = $1",
+    )
+    .bind(mints[0].to_string())
+    .fetch_one(database.connection())
+    .await
+    .unwrap();
+    assert_eq!(sold, 100_000);
+    assert_eq!(first_sell, Some(CREATED + 30));
+    assert_eq!(rugged, Some(CREATED + 30));
+
+    let (_, count, _, rugged) = database
+        .get_dev_median_ath_excluding(&dev_address, &Pubkey::new_unique().to_string())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!((count, rugged), (2, 1));
+}
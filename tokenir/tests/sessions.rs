@@ -0,0 +1,102 @@
+//! Per-user session limits and eviction.
+
+use std::time::Duration;
+use tokenir::sessions::{EvictionPolicy, SessionLimit, SessionRegistry};
+
+#[test]
+fn reject_new_refuses_sessions_past_the_limit() {
+    let registry = SessionRegistry::default();
+
+    let first = registry
+        .open(1, "aaaa", 2, EvictionPolicy::RejectNew)
+        .unwrap();
+    let _second = registry
+        .open(1, "aaaa", 2, EvictionPolicy::RejectNew)
+        .unwrap();
+    assert_eq!(
+        registry.open(1, "aaaa", 2, EvictionPolicy::RejectNew).err(),
+        Some(SessionLimit { max: 2 })
+    );
+    // Other users have their own seats
+    let _other = registry
+        .open(2, "bbbb", 2, EvictionPolicy::RejectNew)
+        .unwrap();
+    assert_eq!(registry.len(), 3);
+
+    // A seat frees up once a session ends
+    assert!(registry.remove(1, first.id));
+    assert!(!registry.remove(1, first.id));
+    let _third = registry
+        .open(1, "aaaa", 2, EvictionPolicy::RejectNew)
+        .unwrap();
+}
+
+#[test]
+fn dropped_handles_free_their_seat() {
+    let registry = SessionRegistry::default();
+
+    // An upgrade that never completes drops its handle unused
+    let pending = registry
+        .open(1, "aaaa", 1, EvictionPolicy::RejectNew)
+        .unwrap();
+    assert!(registry.open(1, "aaaa", 1, EvictionPolicy::RejectNew).is_err());
+
+    drop(pending);
+    assert!(registry.is_empty());
+    let _session = registry
+        .open(1, "aaaa", 1, EvictionPolicy::RejectNew)
+        .unwrap();
+    assert_eq!(registry.len(), 1);
+}
+
+#[tokio::test]
+async fn kick_oldest_closes_the_oldest_session() {
+    let registry = SessionRegistry::default();
+
+    let mut oldest = registry
+        .open(1, "aaaa", 1, EvictionPolicy::KickOldest)
+        .unwrap();
+    let mut newest = registry
+        .open(1, "aaaa", 1, EvictionPolicy::KickOldest)
+        .unwrap();
+
+    let reason = tokio::time::timeout(Duration::from_secs(1), oldest.closed())
+        .await
+        .expect("oldest session was not closed");
+    assert_eq!(reason, "replaced by a newer session");
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), newest.closed())
+            .await
+            .is_err()
+    );
+
+    // Already gone, its own cleanup finds nothing
+    assert!(!registry.remove(1, oldest.id));
+    let open = registry.snapshot();
+    assert_eq!(open.len(), 1);
+    assert_eq!(open[0].session_id, newest.id);
+}
+
+#[tokio::test]
+async fn closed_sessions_get_the_reason() {
+    let registry = SessionRegistry::default();
+
+    let mut first = registry
+        .open(1, "aaaa", 3, EvictionPolicy::RejectNew)
+        .unwrap();
+    let mut second = registry
+        .open(1, "aaaa", 3, EvictionPolicy::RejectNew)
+        .unwrap();
+    let mut other = registry
+        .open(2, "bbbb", 3, EvictionPolicy::RejectNew)
+        .unwrap();
+
+    assert!(registry.close(other.id, "disconnected by an admin"));
+    assert!(!registry.close(other.id, "disconnected by an admin"));
+    assert_eq!(other.closed().await, "disconnected by an admin");
+
+    assert_eq!(registry.close_user(1, "access revoked"), 2);
+    assert_eq!(first.closed().await, "access revoked");
+    assert_eq!(second.closed().await, "access revoked");
+    assert!(registry.is_empty());
+}
@@ -0,0 +1,61 @@
+//! Throttled live updates for tokens sent to clients.
+
+use solana_sdk::pubkey::Pubkey;
+use std::time::Duration;
+use tokenir::{constans::helper::pool_pda, updates::UpdateTracker, Token};
+
+fn token(mint: Pubkey) -> Token {
+    Token::fresh(
+        "Test".to_string(),
+        "TST".to_string(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        None,
+        mint,
+        true,
+        None,
+        None,
+    )
+}
+
+#[test]
+fn trades_between_drains_collapse_into_one_update() {
+    let tracker = UpdateTracker::new(Duration::from_secs(60));
+    let mint = Pubkey::new_unique();
+    let pool = pool_pda(&mint).0;
+    tracker.track(&token(mint));
+
+    // Nothing traded yet
+    assert!(tracker.drain().is_empty());
+
+    tracker.trade(&pool, true, 2_000_000_000, 12_000);
+    tracker.trade(&pool, true, 1_000_000_000, 20_000);
+    tracker.trade(&pool, false, 500_000_000, 15_000);
+
+    let updates = tracker.drain();
+    assert_eq!(updates.len(), 1);
+
+    let update = &updates[0];
+    assert_eq!(update.mint, mint);
+    assert_eq!(update.mcap, 15_000);
+    assert_eq!(update.ath, 20_000);
+    assert_eq!((update.buys, update.sells), (2, 1));
+    assert_eq!(update.volume, 3_500_000_000);
+
+    assert!(tracker.drain().is_empty());
+}
+
+#[test]
+fn untracked_and_idle_tokens_are_ignored() {
+    let tracker = UpdateTracker::new(Duration::ZERO);
+    let mint = Pubkey::new_unique();
+    tracker.track(&token(mint));
+
+    tracker.trade(&Pubkey::new_unique(), true, 1, 10_000);
+    assert!(tracker.drain().is_empty());
+
+    // Idle right away, so the first drain forgot it
+    assert!(tracker.is_empty());
+    tracker.trade(&pool_pda(&mint).0, true, 1, 10_000);
+    assert!(tracker.drain().is_empty());
+}
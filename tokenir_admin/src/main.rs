@@ -6,51 +6,89 @@ use serde::{Deserialize, Serialize};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use dotenv::dotenv;
 use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // --- Data Structures ---
 
 #[derive(Clone, Debug, Deserialize)]
 struct User {
     id: i32,
-    access_key: Option<String>,
+    key_prefix: Option<String>,
     hint: Option<String>,
-    admin: bool,
+    role: Option<String>,
     autobuy: bool,
+    #[serde(default = "default_max_sessions")]
+    max_sessions: i32,
+    expires_at: Option<i64>,
+    #[serde(default)]
+    disabled: bool,
 }
 
-#[derive(Serialize)]
-struct AddUserReq {
-    admin_key: String,
-    payload: AddUserPayload,
-}
+fn default_max_sessions() -> i32 { 1 }
 
 #[derive(Serialize)]
 struct AddUserPayload {
     provided_key: String,
     hint: String,
     autobuy: bool,
+    max_sessions: i32,
+    expires_at: Option<i64>,
+    role: Option<&'static str>,
 }
 
 #[derive(Serialize)]
 struct RemoveUserReq {
-    admin_key: String,
     user_id: i32,
 }
 
 #[derive(Serialize)]
-struct GetUsersReq {
-    admin_key: String,
+struct RotateKeyReq {
+    user_id: i32,
 }
 
 #[derive(Serialize)]
-struct RestartReq {
-    admin_key: String,
+struct DisableUserReq {
+    user_id: i32,
+    disabled: bool,
+}
+
+#[derive(Deserialize)]
+struct RotateKeyResp {
+    key: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct AuditEntry {
+    id: i64,
+    at: i64,
+    actor: Option<String>,
+    action: String,
+    target: Option<String>,
+    details: Option<String>,
+    ip: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AuditPage {
+    entries: Vec<AuditEntry>,
+    // Id to pass as `before` for the next older page, none at the end
+    next_before: Option<i64>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Tab {
+    Users,
+    Audit,
 }
 
 enum AppEvent {
     UsersFetched(Vec<User>),
     UserAdded,
     UserRemoved,
+    UserDisabled,
+    KeyRotated(i32, String),
+    // Page of entries, and whether it continues the loaded ones
+    AuditFetched(AuditPage, bool),
     ServerRestarted,
     Error(String),
 }
@@ -63,6 +101,28 @@ fn generate_random_string(length: usize) -> String {
         .collect()
 }
 
+fn now_secs() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+fn age_label(at: i64) -> String {
+    let secs = (now_secs() - at).max(0);
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3_599 => format!("{}m ago", secs / 60),
+        3_600..=86_399 => format!("{}h ago", secs / 3_600),
+        _ => format!("{}d ago", secs / 86_400),
+    }
+}
+
+fn expiry_label(expires_at: Option<i64>) -> String {
+    match expires_at {
+        None => "never".to_string(),
+        Some(at) if at <= now_secs() => "expired".to_string(),
+        Some(at) => format!("in {}d", (at - now_secs()) / 86_400),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), eframe::Error> {
     dotenv().ok();
@@ -87,7 +147,16 @@ struct AdminApp {
     new_user_key: String,
     new_user_hint: String,
     new_user_autobuy: bool,
+    new_user_max_sessions: i32,
+    new_user_expiry_days: i64,
+    // Admin API access of the new user, none for feed only
+    new_user_role: Option<&'static str>,
+    // The key a rotation returned, shown once
+    rotated_key: Option<(i32, String)>,
     users: Vec<User>,
+    tab: Tab,
+    audit: Vec<AuditEntry>,
+    audit_next_before: Option<i64>,
     status: String,
     is_loading: bool,
     tx: Sender<AppEvent>,
@@ -106,7 +175,14 @@ impl AdminApp {
             new_user_key: String::new(),
             new_user_hint: String::new(),
             new_user_autobuy: true,
+            new_user_max_sessions: 1,
+            new_user_expiry_days: 0,
+            new_user_role: None,
+            rotated_key: None,
             users: vec![],
+            tab: Tab::Users,
+            audit: vec![],
+            audit_next_before: None,
             status: "Ready.".to_string(),
             is_loading: false,
             tx,
@@ -125,11 +201,11 @@ impl AdminApp {
         self.status = "Fetching users...".to_string();
         let tx = self.tx.clone();
         let url = format!("{}/admin/users", self.api_url);
-        let body = GetUsersReq { admin_key: self.admin_key.clone() };
+        let admin_key = self.admin_key.clone();
 
         tokio::spawn(async move {
             let client = reqwest::Client::new();
-            match client.post(&url).json(&body).send().await {
+            match client.get(&url).bearer_auth(&admin_key).send().await {
                 Ok(resp) if resp.status().is_success() => {
                     if let Ok(users) = resp.json::<Vec<User>>().await {
                         let _ = tx.send(AppEvent::UsersFetched(users));
@@ -141,15 +217,48 @@ impl AdminApp {
         });
     }
 
+    // Newest entries, or the page older than `before`
+    fn fetch_audit(&mut self, before: Option<i64>) {
+        self.is_loading = true;
+        self.status = "Fetching audit log...".to_string();
+        let tx = self.tx.clone();
+        let mut url = format!("{}/admin/audit", self.api_url);
+        if let Some(before) = before {
+            url.push_str(&format!("?before={}", before));
+        }
+        let admin_key = self.admin_key.clone();
+
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            match client.get(&url).bearer_auth(&admin_key).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    match resp.json::<AuditPage>().await {
+                        Ok(page) => { let _ = tx.send(AppEvent::AuditFetched(page, before.is_some())); }
+                        Err(e) => { let _ = tx.send(AppEvent::Error(e.to_string())); }
+                    }
+                }
+                Ok(resp) => { let _ = tx.send(AppEvent::Error(resp.status().to_string())); }
+                Err(e) => { let _ = tx.send(AppEvent::Error(e.to_string())); }
+            }
+        });
+    }
+
+    fn refresh(&mut self) {
+        match self.tab {
+            Tab::Users => self.fetch_users(),
+            Tab::Audit => self.fetch_audit(None),
+        }
+    }
+
     fn restart_server(&mut self) {
         self.is_loading = true;
         let tx = self.tx.clone();
         let url = format!("{}/admin/restart", self.api_url);
-        let body = RestartReq { admin_key: self.admin_key.clone() };
+        let admin_key = self.admin_key.clone();
 
         tokio::spawn(async move {
             let client = reqwest::Client::new();
-            match client.post(&url).json(&body).send().await {
+            match client.post(&url).bearer_auth(&admin_key).send().await {
                 Ok(resp) if resp.status().is_success() => { let _ = tx.send(AppEvent::ServerRestarted); }
                 Ok(resp) => { let _ = tx.send(AppEvent::Error(format!("Fail: {}", resp.status()))); }
                 Err(e) => { let _ = tx.send(AppEvent::Error(e.to_string())); }
@@ -162,36 +271,256 @@ impl AdminApp {
         self.is_loading = true;
         let tx = self.tx.clone();
         let url = format!("{}/admin/add_user", self.api_url);
-        let body = AddUserReq {
-            admin_key: self.admin_key.clone(),
-            payload: AddUserPayload {
-                provided_key: self.new_user_key.clone(),
-                hint: self.new_user_hint.clone(),
-                autobuy: self.new_user_autobuy,
-            },
+        let admin_key = self.admin_key.clone();
+        let body = AddUserPayload {
+            provided_key: self.new_user_key.clone(),
+            hint: self.new_user_hint.clone(),
+            autobuy: self.new_user_autobuy,
+            max_sessions: self.new_user_max_sessions,
+            expires_at: (self.new_user_expiry_days > 0).then(|| now_secs() + self.new_user_expiry_days * 86_400),
+            role: self.new_user_role,
         };
         tokio::spawn(async move {
             let client = reqwest::Client::new();
-            match client.post(&url).json(&body).send().await {
+            match client.post(&url).bearer_auth(&admin_key).json(&body).send().await {
                 Ok(resp) if resp.status().is_success() => { let _ = tx.send(AppEvent::UserAdded); }
                 _ => { let _ = tx.send(AppEvent::Error("Failed to add".to_string())); }
             }
         });
     }
 
+    fn rotate_key(&mut self, user_id: i32) {
+        self.is_loading = true;
+        let tx = self.tx.clone();
+        let url = format!("{}/admin/rotate_key", self.api_url);
+        let admin_key = self.admin_key.clone();
+        let body = RotateKeyReq { user_id };
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            match client.post(&url).bearer_auth(&admin_key).json(&body).send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    match resp.json::<RotateKeyResp>().await {
+                        Ok(rotated) => { let _ = tx.send(AppEvent::KeyRotated(user_id, rotated.key)); }
+                        Err(e) => { let _ = tx.send(AppEvent::Error(e.to_string())); }
+                    }
+                }
+                _ => { let _ = tx.send(AppEvent::Error("Failed to rotate key".to_string())); }
+            }
+        });
+    }
+
+    fn set_disabled(&mut self, user_id: i32, disabled: bool) {
+        self.is_loading = true;
+        let tx = self.tx.clone();
+        let url = format!("{}/admin/disable_user", self.api_url);
+        let admin_key = self.admin_key.clone();
+        let body = DisableUserReq { user_id, disabled };
+        tokio::spawn(async move {
+            let client = reqwest::Client::new();
+            match client.post(&url).bearer_auth(&admin_key).json(&body).send().await {
+                Ok(resp) if resp.status().is_success() => { let _ = tx.send(AppEvent::UserDisabled); }
+                _ => { let _ = tx.send(AppEvent::Error("Failed to update user".to_string())); }
+            }
+        });
+    }
+
     fn remove_user(&mut self, user_id: i32) {
         self.is_loading = true;
         let tx = self.tx.clone();
         let url = format!("{}/admin/remove_user", self.api_url);
-        let body = RemoveUserReq { admin_key: self.admin_key.clone(), user_id };
+        let admin_key = self.admin_key.clone();
+        let body = RemoveUserReq { user_id };
         tokio::spawn(async move {
             let client = reqwest::Client::new();
-            match client.post(&url).json(&body).send().await {
+            match client.post(&url).bearer_auth(&admin_key).json(&body).send().await {
                 Ok(resp) if resp.status().is_success() => { let _ = tx.send(AppEvent::UserRemoved); }
                 _ => { let _ = tx.send(AppEvent::Error("Failed to remove".to_string())); }
             }
         });
     }
+
+    fn users_tab(&mut self, ui: &mut egui::Ui) {
+        // Responsive Add User Section
+        ui.group(|ui| {
+            ui.label(egui::RichText::new("Add New User").strong());
+            ui.horizontal_wrapped(|ui| {
+                // Responsive field widths: use a fraction of available width but clamp it
+                let input_width = (ui.available_width() * 0.25).max(120.0);
+
+                ui.add(egui::TextEdit::singleline(&mut self.new_user_key)
+                    .hint_text("Access Key")
+                    .desired_width(input_width));
+                
+                if ui.button("🎲").on_hover_text("Generate Random Key").clicked() {
+                    self.new_user_key = generate_random_string(32);
+                }
+
+                ui.add(egui::TextEdit::singleline(&mut self.new_user_hint)
+                    .hint_text("Hint (Optional)")
+                    .desired_width(input_width * 0.6));
+
+                ui.checkbox(&mut self.new_user_autobuy, "AutoBuy");
+
+                ui.label("Sessions:");
+                ui.add(egui::DragValue::new(&mut self.new_user_max_sessions).clamp_range(1..=16));
+
+                egui::ComboBox::from_id_source("new_user_role")
+                    .selected_text(self.new_user_role.unwrap_or("feed only"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.new_user_role, None, "feed only");
+                        for role in ["viewer", "operator", "admin"] {
+                            ui.selectable_value(&mut self.new_user_role, Some(role), role);
+                        }
+                    });
+
+                ui.label("Expires in days:");
+                ui.add(egui::DragValue::new(&mut self.new_user_expiry_days).clamp_range(0..=3650))
+                    .on_hover_text("0 = never");
+
+                if ui.button("➕ Add User").clicked() {
+                    self.add_user();
+                }
+            });
+        });
+
+        // Rotated keys are not stored in clear, this is the only chance to copy one
+        if let Some((user_id, key)) = self.rotated_key.clone() {
+            ui.add_space(6.0);
+            ui.group(|ui| {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(format!("New key of user {}:", user_id));
+                    ui.monospace(&key);
+                    if ui.small_button("📋").clicked() {
+                        ui.output_mut(|o| o.copied_text = key);
+                    }
+                    if ui.small_button("✖").on_hover_text("Hide").clicked() {
+                        self.rotated_key = None;
+                    }
+                });
+            });
+        }
+
+        ui.add_space(10.0);
+
+        // Responsive Scrollable Grid
+        ui.heading("Existing Users");
+        ui.separator();
+
+        egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+            egui::Grid::new("users_grid")
+                .striped(true)
+                .spacing([15.0, 8.0])
+                .min_col_width(50.0)
+                .show(ui, |ui| {
+                    // Headers
+                    ui.strong("ID");
+                    ui.strong("Key");
+                    ui.strong("Hint");
+                    ui.strong("Role");
+                    ui.strong("AutoBuy");
+                    ui.strong("Sessions");
+                    ui.strong("Expires");
+                    ui.strong("Actions");
+                    ui.end_row();
+
+                    for user in self.users.clone() {
+                        ui.label(user.id.to_string());
+
+                        // Only the prefix of a key is known, the rest is hashed
+                        ui.horizontal(|ui| {
+                            let prefix = user.key_prefix.clone().unwrap_or_default();
+                            if user.disabled {
+                                ui.colored_label(egui::Color32::GRAY, format!("{}... (disabled)", prefix));
+                            } else {
+                                ui.monospace(format!("{}...", prefix));
+                            }
+                        });
+
+                        ui.label(user.hint.clone().unwrap_or_default());
+
+                        if user.role.as_deref() == Some("admin") {
+                            ui.colored_label(egui::Color32::LIGHT_BLUE, "ADMIN");
+                            ui.colored_label(egui::Color32::GRAY, "FORCED");
+                            ui.label(user.max_sessions.to_string());
+                            ui.label(expiry_label(user.expires_at));
+                            if ui.button("🔑").on_hover_text("Rotate Key").clicked() {
+                                self.rotate_key(user.id);
+                            }
+                        } else {
+                            match &user.role {
+                                Some(role) => { ui.colored_label(egui::Color32::LIGHT_BLUE, role.to_uppercase()); }
+                                None => { ui.label("User"); }
+                            }
+                            if user.autobuy {
+                                ui.colored_label(egui::Color32::LIGHT_GREEN, "ON");
+                            } else {
+                                ui.label("OFF");
+                            }
+                            ui.label(user.max_sessions.to_string());
+                            ui.label(expiry_label(user.expires_at));
+                            ui.horizontal(|ui| {
+                                if ui.button("🔑").on_hover_text("Rotate Key").clicked() {
+                                    self.rotate_key(user.id);
+                                }
+                                let (icon, hover) = if user.disabled { ("▶", "Enable User") } else { ("⏸", "Disable User") };
+                                if ui.button(icon).on_hover_text(hover).clicked() {
+                                    self.set_disabled(user.id, !user.disabled);
+                                }
+                                if ui.button("🗑").on_hover_text("Delete User").clicked() {
+                                    self.remove_user(user.id);
+                                }
+                            });
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    fn audit_tab(&mut self, ui: &mut egui::Ui) {
+        egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
+            egui::Grid::new("audit_grid")
+                .striped(true)
+                .spacing([15.0, 6.0])
+                .min_col_width(50.0)
+                .show(ui, |ui| {
+                    ui.strong("When");
+                    ui.strong("Actor");
+                    ui.strong("Action");
+                    ui.strong("Target");
+                    ui.strong("Details");
+                    ui.strong("IP");
+                    ui.end_row();
+
+                    for entry in &self.audit {
+                        ui.label(age_label(entry.at)).on_hover_text(format!("#{} at {}", entry.id, entry.at));
+                        match &entry.actor {
+                            Some(actor) => { ui.monospace(format!("{}...", actor)); }
+                            None => { ui.label("-"); }
+                        }
+                        // Failed logins stand out
+                        if entry.action.ends_with("_failed") || entry.action.ends_with("_forbidden") {
+                            ui.colored_label(egui::Color32::LIGHT_RED, &entry.action);
+                        } else {
+                            ui.label(&entry.action);
+                        }
+                        ui.label(entry.target.as_deref().unwrap_or("-"));
+                        ui.label(entry.details.as_deref().unwrap_or("-"));
+                        ui.label(entry.ip.as_deref().unwrap_or("-"));
+                        ui.end_row();
+                    }
+                });
+
+            ui.add_space(6.0);
+            if let Some(before) = self.audit_next_before {
+                if ui.button("⬇ Load older").clicked() {
+                    self.fetch_audit(Some(before));
+                }
+            } else if !self.audit.is_empty() {
+                ui.small("End of the audit log");
+            }
+        });
+    }
 }
 
 impl eframe::App for AdminApp {
@@ -203,6 +532,21 @@ impl eframe::App for AdminApp {
                 AppEvent::UsersFetched(users) => { self.users = users; self.status = "Users loaded".to_string(); }
                 AppEvent::UserAdded => { self.new_user_key.clear(); self.fetch_users(); }
                 AppEvent::UserRemoved => { self.fetch_users(); }
+                AppEvent::UserDisabled => { self.fetch_users(); }
+                AppEvent::KeyRotated(user_id, key) => {
+                    self.status = format!("Key of user {} rotated, copy it now", user_id);
+                    self.rotated_key = Some((user_id, key));
+                    self.fetch_users();
+                }
+                AppEvent::AuditFetched(page, older) => {
+                    if older {
+                        self.audit.extend(page.entries);
+                    } else {
+                        self.audit = page.entries;
+                    }
+                    self.audit_next_before = page.next_before;
+                    self.status = "Audit log loaded".to_string();
+                }
                 AppEvent::ServerRestarted => { self.status = "Restarted!".to_string(); }
                 AppEvent::Error(e) => { self.status = format!("Error: {}", e); }
             }
@@ -220,7 +564,7 @@ impl eframe::App for AdminApp {
                 
                 ui.separator();
 
-                if ui.button("🔄 Refresh").clicked() { self.fetch_users(); }
+                if ui.button("🔄 Refresh").clicked() { self.refresh(); }
                 
                 if ui.add(egui::Button::new("🔌 Restart Server").fill(egui::Color32::from_rgb(100, 40, 40))).clicked() {
                     self.restart_server();
@@ -240,87 +584,19 @@ impl eframe::App for AdminApp {
 
         // --- MAIN CONTENT ---
         egui::CentralPanel::default().show(ctx, |ui| {
-            // Responsive Add User Section
-            ui.group(|ui| {
-                ui.label(egui::RichText::new("Add New User").strong());
-                ui.horizontal_wrapped(|ui| {
-                    // Responsive field widths: use a fraction of available width but clamp it
-                    let input_width = (ui.available_width() * 0.25).max(120.0);
-
-                    ui.add(egui::TextEdit::singleline(&mut self.new_user_key)
-                        .hint_text("Access Key")
-                        .desired_width(input_width));
-                    
-                    if ui.button("🎲").on_hover_text("Generate Random Key").clicked() {
-                        self.new_user_key = generate_random_string(32);
-                    }
-
-                    ui.add(egui::TextEdit::singleline(&mut self.new_user_hint)
-                        .hint_text("Hint (Optional)")
-                        .desired_width(input_width * 0.6));
-
-                    ui.checkbox(&mut self.new_user_autobuy, "AutoBuy");
-
-                    if ui.button("➕ Add User").clicked() {
-                        self.add_user();
-                    }
-                });
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, Tab::Users, "👥 Users");
+                let audit = ui.selectable_value(&mut self.tab, Tab::Audit, "📜 Audit");
+                if audit.clicked() && self.audit.is_empty() {
+                    self.fetch_audit(None);
+                }
             });
-
-            ui.add_space(10.0);
-
-            // Responsive Scrollable Grid
-            ui.heading("Existing Users");
             ui.separator();
 
-            egui::ScrollArea::both().auto_shrink([false, false]).show(ui, |ui| {
-                egui::Grid::new("users_grid")
-                    .striped(true)
-                    .spacing([15.0, 8.0])
-                    .min_col_width(50.0)
-                    .show(ui, |ui| {
-                        // Headers
-                        ui.strong("ID");
-                        ui.strong("Key");
-                        ui.strong("Hint");
-                        ui.strong("Role");
-                        ui.strong("AutoBuy");
-                        ui.strong("Actions");
-                        ui.end_row();
-
-                        for user in self.users.clone() {
-                            ui.label(user.id.to_string());
-
-                            // Key snippet with copy button
-                            ui.horizontal(|ui| {
-                                let key = user.access_key.clone().unwrap_or_default();
-                                ui.label(format!("{}...", &key[..4.min(key.len())]));
-                                if ui.small_button("📋").clicked() {
-                                    ui.output_mut(|o| o.copied_text = key);
-                                }
-                            });
-
-                            ui.label(user.hint.clone().unwrap_or_default());
-
-                            if user.admin {
-                                ui.colored_label(egui::Color32::LIGHT_BLUE, "ADMIN");
-                                ui.colored_label(egui::Color32::GRAY, "FORCED");
-                                ui.label("-");
-                            } else {
-                                ui.label("User");
-                                if user.autobuy {
-                                    ui.colored_label(egui::Color32::LIGHT_GREEN, "ON");
-                                } else {
-                                    ui.label("OFF");
-                                }
-                                if ui.button("🗑").on_hover_text("Delete User").clicked() {
-                                    self.remove_user(user.id);
-                                }
-                            }
-                            ui.end_row();
-                        }
-                    });
-            });
+            match self.tab {
+                Tab::Users => self.users_tab(ui),
+                Tab::Audit => self.audit_tab(ui),
+            }
         });
     }
-}
\ No newline at end of file
+}
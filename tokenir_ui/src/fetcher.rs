@@ -1,10 +1,16 @@
-use futures::StreamExt;
-use serde::{Deserialize, Serialize};
-use tokenir_ui::Token;
-use tokio_tungstenite::connect_async;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::sync::Arc;
+use tokenir_ui::{
+    Token, TokenUpdate,
+    health::{Backoff, ConnectionHealth},
+};
+use tokio::sync::watch;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
 
 pub struct Client {
     url: String,
+    health: Arc<ConnectionHealth>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -14,48 +20,142 @@ enum ServerMessage {
     ConnectionInfo { autobuy: bool, message: String },
     #[serde(rename = "NewToken")]
     NewToken { data: Token },
+    #[serde(rename = "TokenUpdate")]
+    TokenUpdate { data: TokenUpdate },
+    /// Replies to the subscribe messages
+    #[serde(rename = "subscribed")]
+    Subscribed { filters: usize },
+    #[serde(rename = "unsubscribed")]
+    Unsubscribed,
+    #[serde(rename = "error")]
+    Error { message: String },
+    /// Reconnected too late, some messages were lost
+    #[serde(rename = "gap_too_large")]
+    GapTooLarge {
+        resume_from: u64,
+        oldest: Option<u64>,
+    },
+}
+
+/// Feed position the server puts on every broadcast message.
+#[derive(Deserialize)]
+struct Sequenced {
+    seq: Option<u64>,
+}
+
+/// Feed messages come as JSON text or, with `encoding=msgpack`, as binary
+/// frames of the same shape.
+enum Frame<'a> {
+    Json(&'a str),
+    Msgpack(&'a [u8]),
+}
+
+impl Frame<'_> {
+    fn decode<T: DeserializeOwned>(&self) -> Result<T, String> {
+        match self {
+            Frame::Json(text) => serde_json::from_str(text).map_err(|err| err.to_string()),
+            Frame::Msgpack(bytes) => rmp_serde::from_slice(bytes).map_err(|err| err.to_string()),
+        }
+    }
 }
 
 impl Client {
-    pub fn new(url: String) -> Self {
-        Self { url }
+    pub fn new(url: String, health: Arc<ConnectionHealth>) -> Self {
+        Self { url, health }
     }
 
-    pub async fn subscribe<F, Fut>(&self, mut __func__: F) -> Result<(), std::io::Error>
+    /// `on_update` gets the live numbers of tokens `__func__` was given earlier.
+    /// `subscription` is sent on connect and again whenever it changes, so the
+    /// server only sends tokens that can pass the local filters.
+    pub async fn subscribe<F, Fut, U, UFut>(
+        &self,
+        mut subscription: watch::Receiver<String>,
+        mut on_update: U,
+        mut __func__: F,
+    ) -> Result<(), std::io::Error>
     where
         F: FnMut(Token, bool) -> Fut,
         Fut: Future<Output = ()>,
+        U: FnMut(TokenUpdate) -> UFut,
+        UFut: Future<Output = ()>,
     {
         let mut autobuy = false; // Store autobuy status
+        let mut backoff = Backoff::default();
+        // Last message seen, the server replays what came after on reconnect
+        let mut last_seq: Option<u64> = None;
 
         loop {
-            let ws_stream = match connect_async(&self.url).await {
+            self.health.connecting();
+
+            let url = match last_seq {
+                Some(seq) => format!("{}&resume_from={}", self.url, seq),
+                None => self.url.clone(),
+            };
+
+            let ws_stream = match connect_async(&url).await {
                 Ok((stream, _)) => {
                     println!("[client] Connected to WebSocket");
+                    self.health.connected();
+                    backoff.connected();
                     stream
                 }
                 Err(e) => {
-                    eprintln!("[client] Connection failed: {}, retrying in 5s...", e);
-                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    let delay = backoff.next_delay();
+                    eprintln!(
+                        "[client] Connection failed: {}, retrying in {}ms...",
+                        e,
+                        delay.as_millis()
+                    );
+                    self.health.disconnected(&e);
+                    tokio::time::sleep(delay).await;
                     continue;
                 }
             };
 
-            let (_, mut __read__) = ws_stream.split();
+            let (mut write, mut __read__) = ws_stream.split();
+            let mut reason = String::from("connection closed");
 
-            while let Some(msg) = __read__.next().await {
-                let msg: tokio_tungstenite::tungstenite::Message = match msg {
+            let message = subscription.borrow_and_update().clone();
+            if let Err(err) = write.send(Message::Text(message.into())).await {
+                eprintln!("[client] Subscribe failed: {}", err);
+            }
+
+            loop {
+                let msg = tokio::select! {
+                    msg = __read__.next() => match msg {
+                        Some(msg) => msg,
+                        None => break,
+                    },
+                    Ok(()) = subscription.changed() => {
+                        let message = subscription.borrow_and_update().clone();
+                        if let Err(err) = write.send(Message::Text(message.into())).await {
+                            reason = err.to_string();
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                let msg: Message = match msg {
                     Ok(msg) => msg,
                     Err(err) => {
                         eprintln!("[client] Message error: {}", err);
+                        reason = err.to_string();
                         continue;
                     }
                 };
+                self.health.message();
 
-                let text = msg.to_string();
+                let frame = match &msg {
+                    Message::Text(text) => Frame::Json(text.as_str()),
+                    Message::Binary(bytes) => Frame::Msgpack(bytes),
+                    _ => continue,
+                };
 
-                // Try to parse as ServerMessage first
-                match serde_json::from_str::<ServerMessage>(&text) {
+                if let Ok(Sequenced { seq: Some(seq) }) = frame.decode() {
+                    last_seq = Some(seq);
+                }
+
+                match frame.decode::<ServerMessage>() {
                     Ok(ServerMessage::ConnectionInfo {
                         autobuy: ab,
                         message,
@@ -66,20 +166,41 @@ impl Client {
                     Ok(ServerMessage::NewToken { data }) => {
                         __func__(data, autobuy).await;
                     }
-                    Err(_) => {
-                        // Fallback: try parsing as Token directly (for backward compatibility)
-                        match serde_json::from_str::<Token>(&text) {
-                            Ok(token) => __func__(token, autobuy).await,
-                            Err(err) => {
-                                eprintln!("[client] Failed to parse message: {}", err);
-                            }
-                        }
+                    Ok(ServerMessage::TokenUpdate { data }) => {
+                        on_update(data).await;
+                    }
+                    Ok(ServerMessage::Subscribed { filters }) => {
+                        println!("[client] Server filtering with {} filters", filters);
+                    }
+                    Ok(ServerMessage::Unsubscribed) => {
+                        println!("[client] Server filtering off");
+                    }
+                    Ok(ServerMessage::Error { message }) => {
+                        eprintln!("[client] Server rejected a message: {}", message);
+                    }
+                    Ok(ServerMessage::GapTooLarge {
+                        resume_from,
+                        oldest,
+                    }) => {
+                        eprintln!(
+                            "[client] Missed messages after {}, server only has from {:?}",
+                            resume_from, oldest
+                        );
+                    }
+                    Err(err) => {
+                        eprintln!("[client] Failed to parse message: {}", err);
                     }
                 }
             }
 
-            eprintln!("[client] Connection closed, reconnecting in 5s...");
-            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+            self.health.disconnected(&reason);
+
+            let delay = backoff.next_delay();
+            eprintln!(
+                "[client] Connection closed, reconnecting in {}ms...",
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
         }
     }
 }
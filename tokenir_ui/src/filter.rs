@@ -37,6 +37,24 @@ impl FilterSet {
         }
     }
 
+    /// The `/ws` subscribe message with the part of these filters the server
+    /// can apply for us. The server ANDs what it gets, while `matches` only
+    /// gates tokens whose dev has a record, on the dev market cap and rug rate.
+    /// So only those two go, and new devs are let through.
+    pub fn subscribe_message(&self) -> String {
+        let filters: HashMap<&Tag, &Filters> = self
+            .filters
+            .iter()
+            .filter(|(tag, _)| matches!(tag, Tag::AverageDevMarketCap | Tag::RugRate))
+            .collect();
+
+        serde_json::json!({
+            "type": "subscribe",
+            "filters": { "filters": filters, "new_devs_pass": true },
+        })
+        .to_string()
+    }
+
     pub fn add_filter(&mut self, tag: Tag, filter: Filters) {
         self.filters.insert(tag, filter);
     }
@@ -49,6 +67,7 @@ impl FilterSet {
         let mut mcap_pass = None;
         let mut migration_pass = None;
         let mut token_count_pass = None;
+        let mut rug_pass = None;
 
         for (tag, filter) in &self.filters {
             match (tag, filter) {
@@ -65,6 +84,9 @@ impl FilterSet {
                 (Tag::TokenCount, Filters::TokenCount(_)) => {
                     token_count_pass = Some(filter.filter(token, average_mcap.unwrap_or(0)));
                 }
+                (Tag::RugRate, Filters::RugRate(_)) => {
+                    rug_pass = Some(filter.filter(token, average_mcap.unwrap_or(0)));
+                }
 
                 _ => (),
             }
@@ -73,10 +95,12 @@ impl FilterSet {
         let mcap_ok = mcap_pass.unwrap_or(false);
         let migration_ok = migration_pass.unwrap_or(false);
         let token_count_ok = token_count_pass.unwrap_or(false);
+        // Only ever rules tokens out, passes when not set
+        let rug_ok = rug_pass.unwrap_or(true);
 
         let result =
             mcap_ok || ((migration_ok && token_count_ok) && token.dev_performance.is_none());
-        result
+        result && rug_ok
     }
 }
 
@@ -85,6 +109,7 @@ pub enum Tag {
     AverageDevMarketCap,
     MigrationPercentage,
     TokenCount,
+    RugRate,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
@@ -92,6 +117,8 @@ pub enum Filters {
     AverageDevMarketCap(Range<u64>),
     TokenCount(Range<u64>),
     MigrationPercentage(Range<u64>),
+    /// Share of the dev's tokens they dumped right after creation, 0..100
+    RugRate(Range<u64>),
 }
 
 impl Filters {
@@ -124,6 +151,14 @@ impl Filters {
                     return range.contains(&0);
                 }
             }
+
+            Self::RugRate(range) => {
+                let rug_rate = token
+                    .dev_performance
+                    .as_ref()
+                    .map_or(0, |performance| performance.rug_rate);
+                range.contains(&rug_rate)
+            }
         }
     }
 }
@@ -0,0 +1,154 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Reconnect delays: doubling from `base` up to `max`, each one randomized
+/// down to half its value. The delays only start over once a connection has
+/// stayed up for `stable_after`, so a server that accepts and then drops right
+/// away keeps being backed off from. Same policy as the server's upstream
+/// connections.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    stable_after: Duration,
+    attempt: u32,
+    connected_at: Option<Instant>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(500), Duration::from_secs(30))
+    }
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            stable_after: Duration::from_secs(10),
+            attempt: 0,
+            connected_at: None,
+        }
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        if let Some(connected_at) = self.connected_at.take() {
+            if connected_at.elapsed() >= self.stable_after {
+                self.attempt = 0;
+            }
+        }
+
+        let ceiling = self
+            .base
+            .saturating_mul(1 << self.attempt.min(16))
+            .min(self.max);
+        self.attempt = self.attempt.saturating_add(1);
+
+        let half = ceiling / 2;
+        half + half.mul_f64(jitter())
+    }
+
+    /// Call once a connection is up again.
+    pub fn connected(&mut self) {
+        self.connected_at = Some(Instant::now());
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Disconnected,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthSnapshot {
+    pub name: String,
+    pub state: ConnectionState,
+    /// Unix milliseconds of the last message received, if any
+    pub last_message_at: Option<u64>,
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+}
+
+struct Status {
+    state: ConnectionState,
+    last_error: Option<String>,
+}
+
+/// Live status of one connection, updated by its loop and read by the ui.
+pub struct ConnectionHealth {
+    name: String,
+    status: Mutex<Status>,
+    last_message_at: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+impl ConnectionHealth {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: Mutex::new(Status {
+                state: ConnectionState::Connecting,
+                last_error: None,
+            }),
+            last_message_at: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+        }
+    }
+
+    pub fn connecting(&self) {
+        self.status.lock().unwrap().state = ConnectionState::Connecting;
+    }
+
+    pub fn connected(&self) {
+        self.status.lock().unwrap().state = ConnectionState::Connected;
+    }
+
+    /// The connection failed or dropped, a reconnect follows.
+    pub fn disconnected(&self, error: impl Display) {
+        let mut status = self.status.lock().unwrap();
+        status.state = ConnectionState::Disconnected;
+        status.last_error = Some(error.to_string());
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn message(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        self.last_message_at.store(now, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HealthSnapshot {
+        let status = self.status.lock().unwrap();
+        let last_message_at = self.last_message_at.load(Ordering::Relaxed);
+
+        HealthSnapshot {
+            name: self.name.clone(),
+            state: status.state,
+            last_message_at: (last_message_at > 0).then_some(last_message_at),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            last_error: status.last_error.clone(),
+        }
+    }
+}
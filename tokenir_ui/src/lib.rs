@@ -1,4 +1,5 @@
 mod token;
 
+pub mod health;
 pub mod migration;
 pub use token::*;
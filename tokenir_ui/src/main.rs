@@ -12,7 +12,7 @@ use dashmap::DashMap;
 use futures_util::{SinkExt, StreamExt};
 use rmp_serde::{decode, encode};
 use serde::{Deserialize, Serialize};
-use tokenir_ui::{Token, migration::PadreClient};
+use tokenir_ui::{Token, health::ConnectionHealth, migration::PadreClient};
 use tokio::sync::{Mutex, mpsc, oneshot};
 use tokio_tungstenite::{
     MaybeTlsStream, WebSocketStream, connect_async, tungstenite::client::IntoClientRequest,
@@ -64,6 +64,10 @@ async fn main() {
 
     let (tx, mut rx) = tokio::sync::watch::channel(String::new());
 
+    // Shown in the top bar, kept across key changes
+    let server_health = Arc::new(ConnectionHealth::new("server"));
+    let padre_health = Arc::new(ConnectionHealth::new("padre"));
+
     tokio::spawn({
         let pool = pool.clone();
         let total = total.clone();
@@ -71,6 +75,8 @@ async fn main() {
         let automata = automata.clone();
         let login_state = is_logged_in.clone();
         let trade_terminal = trade_terminal.clone();
+        let server_health = server_health.clone();
+        let padre_health = padre_health.clone();
 
         async move {
             let mut current: Option<tokio::task::JoinHandle<()>> = None;
@@ -86,7 +92,12 @@ async fn main() {
                 }
 
                 let base = env::var("SERVER").expect("SERVER missing");
-                let client = Client::new(format!("{}?key={}", base, key));
+                // The client reads both, msgpack is smaller
+                let encoding = env::var("SERVER_ENCODING").unwrap_or("msgpack".to_string());
+                let client = Client::new(
+                    format!("{}?key={}&encoding={}", base, key, encoding),
+                    server_health.clone(),
+                );
 
                 current = Some(tokio::spawn(run_subscription(
                     client,
@@ -97,6 +108,7 @@ async fn main() {
                     automata.clone(),
                     login_state.clone(),
                     trade_terminal.clone(),
+                    padre_health.clone(),
                 )));
             }
         }
@@ -117,6 +129,7 @@ async fn main() {
         tx,
         is_logged_in.clone(),
         trade_terminal.clone(),
+        vec![server_health, padre_health],
     );
 
     eframe::run_native("MemeX", options, Box::new(|_| Ok(Box::new(app))));
@@ -132,16 +145,24 @@ async fn run_subscription(
     automata: Arc<Mutex<BuyAutomata>>,
     login_state: Arc<RwLock<bool>>,
     trade_terminal: Arc<RwLock<TradeTerminal>>,
+    padre_health: Arc<ConnectionHealth>,
 ) {
     // Initialize Padre Client for this subscription session
     let padre = Arc::new(
-        PadreClient::new()
+        PadreClient::new(padre_health)
             .await
             .expect("Failed to connect to Padre"),
     );
 
+    let on_update = |update| {
+        let pool = pool.clone();
+        async move { pool.lock().await.update(update) }
+    };
+
+    let subscription = pool.lock().await.subscription();
+
     let _ = client
-        .subscribe(|mut token, autobuy| {
+        .subscribe(subscription, on_update, |mut token, autobuy| {
             let pool = pool.clone();
             let total = total.clone();
             let blacklist = blacklist.clone();
@@ -160,10 +181,20 @@ async fn run_subscription(
                     return;
                 }
 
-                // --- IMPLEMENTATION: WEBSOCKET MIGRATION FETCH ---
-                // Request dev history from Padre and wait for binary response
-                if let Some(history) = padre.get_dev_history(&token.dev.to_string()).await {
+                // The server only counts migrations it saw happen, so a dev
+                // without any there may have older ones only Padre knows of
+                let server = token
+                    .dev_performance
+                    .as_ref()
+                    .filter(|performance| performance.migrated > 0)
+                    .map(|performance| performance.history());
+                if server.is_some() {
+                    token.migrated = server;
+                } else if let Some(history) = padre.get_dev_history(&token.dev.to_string()).await {
+                    // Request dev history from Padre and wait for binary response
                     token.migrated = Some(history);
+                } else if let Some(performance) = &token.dev_performance {
+                    token.migrated = Some(performance.history());
                 }
 
                 let token_clone = token.clone();
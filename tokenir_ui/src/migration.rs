@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::atomic::{AtomicU32, Ordering};
 use std::{env, sync::Arc, time::Duration};
+
+use crate::health::{Backoff, ConnectionHealth};
 use tokio::sync::{mpsc, oneshot};
 use tokio::time::sleep;
 use tokio_tungstenite::{
@@ -34,7 +36,7 @@ pub struct PadreClient {
 }
 
 impl PadreClient {
-    pub async fn new() -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(health: Arc<ConnectionHealth>) -> Result<Self, Box<dyn std::error::Error>> {
         let (tx, mut rx) = mpsc::channel::<Message>(100);
 
         // Fix: Explicit type annotation
@@ -48,12 +50,16 @@ impl PadreClient {
         tokio::spawn(async move {
             let cookie = env::var("PADRE_COOKIE").unwrap_or_default();
             let url = "wss://backend.padre.gg/_heavy_multiplex?desc=%2Ftrade%2Fsolana%2F3f2e2jJ7H5anAQkc1t7qYfapZnd4WbdUavJgBwtUfC3J";
+            let mut backoff = Backoff::default();
 
             loop {
+                health.connecting();
+
                 let mut request = match url.into_client_request() {
                     Ok(r) => r,
-                    Err(_) => {
-                        sleep(Duration::from_secs(1)).await;
+                    Err(e) => {
+                        health.disconnected(&e);
+                        sleep(backoff.next_delay()).await;
                         continue;
                     }
                 };
@@ -63,40 +69,50 @@ impl PadreClient {
                 headers.insert("Origin", "https://trade.padre.gg".parse().unwrap());
                 headers.insert("Cookie", cookie.parse().unwrap());
 
-                if let Ok((ws_stream, _)) = connect_async(request).await {
-                    let (mut ws_writer, mut ws_reader) = ws_stream.split();
-                    loop {
-                        tokio::select! {
-                            Some(msg) = rx.recv() => {
-                                if ws_writer.send(msg).await.is_err() { break; }
-                            }
-                            msg_res = ws_reader.next() => {
-                                match msg_res {
-                                    Some(Ok(Message::Binary(bin))) => {
-                                        if bin.len() <= 2 {
-                                            let _ = loop_tx.send(Message::Binary(bin)).await;
-                                            continue;
-                                        }
-                                        if let Ok(raw_array) = decode::from_slice::<Vec<Value>>(&bin) {
-                                            if raw_array.len() >= 4 {
-                                                if let Some(seq) = raw_array[1].as_u64() {
-                                                    let seq_u32 = seq as u32;
-                                                    if let Some((_, sender)) = pending_clone.remove(&seq_u32) {
-                                                        if let Ok(history) = serde_json::from_value::<CreatorHistory>(raw_array[3].clone()) {
-                                                            let _ = sender.send(history);
+                match connect_async(request).await {
+                    Ok((ws_stream, _)) => {
+                        health.connected();
+                        backoff.connected();
+
+                        let (mut ws_writer, mut ws_reader) = ws_stream.split();
+                        let reason = loop {
+                            tokio::select! {
+                                Some(msg) = rx.recv() => {
+                                    if let Err(e) = ws_writer.send(msg).await { break e.to_string(); }
+                                }
+                                msg_res = ws_reader.next() => {
+                                    match msg_res {
+                                        Some(Ok(Message::Binary(bin))) => {
+                                            health.message();
+                                            if bin.len() <= 2 {
+                                                let _ = loop_tx.send(Message::Binary(bin)).await;
+                                                continue;
+                                            }
+                                            if let Ok(raw_array) = decode::from_slice::<Vec<Value>>(&bin) {
+                                                if raw_array.len() >= 4 {
+                                                    if let Some(seq) = raw_array[1].as_u64() {
+                                                        let seq_u32 = seq as u32;
+                                                        if let Some((_, sender)) = pending_clone.remove(&seq_u32) {
+                                                            if let Ok(history) = serde_json::from_value::<CreatorHistory>(raw_array[3].clone()) {
+                                                                let _ = sender.send(history);
+                                                            }
                                                         }
                                                     }
                                                 }
                                             }
                                         }
+                                        Some(Ok(_)) => break String::from("unexpected message"),
+                                        Some(Err(e)) => break e.to_string(),
+                                        None => break String::from("stream ended"),
                                     }
-                                    _ => break,
                                 }
                             }
-                        }
+                        };
+                        health.disconnected(reason);
                     }
+                    Err(e) => health.disconnected(&e),
                 }
-                sleep(Duration::from_secs(1)).await; // Wait before reconnecting
+                sleep(backoff.next_delay()).await; // Wait before reconnecting
             }
         });
 
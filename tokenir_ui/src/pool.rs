@@ -2,28 +2,76 @@ use std::collections::HashSet;
 
 use crate::filter::FilterSet;
 use solana_sdk::pubkey::Pubkey;
-use tokenir_ui::Token;
+use tokenir_ui::{Token, TokenUpdate};
+use tokio::sync::watch;
+
+const UNSUBSCRIBE: &str = r#"{"type":"unsubscribe"}"#;
 
 pub struct Pool {
     pub feed: Vec<Token>,
     pub feed_check: HashSet<Pubkey>,
     pub filters: FilterSet,
+    /// Whitelisted tokens skip the filters, so the server can't filter for us
+    whitelist_active: bool,
+    /// What the server connection sends to have the feed filtered
+    subscription: watch::Sender<String>,
 }
 
 impl Pool {
     pub fn new() -> Self {
+        let filters = FilterSet::load("view_filters");
+        let (subscription, _) = watch::channel(filters.subscribe_message());
+
         Self {
             feed: vec![],
-            filters: FilterSet::load("view_filters"),
+            filters,
             feed_check: HashSet::new(),
+            whitelist_active: false,
+            subscription,
         }
     }
 
+    pub fn set_filters(&mut self, filters: FilterSet) {
+        self.filters = filters;
+        self.publish_subscription();
+    }
+
+    pub fn set_whitelist_active(&mut self, active: bool) {
+        self.whitelist_active = active;
+        self.publish_subscription();
+    }
+
+    /// The latest subscribe message, changes whenever the filters do.
+    pub fn subscription(&self) -> watch::Receiver<String> {
+        self.subscription.subscribe()
+    }
+
+    fn publish_subscription(&self) {
+        let message = if self.whitelist_active {
+            UNSUBSCRIBE.to_string()
+        } else {
+            self.filters.subscribe_message()
+        };
+        self.subscription.send_replace(message);
+    }
+
     pub fn add(&mut self, token: Token) {
         self.feed_check.insert(token.mint.clone());
         self.feed.push(token);
     }
 
+    /// Applies live numbers to a token in the feed, unknown mints are ignored.
+    pub fn update(&mut self, update: TokenUpdate) {
+        if !self.feed_check.contains(&update.mint) {
+            return;
+        }
+
+        if let Some(token) = self.feed.iter_mut().find(|t| t.mint == update.mint) {
+            token.ath = token.ath.max(update.ath);
+            token.live = Some(update);
+        }
+    }
+
     pub fn clear(&mut self) {
         self.feed_check.clear();
         self.feed.clear();
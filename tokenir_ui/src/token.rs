@@ -5,7 +5,7 @@ use serde::{Deserialize, Serialize};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
 
-use crate::migration::CreatorHistory;
+use crate::migration::{CreatorHistory, Migrated};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Token {
@@ -23,6 +23,23 @@ pub struct Token {
     pub token_2022: bool,
     pub metadata_ipfs: Option<String>,
     pub metadata: Option<Metadata>,
+    /// Latest numbers pushed by the server after creation
+    #[serde(default)]
+    pub live: Option<TokenUpdate>,
+}
+
+/// Live market data the server sends for tokens already received.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenUpdate {
+    pub mint: Pubkey,
+    /// USD
+    pub mcap: u64,
+    /// USD
+    pub ath: u64,
+    pub buys: u64,
+    pub sells: u64,
+    /// Lamports
+    pub volume: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +57,24 @@ pub struct DevPerformance {
     pub average_ath: u64,
     pub last_tokens: Vec<DbToken>,
     pub count: usize,
+    /// How many of the dev's tokens migrated to PumpSwap
+    #[serde(default)]
+    pub migrated: usize,
+    /// Share of the dev's tokens they dumped right after creation, 0..100
+    #[serde(default)]
+    pub rug_rate: u64,
+}
+
+impl DevPerformance {
+    /// Migration counts from the server's own records, in place of Padre's.
+    pub fn history(&self) -> CreatorHistory {
+        CreatorHistory {
+            counts: Migrated {
+                total_count: self.count as u64,
+                migrated_count: self.migrated as u64,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -132,6 +167,7 @@ impl Token {
             token_2022,
             metadata_ipfs,
             metadata,
+            live: None,
         }
     }
 
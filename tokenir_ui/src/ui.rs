@@ -13,7 +13,10 @@ use std::{
         atomic::{AtomicI64, AtomicU64},
     },
 };
-use tokenir_ui::Token;
+use tokenir_ui::{
+    Token,
+    health::{ConnectionHealth, ConnectionState},
+};
 use tokio::sync::{Mutex, watch::Sender};
 
 use crate::{
@@ -48,6 +51,7 @@ pub struct Launcher {
     // Added permission lock
     is_logged_in: Arc<RwLock<bool>>,
     pub trade_terminal: Arc<RwLock<TradeTerminal>>,
+    health: Vec<Arc<ConnectionHealth>>,
 }
 
 enum AppState {
@@ -69,6 +73,7 @@ impl Launcher {
         startup_tx: Sender<String>,
         is_logged_in: Arc<RwLock<bool>>, // New argument,
         trade_terminal: Arc<RwLock<TradeTerminal>>,
+        health: Vec<Arc<ConnectionHealth>>,
     ) -> Self {
         // 1. Try to load key.json
         let loaded_key = if let Ok(mut file) = File::open("key.json") {
@@ -100,6 +105,7 @@ impl Launcher {
                 automata.clone(),
                 config.clone(),
                 trade_terminal.clone(),
+                health.clone(),
             );
             AppState::Running(app)
         } else {
@@ -125,6 +131,7 @@ impl Launcher {
             startup_tx,
             is_logged_in,
             trade_terminal,
+            health,
         }
     }
 }
@@ -194,6 +201,7 @@ impl eframe::App for Launcher {
                                                         self.automata.clone(),
                                                         self.config.clone(),
                                                         self.trade_terminal.clone(),
+                                                        self.health.clone(),
                                                     );
                                                     next_state = Some(AppState::Running(app));
                                                 } else {
@@ -285,6 +293,12 @@ pub struct MyApp {
     pub migration_buy_min: String,
     pub migration_buy_max: String,
 
+    // dev rug rate % fields (expects 0..100 values)
+    pub rug_min: String,
+    pub rug_max: String,
+    pub rug_buy_min: String,
+    pub rug_buy_max: String,
+
     pub filters: FilterSet,
     pub filters_buy: FilterSet,
 
@@ -296,6 +310,7 @@ pub struct MyApp {
     // cached feed so ui can keep showing last known items if lock fails
     pub cached_feed: Vec<Token>,
     pub trade_terminal: Arc<RwLock<TradeTerminal>>,
+    pub health: Vec<Arc<ConnectionHealth>>,
 }
 
 impl MyApp {
@@ -307,6 +322,7 @@ impl MyApp {
         automata: Arc<Mutex<BuyAutomata>>,
         config: Option<AutoBuyConfig>,
         trade_terminal: Arc<RwLock<TradeTerminal>>,
+        health: Vec<Arc<ConnectionHealth>>,
     ) -> Self {
         // если конфиг есть, вытаскиваем значения, иначе пустые строки
         let (sol_input, fee_input, slip_input, bribe_input, filters_buy) =
@@ -338,6 +354,7 @@ impl MyApp {
                 Some(Filters::AverageDevMarketCap(r)) => (r.start.to_string(), r.end.to_string()),
                 Some(Filters::TokenCount(r)) => (r.start.to_string(), r.end.to_string()),
                 Some(Filters::MigrationPercentage(r)) => (r.start.to_string(), r.end.to_string()),
+                Some(Filters::RugRate(r)) => (r.start.to_string(), r.end.to_string()),
                 None => (String::new(), String::new()),
             }
         }
@@ -367,6 +384,9 @@ impl MyApp {
             None => (String::new(), String::new()),
         };
 
+        let (rug_min, rug_max) = range_to_strings(filters.filters.get(&Tag::RugRate));
+        let (rug_buy_min, rug_buy_max) = range_to_strings(filters_buy.filters.get(&Tag::RugRate));
+
         Self {
             pool,
             automata,
@@ -390,6 +410,11 @@ impl MyApp {
             migration_buy_min,
             migration_buy_max,
 
+            rug_min,
+            rug_max,
+            rug_buy_min,
+            rug_buy_max,
+
             filters,
             filters_buy,
 
@@ -400,6 +425,7 @@ impl MyApp {
 
             cached_feed: Vec::new(),
             trade_terminal,
+            health,
             //account_data
         }
     }
@@ -437,6 +463,32 @@ impl TradeTerminal {
     }
 }
 
+fn connection_status(ui: &mut egui::Ui, health: &ConnectionHealth) {
+    let snapshot = health.snapshot();
+    let (text, color) = match snapshot.state {
+        ConnectionState::Connected => ("connected", Color32::GREEN),
+        ConnectionState::Connecting => ("connecting", Color32::YELLOW),
+        ConnectionState::Disconnected => ("reconnecting", Color32::RED),
+    };
+
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let last_message = match snapshot.last_message_at {
+        Some(at) => format!("{}s ago", now.saturating_sub(at) / 1000),
+        None => "never".to_string(),
+    };
+
+    ui.label(RichText::new(format!("{}: {}", snapshot.name, text)).color(color))
+        .on_hover_text(format!(
+            "last message: {}\nreconnects: {}\nlast error: {}",
+            last_message,
+            snapshot.reconnects,
+            snapshot.last_error.as_deref().unwrap_or("-")
+        ));
+}
+
 impl Drop for MyApp {
     fn drop(&mut self) {
         let _ = self.filters.to_file("view_filters");
@@ -473,6 +525,10 @@ impl eframe::App for MyApp {
                     // тоже очистим кэш чтобы не показывать старые данные
                     self.cached_feed.clear();
                 }
+
+                for health in &self.health {
+                    connection_status(ui, health);
+                }
             });
 
             ui.add_space(10.0);
@@ -525,7 +581,7 @@ impl eframe::App for MyApp {
                         );
 
                         if let Ok(mut pool) = self.pool.try_lock() {
-                            pool.filters = self.filters.clone();
+                            pool.set_filters(self.filters.clone());
                         }
                     }
 
@@ -553,7 +609,7 @@ impl eframe::App for MyApp {
                             .add_filter(Tag::TokenCount, Filters::TokenCount(min..max));
 
                         if let Ok(mut pool) = self.pool.try_lock() {
-                            pool.filters = self.filters.clone();
+                            pool.set_filters(self.filters.clone());
                         }
                     }
 
@@ -583,7 +639,35 @@ impl eframe::App for MyApp {
                         );
 
                         if let Ok(mut pool) = self.pool.try_lock() {
-                            pool.filters = self.filters.clone();
+                            pool.set_filters(self.filters.clone());
+                        }
+                    }
+
+                    // --- dev rug rate range ---
+                    ui.add_space(4.0);
+                    ui.label("dev rug rate % range (0 - 100):");
+                    let mut changed_rug = false;
+                    ui.horizontal(|ui| {
+                        ui.label("min:");
+                        if ui.text_edit_singleline(&mut self.rug_min).changed() {
+                            changed_rug = true;
+                        }
+
+                        ui.label("max:");
+                        if ui.text_edit_singleline(&mut self.rug_max).changed() {
+                            changed_rug = true;
+                        }
+                    });
+
+                    if changed_rug {
+                        let min = self.rug_min.parse::<u64>().unwrap_or(0);
+                        let max = self.rug_max.parse::<u64>().unwrap_or(101);
+
+                        self.filters
+                            .add_filter(Tag::RugRate, Filters::RugRate(min..max));
+
+                        if let Ok(mut pool) = self.pool.try_lock() {
+                            pool.set_filters(self.filters.clone());
                         }
                     }
                     if let Ok(mut automata) = self.automata.try_lock()
@@ -683,6 +767,31 @@ impl eframe::App for MyApp {
                             automata.config.params.filters = self.filters_buy.clone();
                         }
 
+                        // --- auto-buy dev rug rate % ---
+                        let mut changed_rug_buy = false;
+                        ui.add_space(4.0);
+                        ui.label("dev rug rate % range (auto-buy, 0 - 100):");
+                        ui.horizontal(|ui| {
+                            ui.label("min:");
+                            if ui.text_edit_singleline(&mut self.rug_buy_min).changed() {
+                                changed_rug_buy = true;
+                            }
+                            ui.label("max:");
+                            if ui.text_edit_singleline(&mut self.rug_buy_max).changed() {
+                                changed_rug_buy = true;
+                            }
+                        });
+
+                        if changed_rug_buy {
+                            let min = self.rug_buy_min.parse::<u64>().unwrap_or(0);
+                            let max = self.rug_buy_max.parse::<u64>().unwrap_or(101);
+
+                            self.filters_buy
+                                .add_filter(Tag::RugRate, Filters::RugRate(min..max));
+
+                            automata.config.params.filters = self.filters_buy.clone();
+                        }
+
                         let mut active = automata.active_twitter;
                         if ui.checkbox(&mut active, "enabled market cap").changed() {
                             automata.active_twitter = active;
@@ -697,6 +806,10 @@ impl eframe::App for MyApp {
                         if ui.checkbox(&mut active, "enabled whitelist").changed() {
                             automata.active_whitelist = active;
                             println!("{}", automata.active_whitelist);
+
+                            if let Ok(mut pool) = self.pool.try_lock() {
+                                pool.set_whitelist_active(active);
+                            }
                         }
 
                         // lamports
@@ -778,6 +891,23 @@ impl eframe::App for MyApp {
                             }
 
                             ui.label(RichText::new(&token.name).italics());
+
+                            if let Some(live) = &token.live {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "mcap: {}$ | ath: {}$",
+                                        fmt.format(live.mcap as f64),
+                                        fmt.format(live.ath as f64)
+                                    ))
+                                    .color(Color32::LIGHT_GREEN),
+                                );
+                                ui.label(format!(
+                                    "buys: {} | sells: {} | vol: {:.2} SOL",
+                                    live.buys,
+                                    live.sells,
+                                    live.volume as f64 / 1_000_000_000.0
+                                ));
+                            }
                         });
 
                         ui.vertical(|ui| {
@@ -870,6 +1000,15 @@ impl eframe::App for MyApp {
                                         .font(FontId::proportional(16.0)),
                                     );
 
+                                    ui.label(
+                                        RichText::new(format!(
+                                            "rug rate: {}%",
+                                            performance.rug_rate
+                                        ))
+                                        .color(Color32::LIGHT_RED)
+                                        .font(FontId::proportional(16.0)),
+                                    );
+
                                     ui.add_space(10.0);
                                     ui.heading("last 3 coins");
 